@@ -0,0 +1,152 @@
+// Modul untuk menghentikan generasi dengan rapi: Ctrl-C (SIGINT) dan batas waktu --max-time / --max-prompt-time
+use anyhow::Result;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// Ditandai oleh handler Ctrl-C dan diperiksa di setiap langkah generasi
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+// Enum untuk alasan generasi berhenti, ditampilkan bersama statistik
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinishReason {
+    Eos,
+    Length,
+    Cancelled,
+    Timeout,
+    Stop,
+    Context,
+}
+
+impl std::fmt::Display for FinishReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::Eos => "eos",
+            Self::Length => "length",
+            Self::Cancelled => "cancelled",
+            Self::Timeout => "timeout",
+            Self::Stop => "stop",
+            Self::Context => "context",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+// Fungsi untuk menandai generasi dihentikan seperti Ctrl-C, mengembalikan true jika sudah ditandai sebelumnya
+pub fn cancel() -> bool {
+    CANCELLED.swap(true, Ordering::SeqCst)
+}
+
+// Fungsi untuk memasang handler Ctrl-C: yang pertama menghentikan generasi setelah token saat ini,
+// yang kedua langsung keluar
+pub fn install_ctrlc_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if cancel() {
+            std::process::exit(130)
+        }
+        eprintln!("\ninterrupted, stopping after the current token (press ctrl-c again to exit)");
+    })?;
+    Ok(())
+}
+
+// Fungsi untuk membaca batas waktu --max-time / --max-prompt-time dalam detik, nilai negatif, NaN, tak hingga atau
+// terlalu besar untuk Duration ditolak saat parsing argumen
+pub fn parse_seconds(s: &str) -> Result<f64, String> {
+    let seconds: f64 = s.parse().map_err(|err| format!("{err}"))?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(_) => Ok(seconds),
+        Err(_) => Err(format!(
+            "{s} is not a valid number of seconds, expected a finite value from 0 to 2^64"
+        )),
+    }
+}
+
+// Fungsi untuk melupakan Ctrl-C sebelumnya, dipakai mode chat agar Ctrl-C hanya menghentikan jawaban yang sedang
+// dihasilkan
+pub fn clear_cancelled() {
+    CANCELLED.store(false, Ordering::SeqCst);
+}
+
+// Struct untuk batas waktu generasi, dihitung sejak Budget dibuat, beserta tanda berhenti karena stop sequence
+#[derive(Debug, Clone)]
+pub struct Budget {
+    start: Instant,
+    max_time: Option<Duration>,
+    max_prompt_time: Option<Duration>,
+    stopped: Cell<bool>,
+}
+
+impl Budget {
+    pub fn new(max_time: Option<f64>, max_prompt_time: Option<f64>) -> Self {
+        Self {
+            start: Instant::now(),
+            max_time: max_time.map(Duration::from_secs_f64),
+            max_prompt_time: max_prompt_time.map(Duration::from_secs_f64),
+            stopped: Cell::new(false),
+        }
+    }
+
+    // Dipanggil dari callback teks saat stop sequence muncul, generasi berhenti sebelum token berikutnya
+    pub fn stop(&self) {
+        self.stopped.set(true);
+    }
+
+    // Diperiksa di setiap langkah generasi, Some berarti generasi harus berhenti
+    pub fn check(&self) -> Option<FinishReason> {
+        if CANCELLED.load(Ordering::SeqCst) {
+            return Some(FinishReason::Cancelled);
+        }
+        if self.stopped.get() {
+            return Some(FinishReason::Stop);
+        }
+        match self.max_time {
+            Some(max_time) if self.start.elapsed() >= max_time => Some(FinishReason::Timeout),
+            _ => None,
+        }
+    }
+
+    // Diperiksa selama atau setelah pemrosesan prompt, juga memperhitungkan --max-prompt-time
+    pub fn check_prompt(&self) -> Option<FinishReason> {
+        match self.max_prompt_time {
+            Some(max_time) if self.start.elapsed() >= max_time => Some(FinishReason::Timeout),
+            _ => self.check(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ctrl-C memakai flag global yang juga dibaca test lain yang berjalan paralel, sehingga diuji terpisah di
+    // tests/cancel.rs
+
+    #[test]
+    fn timeouts_and_stop_sequences_finish_generation() {
+        let budget = Budget::new(None, None);
+        assert_eq!(budget.check(), None);
+        assert_eq!(budget.check_prompt(), None);
+        budget.stop();
+        assert_eq!(budget.check(), Some(FinishReason::Stop));
+        assert_eq!(budget.check_prompt(), Some(FinishReason::Stop));
+
+        // Batas 0 detik langsung habis
+        let budget = Budget::new(Some(0.), None);
+        assert_eq!(budget.check(), Some(FinishReason::Timeout));
+        assert_eq!(budget.check_prompt(), Some(FinishReason::Timeout));
+
+        // --max-prompt-time hanya berlaku untuk check_prompt
+        let budget = Budget::new(Some(3600.), Some(0.));
+        assert_eq!(budget.check(), None);
+        assert_eq!(budget.check_prompt(), Some(FinishReason::Timeout));
+    }
+
+    #[test]
+    fn invalid_seconds_are_rejected() {
+        assert_eq!(parse_seconds("1.5"), Ok(1.5));
+        assert_eq!(parse_seconds("0"), Ok(0.));
+        for value in ["-1", "NaN", "inf", "1e30", "abc"] {
+            assert!(parse_seconds(value).is_err(), "{value}");
+        }
+    }
+}
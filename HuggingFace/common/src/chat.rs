@@ -0,0 +1,299 @@
+// Modul untuk merender daftar pesan (system/user/assistant) menjadi prompt dengan template chat
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Struct untuk satu pesan dalam percakapan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+// Enum untuk template chat bawaan yang bisa dipakai jika tokenizer_config.json tidak memiliki chat_template
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum BuiltinTemplate {
+    Chatml,
+    MistralInst,
+    Rwkv,
+    Alpaca,
+    Vicuna,
+}
+
+impl BuiltinTemplate {
+    // Sumber template jinja untuk setiap template bawaan
+    fn source(&self) -> &'static str {
+        match self {
+            Self::Chatml => concat!(
+                "{% for message in messages %}",
+                "{{ '<|im_start|>' + message['role'] + '\\n' + message['content'] + '<|im_end|>\\n' }}",
+                "{% endfor %}",
+                "{% if add_generation_prompt %}{{ '<|im_start|>assistant\\n' }}{% endif %}",
+            ),
+            Self::MistralInst => concat!(
+                "{% if messages[0]['role'] == 'system' %}",
+                "{% set system = messages[0]['content'] + '\\n\\n' %}{% set loop_messages = messages[1:] %}",
+                "{% else %}{% set system = '' %}{% set loop_messages = messages %}{% endif %}",
+                "{{ bos_token }}{% for message in loop_messages %}",
+                "{% if message['role'] == 'user' %}",
+                "{% if loop.first %}{{ '[INST] ' + system + message['content'] + ' [/INST]' }}",
+                "{% else %}{{ '[INST] ' + message['content'] + ' [/INST]' }}{% endif %}",
+                "{% else %}{{ message['content'] + eos_token }}{% endif %}",
+                "{% endfor %}",
+            ),
+            Self::Rwkv => concat!(
+                "{% for message in messages %}",
+                "{% set content = message['content'] | trim | replace('\\n\\n', '\\n') %}",
+                "{% if message['role'] == 'system' %}{{ 'System: ' + content + '\\n\\n' }}",
+                "{% elif message['role'] == 'user' %}{{ 'User: ' + content + '\\n\\n' }}",
+                "{% else %}{{ 'Assistant: ' + content + '\\n\\n' }}{% endif %}",
+                "{% endfor %}",
+                "{% if add_generation_prompt %}{{ 'Assistant:' }}{% endif %}",
+            ),
+            Self::Alpaca => concat!(
+                "{% for message in messages %}",
+                "{% if message['role'] == 'system' %}{{ message['content'] + '\\n\\n' }}",
+                "{% elif message['role'] == 'user' %}{{ '### Instruction:\\n' + message['content'] + '\\n\\n' }}",
+                "{% else %}{{ '### Response:\\n' + message['content'] + eos_token + '\\n\\n' }}{% endif %}",
+                "{% endfor %}",
+                "{% if add_generation_prompt %}{{ '### Response:\\n' }}{% endif %}",
+            ),
+            Self::Vicuna => concat!(
+                "{% for message in messages %}",
+                "{% if message['role'] == 'system' %}{{ message['content'] + '\\n' }}",
+                "{% elif message['role'] == 'user' %}{{ 'USER: ' + message['content'] + '\\n' }}",
+                "{% else %}{{ 'ASSISTANT: ' + message['content'] + eos_token + '\\n' }}{% endif %}",
+                "{% endfor %}",
+                "{% if add_generation_prompt %}{{ 'ASSISTANT:' }}{% endif %}",
+            ),
+        }
+    }
+}
+
+// Struct untuk template chat yang siap dirender
+#[derive(Debug, Clone)]
+pub struct ChatTemplate {
+    source: String,
+    eos_token: String,
+}
+
+// Fungsi untuk mengambil string dari token di tokenizer_config.json (bisa berupa string atau objek {content})
+fn token_content(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Object(o) => o.get("content").and_then(|v| v.as_str()).map(String::from),
+        _ => None,
+    }
+}
+
+impl ChatTemplate {
+    pub fn builtin(template: BuiltinTemplate, eos_token: &str) -> Self {
+        Self {
+            source: template.source().to_string(),
+            eos_token: eos_token.to_string(),
+        }
+    }
+
+    // Membaca chat_template dari tokenizer_config.json, None jika file tidak memiliki template
+    pub fn from_tokenizer_config(path: &Path, eos_token: &str) -> Result<Option<Self>> {
+        let config: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        let eos_token = config
+            .get("eos_token")
+            .and_then(token_content)
+            .unwrap_or_else(|| eos_token.to_string());
+        // chat_template bisa berupa string atau daftar {name, template}
+        let source = match config.get("chat_template") {
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(serde_json::Value::Array(templates)) => templates
+                .iter()
+                .find(|t| t.get("name").and_then(|n| n.as_str()) == Some("default"))
+                .or_else(|| templates.first())
+                .and_then(|t| t.get("template"))
+                .and_then(|t| t.as_str())
+                .map(String::from),
+            _ => None,
+        };
+        Ok(source.map(|source| Self { source, eos_token }))
+    }
+
+    // Merender pesan menjadi prompt, bos_token dikosongkan karena tokenizer sudah menambahkannya saat encode
+    pub fn render(&self, messages: &[Message], add_generation_prompt: bool) -> Result<String> {
+        let mut env = minijinja::Environment::new();
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_function(
+            "raise_exception",
+            |msg: String| -> Result<String, minijinja::Error> {
+                Err(minijinja::Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    msg,
+                ))
+            },
+        );
+        let prompt = env.render_str(
+            &self.source,
+            minijinja::context! {
+                messages => messages,
+                add_generation_prompt => add_generation_prompt,
+                bos_token => "",
+                eos_token => self.eos_token,
+            },
+        )?;
+        Ok(prompt)
+    }
+}
+
+// Fungsi untuk memilih template: argumen --chat-template (nama bawaan atau file jinja),
+// lalu chat_template dari tokenizer_config.json, lalu template bawaan model
+pub fn resolve(
+    arg: Option<&str>,
+    tokenizer_config: Option<&Path>,
+    default: BuiltinTemplate,
+    eos_token: &str,
+) -> Result<ChatTemplate> {
+    if let Some(arg) = arg {
+        if let Ok(template) = BuiltinTemplate::from_str(arg, true) {
+            return Ok(ChatTemplate::builtin(template, eos_token));
+        }
+        let source = std::fs::read_to_string(arg).map_err(|err| {
+            anyhow::anyhow!("chat template {arg} is neither a builtin nor a readable file: {err}")
+        })?;
+        return Ok(ChatTemplate {
+            source,
+            eos_token: eos_token.to_string(),
+        });
+    }
+    if let Some(path) = tokenizer_config {
+        if let Some(template) = ChatTemplate::from_tokenizer_config(path, eos_token)? {
+            return Ok(template);
+        }
+    }
+    Ok(ChatTemplate::builtin(default, eos_token))
+}
+
+// Fungsi untuk membaca daftar pesan dari file JSON
+pub fn load_messages<P: AsRef<Path>>(path: P) -> Result<Vec<Message>> {
+    let messages: Vec<Message> = serde_json::from_slice(&std::fs::read(path)?)?;
+    if messages.is_empty() {
+        anyhow::bail!("the messages file does not contain any message")
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    fn messages() -> Vec<Message> {
+        [
+            ("system", "Jawab singkat."),
+            ("user", "Ibu kota Indonesia?"),
+            ("assistant", "Jakarta."),
+            ("user", "Kalau Jepang?"),
+        ]
+        .into_iter()
+        .map(|(role, content)| Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        })
+        .collect()
+    }
+
+    fn render(template: BuiltinTemplate) -> String {
+        ChatTemplate::builtin(template, "</s>")
+            .render(&messages(), true)
+            .unwrap()
+    }
+
+    #[test]
+    fn chatml_is_rendered() {
+        assert_eq!(
+            render(BuiltinTemplate::Chatml),
+            "<|im_start|>system\nJawab singkat.<|im_end|>\n\
+             <|im_start|>user\nIbu kota Indonesia?<|im_end|>\n\
+             <|im_start|>assistant\nJakarta.<|im_end|>\n\
+             <|im_start|>user\nKalau Jepang?<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn mistral_inst_is_rendered() {
+        assert_eq!(
+            render(BuiltinTemplate::MistralInst),
+            "[INST] Jawab singkat.\n\nIbu kota Indonesia? [/INST]Jakarta.</s>[INST] Kalau Jepang? [/INST]"
+        );
+    }
+
+    #[test]
+    fn rwkv_is_rendered() {
+        assert_eq!(
+            render(BuiltinTemplate::Rwkv),
+            "System: Jawab singkat.\n\nUser: Ibu kota Indonesia?\n\n\
+             Assistant: Jakarta.\n\nUser: Kalau Jepang?\n\nAssistant:"
+        );
+    }
+
+    #[test]
+    fn alpaca_is_rendered() {
+        assert_eq!(
+            render(BuiltinTemplate::Alpaca),
+            "Jawab singkat.\n\n### Instruction:\nIbu kota Indonesia?\n\n\
+             ### Response:\nJakarta.</s>\n\n### Instruction:\nKalau Jepang?\n\n### Response:\n"
+        );
+    }
+
+    #[test]
+    fn vicuna_is_rendered() {
+        assert_eq!(
+            render(BuiltinTemplate::Vicuna),
+            "Jawab singkat.\nUSER: Ibu kota Indonesia?\nASSISTANT: Jakarta.</s>\n\
+             USER: Kalau Jepang?\nASSISTANT:"
+        );
+    }
+
+    #[test]
+    fn template_is_read_from_tokenizer_config() -> Result<()> {
+        let dir = TempDir::new("chat-template")?;
+        let message = [Message {
+            role: "user".to_string(),
+            content: "halo".to_string(),
+        }];
+
+        // chat_template berupa string, eos_token berupa objek {content}
+        let path = dir.join("string.json");
+        std::fs::write(
+            &path,
+            r#"{"chat_template": "{{ messages[0]['content'] }}{{ eos_token }}", "eos_token": {"content": "<eos>"}}"#,
+        )?;
+        let template = ChatTemplate::from_tokenizer_config(&path, "</s>")?.unwrap();
+        assert_eq!(template.render(&message, false)?, "halo<eos>");
+
+        // chat_template berupa daftar {name, template}, yang bernama default dipilih
+        let path = dir.join("list.json");
+        std::fs::write(
+            &path,
+            r#"{"chat_template": [{"name": "tool_use", "template": "tool"}, {"name": "default", "template": "[{{ messages[0]['content'] }}]"}]}"#,
+        )?;
+        let template = ChatTemplate::from_tokenizer_config(&path, "</s>")?.unwrap();
+        assert_eq!(template.render(&message, false)?, "[halo]");
+
+        // Urutan prioritas: argumen, lalu tokenizer_config.json, lalu template bawaan
+        let resolved = resolve(Some("vicuna"), Some(&path), BuiltinTemplate::Chatml, "</s>")?;
+        assert_eq!(resolved.render(&message, true)?, "USER: halo\nASSISTANT:");
+        let resolved = resolve(None, Some(&path), BuiltinTemplate::Chatml, "</s>")?;
+        assert_eq!(resolved.render(&message, false)?, "[halo]");
+
+        // Tanpa chat_template, template bawaan dipakai
+        let path = dir.join("empty.json");
+        std::fs::write(&path, r#"{"eos_token": "</s>"}"#)?;
+        assert!(ChatTemplate::from_tokenizer_config(&path, "</s>")?.is_none());
+        let resolved = resolve(None, Some(&path), BuiltinTemplate::Chatml, "</s>")?;
+        assert_eq!(
+            resolved.render(&message, true)?,
+            "<|im_start|>user\nhalo<|im_end|>\n<|im_start|>assistant\n"
+        );
+        Ok(())
+    }
+}
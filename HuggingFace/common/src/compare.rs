@@ -0,0 +1,321 @@
+// Modul untuk subcommand compare: dua file hasil eval per soal (dari eval --output atau --mmlu-output) dibandingkan
+// per subjek dengan interval kepercayaan bootstrap berpasangan, soal yang berubah benar/salah dicetak, dan perintah
+// gagal jika akurasi turun lebih dari batas yang diberikan
+use anyhow::Result;
+use clap::{Args as ClapArgs, ValueEnum};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+// Enum untuk metrik yang dibandingkan
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum Metric {
+    /// `correct_norm` when every result has it, `correct` otherwise.
+    Auto,
+    /// The answer with the highest log-likelihood (`correct`).
+    Acc,
+    /// The answer with the highest log-likelihood per byte (`correct_norm`).
+    AccNorm,
+}
+
+// Argumen untuk subcommand compare
+#[derive(ClapArgs, Debug)]
+pub struct CompareArgs {
+    /// Per-question results of the baseline run (JSONL).
+    baseline: PathBuf,
+
+    /// Per-question results of the candidate run (JSONL).
+    candidate: PathBuf,
+
+    /// The metric to compare.
+    #[arg(long, value_enum, default_value = "auto")]
+    metric: Metric,
+
+    /// Fail when the total accuracy of the candidate is lower than the baseline by more than this
+    /// (as a fraction, e.g. 0.01 for one point).
+    #[arg(long)]
+    max_drop: Option<f64>,
+
+    /// The number of bootstrap resamples used for the confidence intervals.
+    #[arg(long, default_value_t = 1000)]
+    bootstrap: usize,
+
+    /// The seed of the bootstrap resampling.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// The maximum number of flipped questions printed in each direction.
+    #[arg(long, default_value_t = 20)]
+    show_flips: usize,
+}
+
+// Struct untuk satu baris hasil per soal, field lain diabaikan
+#[derive(Debug, Deserialize)]
+struct ResultLine {
+    id: Value,
+    #[serde(default)]
+    subject: String,
+    correct: bool,
+    #[serde(default)]
+    correct_norm: Option<bool>,
+}
+
+// Struct untuk hasil per soal yang sudah dibaca: kunci (subjek, id), benar menurut acc dan acc_norm
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub subject: String,
+    pub id: String,
+    pub correct: bool,
+    pub correct_norm: Option<bool>,
+}
+
+// Fungsi untuk membaca file hasil per soal
+pub fn load_outcomes(path: &Path) -> Result<Vec<Outcome>> {
+    let mut outcomes = vec![];
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let l: ResultLine = serde_json::from_str(line)
+            .map_err(|err| anyhow::anyhow!("{path:?} line {}: {err}", index + 1))?;
+        let id = match l.id {
+            Value::String(s) => s,
+            v => v.to_string(),
+        };
+        outcomes.push(Outcome {
+            subject: l.subject,
+            id,
+            correct: l.correct,
+            correct_norm: l.correct_norm,
+        });
+    }
+    if outcomes.is_empty() {
+        anyhow::bail!("no results in {path:?}")
+    }
+    Ok(outcomes)
+}
+
+// Generator acak splitmix64 kecil agar bootstrap bisa diulang dengan seed yang sama (juga dipakai fixture test)
+pub struct SplitMix(pub u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // Bilangan acak uniform di [0, 1), untuk fixture test di crate ini dan di binary
+    pub fn uniform(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// Struct untuk perbandingan satu subjek (atau total)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub total: usize,
+    pub baseline: f64,
+    pub candidate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Delta {
+    pub fn delta(&self) -> f64 {
+        self.candidate - self.baseline
+    }
+}
+
+// Fungsi untuk menghitung selisih akurasi dengan interval kepercayaan 95% dari bootstrap berpasangan: soal yang sama
+// diambil ulang dari kedua run sekaligus
+fn paired_delta(pairs: &[(bool, bool)], samples: usize, rng: &mut SplitMix) -> Delta {
+    let n = pairs.len().max(1) as f64;
+    let baseline = pairs.iter().filter(|(b, _)| *b).count() as f64 / n;
+    let candidate = pairs.iter().filter(|(_, c)| *c).count() as f64 / n;
+    let mut deltas = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let mut sum = 0i64;
+        for _ in 0..pairs.len() {
+            let (b, c) = pairs[rng.below(pairs.len())];
+            sum += c as i64 - b as i64;
+        }
+        deltas.push(sum as f64 / n);
+    }
+    deltas.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: f64| match deltas.len() {
+        0 => candidate - baseline,
+        len => deltas[((len - 1) as f64 * p).round() as usize],
+    };
+    Delta {
+        total: pairs.len(),
+        baseline,
+        candidate,
+        low: percentile(0.025),
+        high: percentile(0.975),
+    }
+}
+
+// Fungsi untuk menulis satu baris tabel perbandingan
+fn row(name: &str, delta: &Delta) -> String {
+    format!(
+        "{name:<32} {:>6} {:>9.4} {:>9.4} {:>+8.4} [{:+.4}, {:+.4}]",
+        delta.total,
+        delta.baseline,
+        delta.candidate,
+        delta.delta(),
+        delta.low,
+        delta.high
+    )
+}
+
+// Fungsi untuk menjalankan subcommand compare, error dikembalikan jika akurasi turun melewati --max-drop
+pub fn run(args: &CompareArgs) -> Result<()> {
+    let baseline = load_outcomes(&args.baseline)?;
+    let candidate = load_outcomes(&args.candidate)?;
+    let norm = match args.metric {
+        Metric::Acc => false,
+        Metric::AccNorm => true,
+        Metric::Auto => baseline
+            .iter()
+            .chain(candidate.iter())
+            .all(|o| o.correct_norm.is_some()),
+    };
+    let value = |o: &Outcome| -> Result<bool> {
+        match (norm, o.correct_norm) {
+            (false, _) => Ok(o.correct),
+            (true, Some(correct_norm)) => Ok(correct_norm),
+            (true, None) => anyhow::bail!("{}/{} has no correct_norm", o.subject, o.id),
+        }
+    };
+    println!("metric: {}", if norm { "acc_norm" } else { "acc" });
+
+    // Soal dipasangkan dengan kunci (subjek, id), soal yang hanya ada di satu file dilewati
+    let mut candidate_by_key = HashMap::new();
+    for o in candidate.iter() {
+        candidate_by_key.insert((o.subject.as_str(), o.id.as_str()), o);
+    }
+    let mut subjects: BTreeMap<&str, Vec<(bool, bool)>> = BTreeMap::new();
+    let mut all = vec![];
+    let (mut lost, mut gained) = (vec![], vec![]);
+    for b in baseline.iter() {
+        let c = match candidate_by_key.remove(&(b.subject.as_str(), b.id.as_str())) {
+            Some(c) => c,
+            None => continue,
+        };
+        let pair = (value(b)?, value(c)?);
+        match pair {
+            (true, false) => lost.push(b),
+            (false, true) => gained.push(b),
+            _ => {}
+        }
+        subjects.entry(b.subject.as_str()).or_default().push(pair);
+        all.push(pair);
+    }
+    let unmatched = baseline.len() - all.len() + candidate_by_key.len();
+    if all.is_empty() {
+        anyhow::bail!("no questions in common between the two result files")
+    }
+    if unmatched > 0 {
+        println!("warning: {unmatched} questions appear in only one of the files and are skipped");
+    }
+
+    let mut rng = SplitMix(args.seed);
+    println!(
+        "{:<32} {:>6} {:>9} {:>9} {:>8} 95% CI",
+        "subject", "n", "baseline", "candidate", "delta"
+    );
+    if subjects.len() > 1 {
+        for (subject, pairs) in subjects.iter() {
+            println!(
+                "{}",
+                row(subject, &paired_delta(pairs, args.bootstrap, &mut rng))
+            );
+        }
+    }
+    let total = paired_delta(&all, args.bootstrap, &mut rng);
+    println!("{}", row("total", &total));
+
+    for (name, flips) in [("lost", &lost), ("gained", &gained)] {
+        println!("{name}: {} questions", flips.len());
+        for o in flips.iter().take(args.show_flips) {
+            println!("  {}/{}", o.subject, o.id);
+        }
+        if flips.len() > args.show_flips {
+            println!("  ... and {} more", flips.len() - args.show_flips);
+        }
+    }
+
+    if let Some(max_drop) = args.max_drop {
+        if -total.delta() > max_drop {
+            anyhow::bail!(
+                "accuracy dropped by {:.4} ({:.4} -> {:.4}), more than --max-drop {max_drop}",
+                -total.delta(),
+                total.baseline,
+                total.candidate
+            )
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootstrap_interval_contains_the_delta() {
+        let mut pairs = vec![(true, true); 60];
+        pairs.extend(vec![(true, false); 15]);
+        pairs.extend(vec![(false, true); 5]);
+        pairs.extend(vec![(false, false); 20]);
+        let delta = paired_delta(&pairs, 500, &mut SplitMix(7));
+        assert_eq!(delta.total, 100);
+        assert!((delta.baseline - 0.75).abs() < 1e-9);
+        assert!((delta.candidate - 0.65).abs() < 1e-9);
+        assert!(delta.low <= delta.delta() && delta.delta() <= delta.high);
+        assert!(delta.high < 0.);
+
+        // Tanpa soal yang berubah intervalnya nol
+        let same = paired_delta(&[(true, true), (false, false)], 100, &mut SplitMix(7));
+        assert_eq!((same.low, same.high), (0., 0.));
+    }
+
+    #[test]
+    fn max_drop_fails_on_regression() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("compare-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let write = |name: &str, correct: [bool; 4]| -> Result<PathBuf> {
+            let path = dir.join(name);
+            let lines: Vec<String> = correct
+                .iter()
+                .enumerate()
+                .map(|(i, c)| format!(r#"{{"id": {i}, "subject": "s", "correct": {c}}}"#))
+                .collect();
+            std::fs::write(&path, lines.join("\n"))?;
+            Ok(path)
+        };
+        let args = |max_drop: f64| -> Result<CompareArgs> {
+            Ok(CompareArgs {
+                baseline: write("baseline.jsonl", [true, true, true, false])?,
+                candidate: write("candidate.jsonl", [true, false, true, false])?,
+                metric: Metric::Auto,
+                max_drop: Some(max_drop),
+                bootstrap: 10,
+                seed: 0,
+                show_flips: 5,
+            })
+        };
+        assert!(run(&args(0.5)?).is_ok());
+        assert!(run(&args(0.1)?).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
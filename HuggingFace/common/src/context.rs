@@ -0,0 +1,155 @@
+// Modul untuk batas jendela konteks model: pemotongan prompt (--truncate) dan kebijakan saat generasi melewati batas (--context-overflow)
+use anyhow::Result;
+use clap::ValueEnum;
+use std::path::Path;
+
+// Enum untuk cara memotong prompt yang lebih panjang dari jendela konteks
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum Truncate {
+    /// Drop the oldest tokens and keep the end of the prompt.
+    Left,
+    /// Drop the newest tokens and keep the start of the prompt.
+    Right,
+    /// Fail when the prompt does not fit.
+    Error,
+}
+
+// Enum untuk kebijakan saat generasi mencapai batas jendela konteks
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum Overflow {
+    /// Stop generating with finish reason `context`.
+    Stop,
+    /// Keep the last half of the window, clear the kv cache and prefill it again.
+    Shift,
+}
+
+// Struct untuk jendela konteks beserta kebijakannya
+#[derive(Debug, Clone, Copy)]
+pub struct ContextWindow {
+    pub size: usize,
+    pub truncate: Truncate,
+    pub overflow: Overflow,
+}
+
+// Fungsi untuk membaca panjang jendela konteks dari config.json: max_position_embeddings (atau n_positions),
+// dibatasi oleh sliding_window jika ada
+pub fn size_from_config(path: &Path) -> Result<usize> {
+    let config: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    let positions = config
+        .get("max_position_embeddings")
+        .or_else(|| config.get("n_positions"))
+        .and_then(|v| v.as_u64());
+    let positions = match positions {
+        Some(positions) => positions as usize,
+        None => anyhow::bail!("no max_position_embeddings or n_positions in {path:?}"),
+    };
+    match config.get("sliding_window").and_then(|v| v.as_u64()) {
+        Some(window) => Ok(positions.min(window as usize)),
+        None => Ok(positions),
+    }
+}
+
+impl ContextWindow {
+    // Memotong prompt agar muat di jendela konteks dengan menyisakan tempat untuk minimal satu token baru
+    pub fn truncate_prompt(&self, tokens: Vec<u32>) -> Result<Vec<u32>> {
+        let max_len = self.size.saturating_sub(1);
+        if tokens.len() <= max_len {
+            return Ok(tokens);
+        }
+        if self.truncate == Truncate::Error {
+            anyhow::bail!(
+                "prompt has {} tokens but the context window is {}, use --truncate left|right",
+                tokens.len(),
+                self.size
+            )
+        }
+        log!(
+            "prompt has {} tokens but the context window is {}, truncating ({:?})",
+            tokens.len(),
+            self.size,
+            self.truncate
+        );
+        match self.truncate {
+            Truncate::Right => Ok(tokens[..max_len].to_vec()),
+            _ => Ok(tokens[tokens.len() - max_len..].to_vec()),
+        }
+    }
+
+    // Jumlah token yang disimpan saat jendela digeser
+    pub fn shift_keep(&self) -> usize {
+        self.size / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    fn window(size: usize, truncate: Truncate) -> ContextWindow {
+        ContextWindow {
+            size,
+            truncate,
+            overflow: Overflow::Stop,
+        }
+    }
+
+    #[test]
+    fn prompts_are_truncated_to_leave_room_for_one_token() -> Result<()> {
+        let tokens = (0..8).collect::<Vec<u32>>();
+        // Prompt yang muat tidak diubah oleh mode apa pun
+        for truncate in [Truncate::Left, Truncate::Right, Truncate::Error] {
+            assert_eq!(window(9, truncate).truncate_prompt(tokens.clone())?, tokens);
+        }
+        assert_eq!(
+            window(5, Truncate::Left).truncate_prompt(tokens.clone())?,
+            [4, 5, 6, 7]
+        );
+        assert_eq!(
+            window(5, Truncate::Right).truncate_prompt(tokens.clone())?,
+            [0, 1, 2, 3]
+        );
+        let err = window(8, Truncate::Error)
+            .truncate_prompt(tokens)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("prompt has 8 tokens but the context window is 8"),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn size_is_read_from_config() -> Result<()> {
+        let dir = TempDir::new("context")?;
+        let path = dir.join("config.json");
+        let size = |config: &str| -> Result<usize> {
+            std::fs::write(&path, config)?;
+            size_from_config(&path)
+        };
+        assert_eq!(size(r#"{"max_position_embeddings": 2048}"#)?, 2048);
+        assert_eq!(size(r#"{"n_positions": 1024}"#)?, 1024);
+        // sliding_window membatasi jendela hanya jika lebih kecil, null berarti tidak ada batas
+        assert_eq!(
+            size(r#"{"max_position_embeddings": 32768, "sliding_window": 4096}"#)?,
+            4096
+        );
+        assert_eq!(
+            size(r#"{"max_position_embeddings": 2048, "sliding_window": 4096}"#)?,
+            2048
+        );
+        assert_eq!(
+            size(r#"{"max_position_embeddings": 2048, "sliding_window": null}"#)?,
+            2048
+        );
+        assert!(size(r#"{"hidden_size": 64}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn shift_keeps_half_of_the_window() {
+        assert_eq!(window(4096, Truncate::Error).shift_keep(), 2048);
+        assert_eq!(window(7, Truncate::Error).shift_keep(), 3);
+    }
+}
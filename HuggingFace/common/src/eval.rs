@@ -0,0 +1,682 @@
+// Modul untuk subcommand eval: soal pilihan ganda dibaca dari file JSONL (format umum, ARC, HellaSwag, PIQA atau COPA)
+// atau CSV IndoMMLU, setiap pilihan dinilai dengan log-likelihood teks jawaban lengkap setelah prompt lewat Backend
+// yang sama dengan mode --worker, lalu dinormalisasi dengan panjang jawaban dalam byte agar jawaban yang panjang tidak
+// dirugikan
+use anyhow::Result;
+use clap::{Args as ClapArgs, ValueEnum};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::indommlu::{self, PromptTemplate};
+use crate::journal::{self, Journal};
+use crate::worker::Backend;
+
+// Enum untuk format file task yang didukung
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum TaskFormat {
+    /// `{"query", "choices", "gold"}` with optional `id` and `subject`, the choices are appended
+    /// to the query as is.
+    Generic,
+    /// ARC as exported from the Hugging Face dataset: `question`, `choices.text`,
+    /// `choices.label` and `answerKey`.
+    Arc,
+    /// HellaSwag: `activity_label`, `ctx_a` and `ctx_b` (or `ctx`), `endings` and `label`.
+    Hellaswag,
+    /// PIQA: `goal`, `sol1`, `sol2` and `label`.
+    Piqa,
+    /// COPA: `premise`, `choice1`, `choice2`, `question` (`cause` or `effect`) and `label`.
+    Copa,
+    /// IndoMMLU-style CSV with a header row: `soal` (question), `pilihan` (options, one per line
+    /// or a Python list) or `A`, `B`, ... columns, `jawaban` (answer letter) and optional
+    /// `subject`, `level`, `kelas` and `is_for_fewshot`. The number of choices can differ per
+    /// question and every choice is scored as its letter.
+    Indommlu,
+}
+
+// Argumen untuk subcommand eval
+#[derive(ClapArgs, Debug)]
+pub struct EvalArgs {
+    /// JSONL task file with one question per line.
+    #[arg(long)]
+    task: PathBuf,
+
+    /// The format of the task file (and of the few-shot file).
+    #[arg(long, value_enum, default_value = "generic")]
+    format: TaskFormat,
+
+    /// The prompt template of --format indommlu: the intro sentence of every subject and the
+    /// answer cue ("Answer:" or "Jawaban:").
+    #[arg(long, value_enum, default_value = "indonesian")]
+    template: PromptTemplate,
+
+    /// The number of few-shot examples put before every question. With --format indommlu the
+    /// examples are taken from the rows marked is_for_fewshot of the same subject.
+    #[arg(long, default_value_t = 0)]
+    shots: usize,
+
+    /// JSONL file the few-shot examples are taken from, in the same format as the task. Defaults
+    /// to the first questions of the task file, which are then not evaluated.
+    #[arg(long)]
+    fewshot_file: Option<PathBuf>,
+
+    /// Only evaluate the first questions of the task file.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// JSONL file where the per-question results (log-likelihoods and predictions) are written.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// JSONL journal where every result is appended as soon as the question is scored, keyed by
+    /// the task file and row with a hash of the model and prompt template.
+    #[arg(long)]
+    journal: Option<PathBuf>,
+
+    /// Continue an interrupted run: the questions already in --journal with the same model and
+    /// prompt template are not scored again.
+    #[arg(long, requires = "journal")]
+    resume: bool,
+}
+
+// Struct untuk satu soal: prompt, pilihan jawaban (sudah dengan pemisah di depannya) dan indeks jawaban benar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: String,
+    pub subject: String,
+    pub level: String,
+    pub query: String,
+    pub choices: Vec<String>,
+    pub gold: usize,
+}
+
+// Struct untuk hasil penilaian satu soal: log-likelihood dan jumlah token setiap pilihan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scored {
+    pub logprobs: Vec<f64>,
+    pub tokens: Vec<usize>,
+}
+
+// Fungsi untuk mencari indeks nilai terbesar
+fn argmax(values: impl Iterator<Item = f64>) -> usize {
+    let mut best = (0, f64::NEG_INFINITY);
+    for (index, value) in values.enumerate() {
+        if value > best.1 {
+            best = (index, value);
+        }
+    }
+    best.0
+}
+
+impl Scored {
+    // Pilihan dengan log-likelihood total terbesar
+    pub fn prediction(&self) -> usize {
+        argmax(self.logprobs.iter().copied())
+    }
+
+    // Pilihan dengan log-likelihood per byte teks jawaban terbesar
+    pub fn normalized_prediction(&self, item: &Item) -> usize {
+        let normalized = self
+            .logprobs
+            .iter()
+            .zip(item.choices.iter())
+            .map(|(logprob, choice)| logprob / choice.len().max(1) as f64);
+        argmax(normalized)
+    }
+}
+
+// Struct untuk baris task format umum
+#[derive(Debug, Deserialize)]
+struct GenericLine {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    subject: Option<String>,
+    query: String,
+    choices: Vec<String>,
+    gold: usize,
+}
+
+// Struct untuk pilihan ARC dari dataset Hugging Face
+#[derive(Debug, Deserialize)]
+struct ArcChoices {
+    text: Vec<String>,
+    label: Vec<String>,
+}
+
+// Struct untuk baris task ARC
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArcLine {
+    #[serde(default)]
+    id: Option<String>,
+    question: String,
+    choices: ArcChoices,
+    answer_key: String,
+}
+
+// Struct untuk baris task HellaSwag
+#[derive(Debug, Deserialize)]
+struct HellaswagLine {
+    #[serde(default)]
+    ind: Option<Value>,
+    #[serde(default)]
+    activity_label: String,
+    #[serde(default)]
+    ctx_a: Option<String>,
+    #[serde(default)]
+    ctx_b: Option<String>,
+    #[serde(default)]
+    ctx: Option<String>,
+    endings: Vec<String>,
+    label: Value,
+}
+
+// Struct untuk baris task PIQA
+#[derive(Debug, Deserialize)]
+struct PiqaLine {
+    goal: String,
+    sol1: String,
+    sol2: String,
+    label: Value,
+}
+
+// Struct untuk baris task COPA
+#[derive(Debug, Deserialize)]
+struct CopaLine {
+    #[serde(default)]
+    idx: Option<Value>,
+    premise: String,
+    choice1: String,
+    choice2: String,
+    question: String,
+    label: Value,
+}
+
+// Fungsi untuk membaca label berupa angka atau string angka
+fn label_index(label: &Value) -> Result<usize> {
+    let index = match label {
+        Value::Number(n) => n.as_u64().map(|n| n as usize),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    match index {
+        Some(index) => Ok(index),
+        None => anyhow::bail!("invalid label {label}"),
+    }
+}
+
+// Fungsi untuk menulis id opsional sebagai string, nomor baris dipakai jika id tidak ada
+pub fn id_string(id: Option<Value>, line: usize) -> String {
+    match id {
+        Some(Value::String(s)) => s,
+        Some(Value::Null) | None => line.to_string(),
+        Some(v) => v.to_string(),
+    }
+}
+
+// Fungsi untuk membersihkan teks HellaSwag dari WikiHow: judul menjadi kalimat dan tag [..] dibuang
+fn hellaswag_text(text: &str) -> String {
+    let text = text.trim().replace(" [title]", ". ");
+    let mut clean = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '[' => in_tag = true,
+            ']' if in_tag => in_tag = false,
+            c if !in_tag => clean.push(c),
+            _ => {}
+        }
+    }
+    clean.replace("  ", " ")
+}
+
+// Fungsi untuk mengubah huruf pertama menjadi kapital (atau kecil)
+fn with_first(text: &str, upper: bool) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if upper => first.to_uppercase().chain(chars).collect(),
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Fungsi untuk membaca satu baris JSONL menjadi soal sesuai formatnya, line adalah nomor baris untuk id bawaan
+pub fn parse_line(text: &str, format: TaskFormat, line: usize) -> Result<Item> {
+    let item = match format {
+        TaskFormat::Generic => {
+            let l: GenericLine = serde_json::from_str(text)?;
+            Item {
+                id: id_string(l.id, line),
+                subject: l.subject.unwrap_or_default(),
+                level: String::new(),
+                query: l.query,
+                choices: l.choices,
+                gold: l.gold,
+            }
+        }
+        TaskFormat::Arc => {
+            let l: ArcLine = serde_json::from_str(text)?;
+            let gold = match l.choices.label.iter().position(|v| *v == l.answer_key) {
+                Some(gold) => gold,
+                None => anyhow::bail!("answerKey {} is not one of the labels", l.answer_key),
+            };
+            Item {
+                id: l.id.unwrap_or_else(|| line.to_string()),
+                subject: "arc".to_string(),
+                level: String::new(),
+                query: format!("Question: {}\nAnswer:", l.question),
+                choices: l.choices.text.iter().map(|c| format!(" {c}")).collect(),
+                gold,
+            }
+        }
+        TaskFormat::Hellaswag => {
+            let l: HellaswagLine = serde_json::from_str(text)?;
+            let ctx = match (l.ctx_a, l.ctx_b, l.ctx) {
+                (Some(a), Some(b), _) => format!("{a} {}", with_first(&b, true)),
+                (_, _, Some(ctx)) => ctx,
+                _ => anyhow::bail!("missing ctx_a and ctx_b (or ctx)"),
+            };
+            Item {
+                id: id_string(l.ind, line),
+                subject: l.activity_label.clone(),
+                level: String::new(),
+                query: hellaswag_text(&format!("{}: {ctx}", l.activity_label)),
+                choices: l
+                    .endings
+                    .iter()
+                    .map(|e| format!(" {}", hellaswag_text(e)))
+                    .collect(),
+                gold: label_index(&l.label)?,
+            }
+        }
+        TaskFormat::Piqa => {
+            let l: PiqaLine = serde_json::from_str(text)?;
+            Item {
+                id: line.to_string(),
+                subject: "piqa".to_string(),
+                level: String::new(),
+                query: format!("Question: {}\nAnswer:", l.goal),
+                choices: vec![format!(" {}", l.sol1), format!(" {}", l.sol2)],
+                gold: label_index(&l.label)?,
+            }
+        }
+        TaskFormat::Indommlu => anyhow::bail!("IndoMMLU tasks are CSV files, not JSONL"),
+        TaskFormat::Copa => {
+            let l: CopaLine = serde_json::from_str(text)?;
+            let connector = match l.question.as_str() {
+                "cause" => "because",
+                "effect" => "therefore",
+                q => anyhow::bail!("unknown COPA question {q:?}, expected cause or effect"),
+            };
+            // Titik di akhir premis diganti dengan kata penghubung
+            let premise = l.premise.trim();
+            let premise = premise.strip_suffix('.').unwrap_or(premise);
+            Item {
+                id: id_string(l.idx, line),
+                subject: l.question.clone(),
+                level: String::new(),
+                query: format!("{premise} {connector}"),
+                choices: vec![
+                    format!(" {}", with_first(&l.choice1, false)),
+                    format!(" {}", with_first(&l.choice2, false)),
+                ],
+                gold: label_index(&l.label)?,
+            }
+        }
+    };
+    if item.choices.len() < 2 {
+        anyhow::bail!("a question needs at least two choices")
+    }
+    if item.gold >= item.choices.len() {
+        anyhow::bail!(
+            "gold answer {} out of range for {} choices",
+            item.gold,
+            item.choices.len()
+        )
+    }
+    Ok(item)
+}
+
+// Fungsi untuk membaca semua soal dari file JSONL
+pub fn load_items(path: &Path, format: TaskFormat) -> Result<Vec<Item>> {
+    let mut items = vec![];
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = parse_line(line, format, index + 1)
+            .map_err(|err| anyhow::anyhow!("{path:?} line {}: {err}", index + 1))?;
+        items.push(item);
+    }
+    if items.is_empty() {
+        anyhow::bail!("no questions in {path:?}")
+    }
+    Ok(items)
+}
+
+// Fungsi untuk membaca file task sesuai formatnya, hasilnya soal yang dinilai dan soal yang ditandai sebagai contoh
+// few-shot (hanya IndoMMLU)
+pub fn load_task(
+    path: &Path,
+    format: TaskFormat,
+    template: PromptTemplate,
+) -> Result<(Vec<Item>, Vec<Item>)> {
+    match format {
+        TaskFormat::Indommlu => indommlu::load_items(path, template),
+        format => Ok((load_items(path, format)?, vec![])),
+    }
+}
+
+// Fungsi untuk membuat header few-shot: setiap contoh ditulis dengan jawaban benarnya
+pub fn fewshot_header(examples: &[Item]) -> String {
+    let mut header = String::new();
+    for example in examples.iter() {
+        header.push_str(&example.query);
+        header.push_str(&example.choices[example.gold]);
+        header.push_str("\n\n");
+    }
+    header
+}
+
+// Fungsi untuk menilai semua pilihan satu soal, header few-shot yang sama membuat prefix prompt bisa dipakai ulang
+pub fn score_item<B: Backend>(backend: &mut B, header: &str, item: &Item) -> Result<Scored> {
+    let prompt = format!("{header}{}", item.query);
+    let mut scored = Scored {
+        logprobs: vec![],
+        tokens: vec![],
+    };
+    for choice in item.choices.iter() {
+        let (logprob, tokens) = backend.score(&prompt, choice)?;
+        scored.logprobs.push(logprob);
+        scored.tokens.push(tokens);
+    }
+    Ok(scored)
+}
+
+// Struct untuk akurasi per subjek
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Accuracy {
+    pub total: usize,
+    pub correct: usize,
+    pub correct_norm: usize,
+}
+
+impl Accuracy {
+    fn add(&mut self, correct: bool, correct_norm: bool) {
+        self.total += 1;
+        self.correct += correct as usize;
+        self.correct_norm += correct_norm as usize;
+    }
+
+    fn line(&self, name: &str) -> String {
+        let total = self.total.max(1) as f64;
+        format!(
+            "{name:<32} {:>6} acc {:.4} acc_norm {:.4}",
+            self.total,
+            self.correct as f64 / total,
+            self.correct_norm as f64 / total
+        )
+    }
+}
+
+// Fungsi untuk menjalankan subcommand eval dan mencetak akurasi per subjek, per jenjang dan total, model adalah sidik
+// jari model untuk journal
+pub fn run<B: Backend>(backend: &mut B, args: &EvalArgs, model: &str) -> Result<()> {
+    let is_indommlu = args.format == TaskFormat::Indommlu;
+    let (mut items, pool) = load_task(&args.task, args.format, args.template)?;
+    let examples: Vec<Item> = match &args.fewshot_file {
+        // IndoMMLU memilih contoh per mata pelajaran, sehingga semua soal file few-shot dipakai sebagai kumpulan contoh
+        Some(path) if is_indommlu => {
+            let (file_items, file_pool) = load_task(path, args.format, args.template)?;
+            pool.into_iter()
+                .chain(file_pool)
+                .chain(file_items)
+                .collect()
+        }
+        Some(path) => load_items(path, args.format)?
+            .into_iter()
+            .take(args.shots)
+            .collect(),
+        None if is_indommlu => pool,
+        None => items.drain(..args.shots.min(items.len())).collect(),
+    };
+    if is_indommlu && args.shots > 0 && examples.is_empty() {
+        anyhow::bail!(
+            "{} few-shot examples requested but no row is marked is_for_fewshot",
+            args.shots
+        )
+    }
+    if !is_indommlu && examples.len() < args.shots {
+        anyhow::bail!(
+            "{} few-shot examples requested but only {} available",
+            args.shots,
+            examples.len()
+        )
+    }
+    if let Some(limit) = args.limit {
+        items.truncate(limit);
+    }
+    if items.is_empty() {
+        anyhow::bail!("no questions left to evaluate after taking the few-shot examples")
+    }
+    // Header IndoMMLU terdiri dari kalimat pembuka dan contoh few-shot dari mata pelajaran soal tersebut
+    let header_of = |item: &Item| {
+        if is_indommlu {
+            let same = examples
+                .iter()
+                .filter(|e| e.subject == item.subject)
+                .take(args.shots)
+                .cloned()
+                .collect::<Vec<_>>();
+            format!(
+                "{}{}",
+                args.template.intro(&item.subject),
+                fewshot_header(&same)
+            )
+        } else {
+            fewshot_header(&examples)
+        }
+    };
+    if is_indommlu {
+        println!(
+            "evaluating {} questions from {:?} with {} shots per subject and the {:?} template",
+            items.len(),
+            args.task,
+            args.shots,
+            args.template
+        );
+    } else {
+        println!(
+            "evaluating {} questions from {:?} with {} shots",
+            items.len(),
+            args.task,
+            examples.len()
+        );
+    }
+
+    // Soal dicatat di journal dengan nomor barisnya di file task, contoh few-shot yang diambil dari task ikut dihitung
+    let file = args.task.display().to_string();
+    let offset = match (&args.fewshot_file, is_indommlu) {
+        (None, false) => examples.len(),
+        _ => 0,
+    };
+    let template = journal::hash(&[
+        &format!("{:?} {:?} {}", args.format, args.template, args.shots),
+        &fewshot_header(&examples),
+    ]);
+    let mut journal = Journal::open(
+        args.journal.as_deref(),
+        args.resume,
+        format!("{model}-{template}"),
+    )?;
+    if let Some(summary) = journal.summary() {
+        println!("{summary}");
+    }
+
+    let start = std::time::Instant::now();
+    let mut subjects: BTreeMap<String, Accuracy> = BTreeMap::new();
+    let mut levels: BTreeMap<String, Accuracy> = BTreeMap::new();
+    let mut overall = Accuracy::default();
+    let mut lines = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let row = offset + index;
+        let line = match journal.get(&file, row) {
+            Some(line) => line.clone(),
+            None => {
+                let scored = score_item(backend, &header_of(item), item)?;
+                let (pred, pred_norm) = (scored.prediction(), scored.normalized_prediction(item));
+                let line = json!({
+                    "id": item.id,
+                    "subject": item.subject,
+                    "level": item.level,
+                    "gold": item.gold,
+                    "pred": pred,
+                    "pred_norm": pred_norm,
+                    "correct": pred == item.gold,
+                    "correct_norm": pred_norm == item.gold,
+                    "logprobs": scored.logprobs,
+                    "tokens": scored.tokens,
+                });
+                journal.record(&file, row, &line)?;
+                line
+            }
+        };
+        let correct = line["correct"].as_bool() == Some(true);
+        let correct_norm = line["correct_norm"].as_bool() == Some(true);
+        subjects
+            .entry(item.subject.clone())
+            .or_default()
+            .add(correct, correct_norm);
+        levels
+            .entry(indommlu::education_level(&item.level).to_string())
+            .or_default()
+            .add(correct, correct_norm);
+        overall.add(correct, correct_norm);
+        lines.push_str(&format!("{line}\n"));
+        if (index + 1) % 100 == 0 {
+            println!(
+                "{}",
+                overall.line(&format!("{}/{}", index + 1, items.len()))
+            );
+        }
+    }
+    if subjects.len() > 1 {
+        for (subject, accuracy) in subjects.iter() {
+            println!("{}", accuracy.line(subject));
+        }
+    }
+    if levels.keys().any(|level| !level.is_empty()) {
+        for (level, accuracy) in levels.iter() {
+            let name = match level.as_str() {
+                "" => "level (unknown)".to_string(),
+                level => format!("level {level}"),
+            };
+            println!("{}", accuracy.line(&name));
+        }
+    }
+    println!("{}", overall.line("total"));
+    println!("evaluated in {:.2}s", start.elapsed().as_secs_f64());
+    if let Some(path) = args.output.as_ref() {
+        std::fs::write(path, lines)?;
+        println!("wrote {} results to {path:?}", items.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Budget;
+    use crate::worker::Generation;
+
+    // Backend palsu: log-likelihood adalah minus jumlah huruf 'x' di continuation, prompt terakhir dicatat
+    struct Letters {
+        prompts: Vec<String>,
+    }
+
+    impl Backend for Letters {
+        fn generate(
+            &mut self,
+            _prompt: &str,
+            _max_tokens: usize,
+            _budget: &Budget,
+            _on_text: &mut dyn FnMut(&str) -> Result<()>,
+        ) -> Result<Generation> {
+            anyhow::bail!("not used")
+        }
+
+        fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)> {
+            self.prompts.push(prompt.to_string());
+            let xs = continuation.chars().filter(|c| *c == 'x').count();
+            Ok((-(xs as f64), continuation.len()))
+        }
+
+        fn tokenize(&mut self, _text: &str) -> Result<Vec<(u32, String)>> {
+            Ok(vec![])
+        }
+
+        fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn loaders_build_queries_and_choices() -> Result<()> {
+        let arc = r#"{"id": "q1", "question": "Which is hot?", "choices": {"text": ["ice", "fire"], "label": ["A", "B"]}, "answerKey": "B"}"#;
+        let item = parse_line(arc, TaskFormat::Arc, 1)?;
+        assert_eq!(item.id, "q1");
+        assert_eq!(item.query, "Question: Which is hot?\nAnswer:");
+        assert_eq!(item.choices, vec![" ice", " fire"]);
+        assert_eq!(item.gold, 1);
+
+        let hellaswag = r#"{"ind": 4, "activity_label": "Baking", "ctx_a": "She mixes flour.", "ctx_b": "then she", "endings": ["bakes [header] it.", "sleeps."], "label": "0"}"#;
+        let item = parse_line(hellaswag, TaskFormat::Hellaswag, 1)?;
+        assert_eq!(item.id, "4");
+        assert_eq!(item.query, "Baking: She mixes flour. Then she");
+        assert_eq!(item.choices, vec![" bakes it.", " sleeps."]);
+        assert_eq!(item.gold, 0);
+
+        let piqa = r#"{"goal": "Open a jar", "sol1": "Twist the lid.", "sol2": "Eat the lid.", "label": 0}"#;
+        let item = parse_line(piqa, TaskFormat::Piqa, 7)?;
+        assert_eq!(item.id, "7");
+        assert_eq!(item.query, "Question: Open a jar\nAnswer:");
+        assert_eq!(item.choices, vec![" Twist the lid.", " Eat the lid."]);
+
+        let copa = r#"{"premise": "The man fell.", "choice1": "He slipped.", "choice2": "He laughed.", "question": "cause", "label": 0, "idx": 3}"#;
+        let item = parse_line(copa, TaskFormat::Copa, 1)?;
+        assert_eq!(item.query, "The man fell because");
+        assert_eq!(item.choices, vec![" he slipped.", " he laughed."]);
+        assert_eq!(item.subject, "cause");
+
+        let bad = r#"{"query": "q", "choices": ["a", "b"], "gold": 2}"#;
+        assert!(parse_line(bad, TaskFormat::Generic, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn length_normalization_changes_the_prediction() -> Result<()> {
+        let item = Item {
+            id: "1".to_string(),
+            subject: String::new(),
+            level: String::new(),
+            query: "Q:".to_string(),
+            choices: vec![" x".to_string(), " xx and a long tail".to_string()],
+            gold: 1,
+        };
+        let example = Item {
+            query: "E:".to_string(),
+            gold: 0,
+            ..item.clone()
+        };
+        let mut backend = Letters { prompts: vec![] };
+        let header = fewshot_header(&[example]);
+        let scored = score_item(&mut backend, &header, &item)?;
+        assert_eq!(scored.logprobs, vec![-1., -2.]);
+        assert_eq!(scored.prediction(), 0);
+        assert_eq!(scored.normalized_prediction(&item), 1);
+        assert_eq!(backend.prompts, vec!["E: x\n\nQ:", "E: x\n\nQ:"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+// Modul fixture untuk test offline: direktori sementara dan file golden di tests/golden setiap crate. Dengan fitur
+// candle juga tokenizer WordLevel kecil dan bobot acak deterministik. File golden hanya direkam saat UPDATE_GOLDEN
+// di-set lalu di-commit, file yang tidak ada membuat test gagal
+use anyhow::Result;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "candle")]
+use crate::compare::SplitMix;
+#[cfg(feature = "candle")]
+use anyhow::Error as E;
+#[cfg(feature = "candle")]
+use candle_core::Tensor;
+#[cfg(feature = "candle")]
+use candle_nn::VarMap;
+#[cfg(feature = "candle")]
+use tokenizers::Tokenizer;
+
+// Struct untuk direktori sementara fixture, dihapus saat di-drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // Membuat direktori sementara yang unik untuk setiap test dan proses
+    pub fn new(name: &str) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{name}-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Fungsi untuk menulis tokenizer.json WordLevel lalu memuatnya: token khusus (id 0 dan seterusnya), "<unk>", huruf
+// jawaban "A".."D", lalu kata pengisi "w{id}" sampai vocab_size. Setiap kata satu token sehingga teks hasil generasi
+// bisa dibaca sebagai urutan token
+#[cfg(feature = "candle")]
+pub fn tokenizer(path: &Path, specials: &[&str], vocab_size: usize) -> Result<Tokenizer> {
+    let mut vocab = serde_json::Map::new();
+    let mut added_tokens = vec![];
+    for (id, token) in specials.iter().enumerate() {
+        vocab.insert(token.to_string(), id.into());
+        added_tokens.push(serde_json::json!({
+            "id": id, "content": token, "single_word": false, "lstrip": false,
+            "rstrip": false, "normalized": false, "special": true
+        }));
+    }
+    for word in ["<unk>", "A", "B", "C", "D"] {
+        vocab.insert(word.to_string(), vocab.len().into());
+    }
+    for id in vocab.len()..vocab_size {
+        vocab.insert(format!("w{id}"), id.into());
+    }
+    let json = serde_json::json!({
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": added_tokens,
+        "normalizer": null,
+        "pre_tokenizer": {"type": "Whitespace"},
+        "post_processor": null,
+        "decoder": {"type": "WordPiece", "prefix": "##", "cleanup": false},
+        "model": {"type": "WordLevel", "vocab": vocab, "unk_token": "<unk>"}
+    });
+    std::fs::write(path, json.to_string())?;
+    Tokenizer::from_file(path).map_err(E::msg)
+}
+
+// Fungsi untuk mengisi semua variabel VarMap dengan nilai uniform di [-0.5, 0.5) dari seed. Variabel diisi urut nama
+// agar bobotnya sama di setiap run (Tensor::randn di CPU tidak bisa diberi seed)
+#[cfg(feature = "candle")]
+pub fn randomize(varmap: &VarMap, seed: u64) -> Result<()> {
+    let data = varmap.data().lock().unwrap();
+    let mut names = data.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut rng = SplitMix(seed);
+    for name in names {
+        let var = &data[name];
+        let values = (0..var.elem_count())
+            .map(|_| rng.uniform() - 0.5)
+            .collect::<Vec<f32>>();
+        var.set(&Tensor::from_vec(values, var.shape(), var.device())?)?;
+    }
+    Ok(())
+}
+
+// Fungsi untuk mengubah nilai satu variabel VarMap, contoh menaikkan bias lm_head pada token eos
+#[cfg(feature = "candle")]
+pub fn update(varmap: &VarMap, name: &str, f: impl FnOnce(&mut [f32])) -> Result<()> {
+    let data = varmap.data().lock().unwrap();
+    let var = data
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("no variable {name} in the fixture"))?;
+    let mut values = var.flatten_all()?.to_vec1::<f32>()?;
+    f(&mut values);
+    var.set(&Tensor::from_vec(values, var.shape(), var.device())?)?;
+    Ok(())
+}
+
+// Fungsi untuk membandingkan output dengan {dir}/{name}.json, file hanya direkam jika UPDATE_GOLDEN di-set. dir adalah
+// tests/golden crate yang memanggil, karena CARGO_MANIFEST_DIR di sini menunjuk ke crate common
+pub fn assert_golden(dir: &str, name: &str, actual: &Value) -> Result<()> {
+    let dir = Path::new(dir);
+    let path = dir.join(format!("{name}.json"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(
+            &path,
+            format!("{}\n", serde_json::to_string_pretty(actual)?),
+        )?;
+        eprintln!("recorded {path:?}");
+        return Ok(());
+    }
+    // File golden yang tidak ada adalah kegagalan, bukan rekaman baru, agar CI tidak lulus dengan sendirinya
+    if !path.exists() {
+        anyhow::bail!("missing golden file {path:?}, record it with UPDATE_GOLDEN=1 and commit it")
+    }
+    let expected: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(
+        &expected, actual,
+        "output differs from {path:?}, rerun with UPDATE_GOLDEN=1 if the change is intended"
+    );
+    Ok(())
+}
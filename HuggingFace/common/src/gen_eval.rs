@@ -0,0 +1,573 @@
+// Modul untuk subcommand eval-gen: soal jawaban bebas (gaya GSM8K) dibaca dari file JSONL dan dijawab dengan generasi
+// lewat Backend yang sama dengan mode --worker. Jawaban akhir diambil dari output dengan regex, dinormalisasi (angka,
+// huruf besar kecil, tanda baca) lalu dibandingkan exact match dengan jawaban benar
+use anyhow::Result;
+use clap::Args as ClapArgs;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::budget::Budget;
+use crate::eval::id_string;
+use crate::journal::{self, Journal};
+use crate::worker::Backend;
+
+// Fungsi untuk mengganti escape `\n`, `\t` dan `\\` dari argumen baris perintah
+pub fn unescape(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    Ok(out)
+}
+
+// Argumen untuk subcommand eval-gen
+#[derive(ClapArgs, Debug)]
+pub struct GenEvalArgs {
+    /// JSONL task file with one `{"question": ..., "answer": ...}` object per line (GSM8K format),
+    /// with optional `id` and `subject`.
+    #[arg(long)]
+    task: PathBuf,
+
+    /// The prompt of every question, `{question}` is replaced by the question (`\n` is a newline).
+    #[arg(long, default_value = "Question: {question}\nAnswer:", value_parser = unescape)]
+    template: String,
+
+    /// Regex extracting the final answer from the output and from the reference answer, using the
+    /// first capture group (or the whole match). Can be repeated: the first regex that matches
+    /// wins and its last match is used.
+    #[arg(
+        long = "answer-regex",
+        default_values = [r"####\s*([^\n]+)", r"(?i)(?:the answer is|jawaban\s*:)\s*([^\n]+)"]
+    )]
+    answer_regex: Vec<String>,
+
+    /// The number of few-shot exemplars put before every question.
+    #[arg(long, default_value_t = 0)]
+    shots: usize,
+
+    /// JSONL file the exemplars are taken from, in the same format as the task. Defaults to the
+    /// first questions of the task file, which are then not evaluated.
+    #[arg(long)]
+    fewshot_file: Option<PathBuf>,
+
+    /// Show the full reference solution in the exemplars (chain of thought) instead of only the
+    /// final answer.
+    #[arg(long)]
+    cot: bool,
+
+    /// How the final answer of an exemplar is written without --cot, `{answer}` is replaced by the
+    /// answer.
+    #[arg(long, default_value = "#### {answer}", value_parser = unescape)]
+    answer_template: String,
+
+    /// Stop generating once the output contains this text (`\n` is a newline). Can be repeated.
+    #[arg(long = "stop", default_values = [r"\n\nQuestion:", r"\n\n\n"], value_parser = unescape)]
+    stop: Vec<String>,
+
+    /// The maximum number of tokens generated for every question.
+    #[arg(long, default_value_t = 256)]
+    max_tokens: usize,
+
+    /// Only evaluate the first questions of the task file.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// JSONL file where the per-question results (output, extracted answer and correctness) are
+    /// written.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// JSONL journal where every result is appended as soon as the question is scored, keyed by
+    /// the task file and row with a hash of the model and prompt template.
+    #[arg(long)]
+    journal: Option<PathBuf>,
+
+    /// Continue an interrupted run: the questions already in --journal with the same model and
+    /// prompt template are not scored again.
+    #[arg(long, requires = "journal")]
+    resume: bool,
+}
+
+// Struct untuk satu baris task, nama field dataset lain diterima sebagai alias
+#[derive(Debug, Deserialize)]
+struct QaLine {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(alias = "query", alias = "problem", alias = "input")]
+    question: String,
+    #[serde(alias = "target", alias = "solution")]
+    answer: Value,
+}
+
+// Struct untuk satu soal: pertanyaan, solusi referensi lengkap dan jawaban akhirnya
+#[derive(Debug, Clone, PartialEq)]
+pub struct QaItem {
+    pub id: String,
+    pub subject: String,
+    pub question: String,
+    pub solution: String,
+    pub gold: String,
+}
+
+// Fungsi untuk membaca semua soal dari file JSONL, jawaban akhir diambil dari solusi dengan regex jawaban
+// (contoh "#### 72" pada GSM8K), atau seluruh solusi jika tidak ada yang cocok
+pub fn load_items(path: &Path, patterns: &[Regex]) -> Result<Vec<QaItem>> {
+    let mut items = vec![];
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let l: QaLine = serde_json::from_str(line)
+            .map_err(|err| anyhow::anyhow!("{path:?} line {}: {err}", index + 1))?;
+        let solution = match l.answer {
+            Value::String(s) => s,
+            v => v.to_string(),
+        };
+        let gold = extract(&solution, patterns).unwrap_or_else(|| solution.trim().to_string());
+        items.push(QaItem {
+            id: id_string(l.id, index + 1),
+            subject: l.subject.unwrap_or_default(),
+            question: l.question,
+            solution,
+            gold,
+        });
+    }
+    if items.is_empty() {
+        anyhow::bail!("no questions in {path:?}")
+    }
+    Ok(items)
+}
+
+// Fungsi untuk mengambil jawaban dari teks: regex pertama yang cocok dipakai, dengan grup pertama (atau seluruh
+// kecocokan) dari kecocokan terakhirnya
+pub fn extract(text: &str, patterns: &[Regex]) -> Option<String> {
+    for pattern in patterns.iter() {
+        if let Some(captures) = pattern.captures_iter(text).last() {
+            let m = captures.get(1).or_else(|| captures.get(0))?;
+            return Some(m.as_str().trim().to_string());
+        }
+    }
+    None
+}
+
+// Fungsi untuk menormalisasi jawaban: huruf kecil, tanda $ dan tanda baca di awal atau akhir dibuang, spasi
+// dirapikan, dan angka ditulis ulang tanpa pemisah ribuan atau nol di belakang koma ("1,000.50" menjadi "1000.5")
+pub fn normalize(text: &str) -> String {
+    let text = text.to_lowercase().replace('$', "");
+    let text = text
+        .trim_start_matches(|c: char| c.is_whitespace() || (c.is_ascii_punctuation() && c != '-'))
+        .trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation());
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let number = text.replace(',', "");
+    match number.parse::<f64>() {
+        Ok(v) if v.is_finite() && v.fract() == 0. && v.abs() < 1e15 => format!("{}", v as i64),
+        Ok(v) if v.is_finite() => format!("{v}"),
+        _ => text,
+    }
+}
+
+// Fungsi untuk mengambil angka pertama dari teks, dipakai jika jawaban benar berupa angka ("42 apples" menjadi "42")
+fn first_number(text: &str) -> Option<String> {
+    let number = Regex::new(r"-?\d[\d,]*(?:\.\d+)?").ok()?;
+    number.find(text).map(|m| m.as_str().to_string())
+}
+
+// Fungsi untuk menormalisasi jawaban model dengan melihat jawaban benar yang sudah dinormalisasi
+pub fn normalize_prediction(pred: &str, gold_norm: &str) -> String {
+    if gold_norm.parse::<f64>().is_ok() {
+        if let Some(number) = first_number(pred) {
+            return normalize(&number);
+        }
+    }
+    normalize(pred)
+}
+
+// Fungsi untuk memotong output pada stop sequence pertama yang muncul
+pub fn truncate_at_stop<'a>(text: &'a str, stops: &[String]) -> &'a str {
+    let end = stops
+        .iter()
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| text.find(s.as_str()))
+        .min()
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+// Fungsi untuk membuat header few-shot: setiap contoh ditulis dengan solusi lengkap (--cot) atau jawaban akhirnya saja
+pub fn fewshot_header(examples: &[QaItem], args: &GenEvalArgs) -> String {
+    let mut header = String::new();
+    for example in examples.iter() {
+        let answer = if args.cot {
+            example.solution.trim().to_string()
+        } else {
+            args.answer_template.replace("{answer}", &example.gold)
+        };
+        let prompt = args.template.replace("{question}", &example.question);
+        header.push_str(&format!("{prompt} {answer}\n\n"));
+    }
+    header
+}
+
+// Struct untuk akurasi per subjek
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Accuracy {
+    total: usize,
+    correct: usize,
+    extracted: usize,
+}
+
+impl Accuracy {
+    fn add(&mut self, correct: bool, extracted: bool) {
+        self.total += 1;
+        self.correct += correct as usize;
+        self.extracted += extracted as usize;
+    }
+
+    fn line(&self, name: &str) -> String {
+        let total = self.total.max(1) as f64;
+        format!(
+            "{name:<32} {:>6} exact_match {:.4} extracted {:.4}",
+            self.total,
+            self.correct as f64 / total,
+            self.extracted as f64 / total
+        )
+    }
+}
+
+// Fungsi untuk menjawab satu soal: generasi dihentikan begitu output berisi stop sequence, lalu jawaban diambil dan
+// dinilai. Hasilnya baris JSON per soal untuk --output dan journal
+fn answer_item<B: Backend>(
+    backend: &mut B,
+    args: &GenEvalArgs,
+    header: &str,
+    patterns: &[Regex],
+    item: &QaItem,
+) -> Result<Value> {
+    // Sampler dikembalikan ke seed awal agar hasil setiap soal tidak bergantung pada soal sebelumnya
+    backend.reset()?;
+    let prompt = format!(
+        "{header}{}",
+        args.template.replace("{question}", &item.question)
+    );
+    let budget = Budget::new(None, None);
+    let mut text = String::new();
+    let generation = backend.generate(&prompt, args.max_tokens, &budget, &mut |chunk| {
+        text.push_str(chunk);
+        if args
+            .stop
+            .iter()
+            .any(|s| !s.is_empty() && text.contains(s.as_str()))
+        {
+            budget.stop();
+        }
+        Ok(())
+    })?;
+    let output = truncate_at_stop(&generation.text, &args.stop);
+    let pred = extract(output, patterns);
+    let gold_norm = normalize(&item.gold);
+    let pred_norm = pred.as_ref().map(|p| normalize_prediction(p, &gold_norm));
+    let correct = pred_norm.as_deref() == Some(gold_norm.as_str());
+    Ok(json!({
+        "id": item.id,
+        "subject": item.subject,
+        "gold": item.gold,
+        "gold_norm": gold_norm,
+        "pred": pred,
+        "pred_norm": pred_norm,
+        "correct": correct,
+        "finish_reason": generation.finish_reason.to_string(),
+        "generated_tokens": generation.generated_tokens,
+        "output": output,
+    }))
+}
+
+// Fungsi untuk menjalankan subcommand eval-gen dan mencetak exact match per subjek dan total, model adalah sidik jari
+// model (termasuk pengaturan sampling) untuk journal
+pub fn run<B: Backend>(backend: &mut B, args: &GenEvalArgs, model: &str) -> Result<()> {
+    let patterns = args
+        .answer_regex
+        .iter()
+        .map(|p| {
+            Regex::new(p).map_err(|err| anyhow::anyhow!("invalid --answer-regex {p:?}: {err}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut items = load_items(&args.task, &patterns)?;
+    let examples: Vec<QaItem> = match &args.fewshot_file {
+        Some(path) => load_items(path, &patterns)?
+            .into_iter()
+            .take(args.shots)
+            .collect(),
+        None => items.drain(..args.shots.min(items.len())).collect(),
+    };
+    if examples.len() < args.shots {
+        anyhow::bail!(
+            "{} few-shot examples requested but only {} available",
+            args.shots,
+            examples.len()
+        )
+    }
+    if let Some(limit) = args.limit {
+        items.truncate(limit);
+    }
+    if items.is_empty() {
+        anyhow::bail!("no questions left to evaluate after taking the few-shot examples")
+    }
+    let header = fewshot_header(&examples, args);
+    println!(
+        "evaluating {} questions from {:?} with {} shots",
+        items.len(),
+        args.task,
+        examples.len()
+    );
+
+    // Soal dicatat di journal dengan nomor barisnya di file task, contoh few-shot yang diambil dari task ikut dihitung.
+    // Regex jawaban, stop sequence dan batas token ikut di-hash karena mengubah hasil
+    let file = args.task.display().to_string();
+    let offset = match args.fewshot_file {
+        Some(_) => 0,
+        None => examples.len(),
+    };
+    let mut parts = vec![
+        header.clone(),
+        args.template.clone(),
+        args.max_tokens.to_string(),
+    ];
+    parts.extend(args.answer_regex.iter().cloned());
+    parts.extend(args.stop.iter().cloned());
+    let template = journal::hash(&parts.iter().map(|p| p.as_str()).collect::<Vec<_>>());
+    let mut journal = Journal::open(
+        args.journal.as_deref(),
+        args.resume,
+        format!("{model}-{template}"),
+    )?;
+    if let Some(summary) = journal.summary() {
+        println!("{summary}");
+    }
+
+    let start = std::time::Instant::now();
+    let mut subjects: BTreeMap<String, Accuracy> = BTreeMap::new();
+    let mut overall = Accuracy::default();
+    let mut lines = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let row = offset + index;
+        let line = match journal.get(&file, row) {
+            Some(line) => line.clone(),
+            None => {
+                let line = answer_item(backend, args, &header, &patterns, item)?;
+                journal.record(&file, row, &line)?;
+                line
+            }
+        };
+        let correct = line["correct"].as_bool() == Some(true);
+        let extracted = !line["pred"].is_null();
+        subjects
+            .entry(item.subject.clone())
+            .or_default()
+            .add(correct, extracted);
+        overall.add(correct, extracted);
+        lines.push_str(&format!("{line}\n"));
+        if (index + 1) % 10 == 0 {
+            println!(
+                "{}",
+                overall.line(&format!("{}/{}", index + 1, items.len()))
+            );
+        }
+    }
+    if subjects.len() > 1 {
+        for (subject, accuracy) in subjects.iter() {
+            println!("{}", accuracy.line(subject));
+        }
+    }
+    println!("{}", overall.line("total"));
+    println!("evaluated in {:.2}s", start.elapsed().as_secs_f64());
+    if let Some(path) = args.output.as_ref() {
+        std::fs::write(path, lines)?;
+        println!("wrote {} results to {path:?}", items.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::FinishReason;
+    use crate::fixtures::TempDir;
+    use crate::worker::Generation;
+
+    // Backend palsu: setiap generasi mengeluarkan potongan teks yang sama satu per satu sambil memeriksa budget
+    struct Scripted {
+        chunks: Vec<&'static str>,
+        prompts: Vec<String>,
+    }
+
+    impl Backend for Scripted {
+        fn generate(
+            &mut self,
+            prompt: &str,
+            max_tokens: usize,
+            budget: &Budget,
+            on_text: &mut dyn FnMut(&str) -> Result<()>,
+        ) -> Result<Generation> {
+            self.prompts.push(prompt.to_string());
+            let mut generation = Generation {
+                text: String::new(),
+                finish_reason: FinishReason::Length,
+                prompt_tokens: 0,
+                generated_tokens: 0,
+            };
+            for chunk in self.chunks.iter().take(max_tokens) {
+                if let Some(reason) = budget.check() {
+                    generation.finish_reason = reason;
+                    break;
+                }
+                on_text(chunk)?;
+                generation.text.push_str(chunk);
+                generation.generated_tokens += 1;
+            }
+            Ok(generation)
+        }
+
+        fn score(&mut self, _prompt: &str, _continuation: &str) -> Result<(f64, usize)> {
+            anyhow::bail!("not used")
+        }
+
+        fn tokenize(&mut self, _text: &str) -> Result<Vec<(u32, String)>> {
+            Ok(vec![])
+        }
+
+        fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn answers_are_extracted_and_normalized() -> Result<()> {
+        let patterns = [
+            Regex::new(r"####\s*([^\n]+)")?,
+            Regex::new(r"(?i)(?:the answer is|jawaban\s*:)\s*([^\n]+)")?,
+        ];
+        let solution = "She has 3 + 4 = 7 apples, so 7 * 1,000 = 7,000.\n#### 7,000";
+        assert_eq!(extract(solution, &patterns).as_deref(), Some("7,000"));
+        assert_eq!(normalize("7,000"), "7000");
+        assert_eq!(normalize(" $1,000.50. "), "1000.5");
+        assert_eq!(normalize("-3.0"), "-3");
+        assert_eq!(normalize("(B)."), "b");
+        assert_eq!(normalize("  New   York! "), "new york");
+        // Regex pertama tidak cocok, kecocokan terakhir regex kedua yang dipakai
+        let output = "Jawaban: A\nBukan, jawaban: C.";
+        assert_eq!(extract(output, &patterns).as_deref(), Some("C."));
+        assert_eq!(normalize_prediction("42 apples.", "42"), "42");
+        assert_eq!(normalize_prediction("Paris.", "paris"), "paris");
+        assert_eq!(extract("no answer here", &patterns), None);
+        Ok(())
+    }
+
+    #[test]
+    fn stop_sequences_end_generation() -> Result<()> {
+        let dir = TempDir::new("gen-eval")?;
+        let task = dir.join("task.jsonl");
+        std::fs::write(
+            &task,
+            [
+                r#"{"question": "1 + 1?", "answer": "1 + 1 = 2\n#### 2"}"#,
+                r#"{"question": "3 + 4?", "answer": "3 + 4 = 7\n#### 7"}"#,
+                r#"{"question": "2 * 3?", "answer": "2 * 3 = 6\n#### 6"}"#,
+            ]
+            .join("\n"),
+        )?;
+        let output = dir.join("results.jsonl");
+        let args = GenEvalArgs {
+            task,
+            template: "Question: {question}\nAnswer:".to_string(),
+            answer_regex: vec![r"####\s*([^\n]+)".to_string()],
+            shots: 1,
+            fewshot_file: None,
+            cot: true,
+            answer_template: "#### {answer}".to_string(),
+            stop: vec!["\n\nQuestion:".to_string()],
+            max_tokens: 64,
+            limit: None,
+            output: Some(output.clone()),
+            journal: Some(dir.join("journal.jsonl")),
+            resume: false,
+        };
+        let mut backend = Scripted {
+            chunks: vec![
+                " 3 + 4",
+                " = 7",
+                "\n#### 7",
+                "\n\nQuestion",
+                ":",
+                " 9 + 9?",
+                " #### 18",
+            ],
+            prompts: vec![],
+        };
+        run(&mut backend, &args, "model")?;
+
+        // Contoh pertama dipakai sebagai contoh few-shot dengan solusi lengkap
+        assert_eq!(
+            backend.prompts[0],
+            "Question: 1 + 1?\nAnswer: 1 + 1 = 2\n#### 2\n\nQuestion: 3 + 4?\nAnswer:"
+        );
+        let results = std::fs::read_to_string(&output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["output"], " 3 + 4 = 7\n#### 7");
+        assert_eq!(results[0]["finish_reason"], "stop");
+        assert_eq!(results[0]["generated_tokens"], 5);
+        assert_eq!(results[0]["correct"], true);
+        assert_eq!(results[1]["gold"], "6");
+        assert_eq!(results[1]["correct"], false);
+
+        // Dengan --resume soal yang sudah ada di journal tidak dijawab ulang dan hasilnya tetap dihitung
+        let args = GenEvalArgs {
+            resume: true,
+            ..args
+        };
+        let mut resumed = Scripted {
+            chunks: vec![" #### 6"],
+            prompts: vec![],
+        };
+        run(&mut resumed, &args, "model")?;
+        assert!(resumed.prompts.is_empty());
+        let lines = std::fs::read_to_string(&output)?;
+        assert_eq!(
+            lines
+                .lines()
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<Value>, _>>()?,
+            results
+        );
+        // Model lain: journal tidak dipakai
+        run(&mut resumed, &args, "other")?;
+        assert_eq!(resumed.prompts.len(), 2);
+        let results = std::fs::read_to_string(&output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(results[1]["correct"], true);
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+// Modul untuk soal pilihan ganda gaya IndoMMLU dari file CSV: jumlah pilihan bisa berbeda setiap soal, dengan metadata
+// mata pelajaran, jenjang (SD, SMP, SMA) dan kelas. Prompt ditulis dengan template bahasa Inggris atau Indonesia dan
+// setiap pilihan dinilai sebagai huruf jawabannya
+use anyhow::Result;
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::eval::Item;
+
+// Enum untuk template prompt soal pilihan ganda
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum PromptTemplate {
+    /// "The following are multiple choice questions (with answers) about ...", "Answer:".
+    English,
+    /// "Berikut adalah soal pilihan ganda (beserta jawabannya) tentang ...", "Jawaban:".
+    Indonesian,
+}
+
+impl PromptTemplate {
+    // Kalimat pembuka prompt tanpa nama mata pelajaran, juga dipakai prompt MMLU
+    pub fn lead(&self) -> &'static str {
+        match self {
+            Self::English => "The following are multiple choice questions (with answers) about",
+            Self::Indonesian => "Berikut adalah soal pilihan ganda (beserta jawabannya) tentang",
+        }
+    }
+
+    // Kata sebelum huruf jawaban
+    pub fn answer_cue(&self) -> &'static str {
+        match self {
+            Self::English => "Answer:",
+            Self::Indonesian => "Jawaban:",
+        }
+    }
+
+    // Kalimat pembuka untuk satu mata pelajaran
+    pub fn intro(&self, subject: &str) -> String {
+        format!("{} {subject}.\n\n", self.lead())
+    }
+
+    // Soal beserta pilihan berlabel A, B, C, ... lalu kata jawaban, huruf jawaban ditambahkan sebagai continuation
+    pub fn query(&self, question: &str, options: &[String]) -> String {
+        let mut query = format!("{}\n", question.trim());
+        for (index, option) in options.iter().enumerate() {
+            query.push_str(&format!("{}. {}\n", label(index), option.trim()));
+        }
+        query.push_str(self.answer_cue());
+        query
+    }
+}
+
+// Fungsi untuk huruf label pilihan ke-index (A, B, C, ...)
+fn label(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+// Fungsi untuk membuang label di depan pilihan seperti "A. ", "A)" atau "(A)"
+fn strip_label(option: &str) -> &str {
+    let option = option.trim();
+    let mut chars = option.chars();
+    let rest = match (chars.next(), chars.next()) {
+        (Some('('), Some(c)) if c.is_ascii_uppercase() => chars.as_str().strip_prefix(')'),
+        (Some(c), Some('.' | ')')) if c.is_ascii_uppercase() => Some(chars.as_str()),
+        _ => None,
+    };
+    rest.map(str::trim).unwrap_or(option)
+}
+
+// Fungsi untuk memecah kolom pilihan: list gaya Python ("['A. x', 'B. y']") atau satu pilihan per baris
+pub fn split_options(cell: &str) -> Vec<String> {
+    let cell = cell.trim();
+    let options = match cell.strip_prefix('[').and_then(|c| c.strip_suffix(']')) {
+        Some(list) => {
+            let mut options = vec![];
+            let mut chars = list.chars();
+            while let Some(c) = chars.next() {
+                if c != '\'' && c != '"' {
+                    continue;
+                }
+                // Isi string sampai tanda kutip penutup yang sama, dengan escape backslash
+                let mut option = String::new();
+                while let Some(d) = chars.next() {
+                    match d {
+                        '\\' => option.extend(chars.next()),
+                        d if d == c => break,
+                        d => option.push(d),
+                    }
+                }
+                options.push(option);
+            }
+            options
+        }
+        None => cell.lines().map(|l| l.to_string()).collect(),
+    };
+    options
+        .iter()
+        .map(|o| strip_label(o).to_string())
+        .filter(|o| !o.is_empty())
+        .collect()
+}
+
+// Fungsi untuk mencari indeks kolom pertama yang namanya cocok dengan salah satu nama
+fn column(headers: &[String], names: &[&str]) -> Option<usize> {
+    names
+        .iter()
+        .find_map(|name| headers.iter().position(|h| h == name))
+}
+
+// Fungsi untuk mencari jawaban benar: huruf pilihan (boleh diikuti titik) atau teks pilihan
+fn gold_index(answer: &str, options: &[String]) -> Option<usize> {
+    let answer = answer.trim();
+    let letter = answer.trim_end_matches(['.', ')']).trim();
+    let mut chars = letter.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        let c = c.to_ascii_uppercase();
+        if c.is_ascii_uppercase() && ((c as u8 - b'A') as usize) < options.len() {
+            return Some((c as u8 - b'A') as usize);
+        }
+    }
+    let answer = strip_label(answer);
+    options.iter().position(|o| o == answer)
+}
+
+// Fungsi untuk membaca file CSV gaya IndoMMLU dengan header. Kolom dikenali dari namanya (bahasa Indonesia atau
+// Inggris): soal/question, pilihan/options (satu kolom) atau kolom A, B, C, ..., jawaban/answer/kunci, serta
+// opsional id, subject/mapel, level/jenjang, kelas/grade dan is_for_fewshot. Hasilnya soal yang dinilai dan soal yang
+// ditandai is_for_fewshot sebagai contoh few-shot
+pub fn load_items(path: &Path, template: PromptTemplate) -> Result<(Vec<Item>, Vec<Item>)> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().trim_start_matches('\u{feff}').to_lowercase())
+        .collect::<Vec<_>>();
+    let question = column(&headers, &["soal", "question", "pertanyaan"]);
+    let answer = column(&headers, &["jawaban", "answer", "kunci", "kunci_jawaban"]);
+    let (question, answer) = match (question, answer) {
+        (Some(question), Some(answer)) => (question, answer),
+        _ => anyhow::bail!("{path:?} needs a soal (question) and a jawaban (answer) column"),
+    };
+    let options = column(&headers, &["pilihan", "options", "choices"]);
+    let letters = (0..8)
+        .map_while(|index| {
+            column(
+                &headers,
+                &[label(index).to_ascii_lowercase().to_string().as_str()],
+            )
+        })
+        .collect::<Vec<_>>();
+    if options.is_none() && letters.len() < 2 {
+        anyhow::bail!("{path:?} needs a pilihan (options) column or A, B, ... columns")
+    }
+    let id = column(&headers, &["id", "no"]);
+    let subject = column(&headers, &["subject", "mapel", "mata_pelajaran"]);
+    let level = column(&headers, &["level", "jenjang"]);
+    let grade = column(&headers, &["kelas", "class", "grade"]);
+    let fewshot = column(&headers, &["is_for_fewshot", "fewshot"]);
+
+    let (mut items, mut examples) = (vec![], vec![]);
+    for (index, record) in reader.records().enumerate() {
+        // Baris 1 adalah header
+        let line = index + 2;
+        let record = record.map_err(|err| anyhow::anyhow!("{path:?} line {line}: {err}"))?;
+        let get = |column: Option<usize>| column.and_then(|c| record.get(c)).unwrap_or("").trim();
+        let choices = match options {
+            Some(options) => split_options(get(Some(options))),
+            None => letters
+                .iter()
+                .map(|&c| get(Some(c)).to_string())
+                .filter(|o| !o.is_empty())
+                .collect(),
+        };
+        if choices.len() < 2 {
+            anyhow::bail!("{path:?} line {line}: a question needs at least two choices")
+        }
+        let gold = match gold_index(get(Some(answer)), &choices) {
+            Some(gold) => gold,
+            None => anyhow::bail!(
+                "{path:?} line {line}: answer {:?} is not one of the {} choices",
+                get(Some(answer)),
+                choices.len()
+            ),
+        };
+        // Kelas ditulis bersama jenjang ("SD kelas 5"), rincian hasil per jenjang memakai kata pertamanya
+        let level = match (get(level), get(grade)) {
+            ("", "") => String::new(),
+            (level, "") => level.to_string(),
+            ("", grade) => format!("kelas {grade}"),
+            (level, grade) => format!("{level} kelas {grade}"),
+        };
+        let item = Item {
+            id: match get(id) {
+                "" => (index + 1).to_string(),
+                id => id.to_string(),
+            },
+            subject: get(subject).to_string(),
+            level,
+            query: template.query(get(Some(question)), &choices),
+            choices: (0..choices.len())
+                .map(|i| format!(" {}", label(i)))
+                .collect(),
+            gold,
+        };
+        match get(fewshot).to_lowercase().as_str() {
+            "1" | "true" | "yes" | "ya" => examples.push(item),
+            _ => items.push(item),
+        }
+    }
+    if items.is_empty() {
+        anyhow::bail!("no questions in {path:?}")
+    }
+    Ok((items, examples))
+}
+
+// Fungsi untuk jenjang pendidikan dari level soal, contoh "SMP kelas 8" menjadi "SMP"
+pub fn education_level(level: &str) -> &str {
+    match level.split_whitespace().next() {
+        Some("kelas") | None => level,
+        Some(first) => first,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    #[test]
+    fn options_and_answers_are_parsed() {
+        assert_eq!(
+            split_options("['A. Jakarta', 'B. Bandung', \"C. Medan's\"]"),
+            vec!["Jakarta", "Bandung", "Medan's"]
+        );
+        assert_eq!(
+            split_options("A. satu\nB) dua\n(C) tiga"),
+            vec!["satu", "dua", "tiga"]
+        );
+        let options = vec!["satu".to_string(), "dua".to_string(), "tiga".to_string()];
+        assert_eq!(gold_index("B", &options), Some(1));
+        assert_eq!(gold_index("c.", &options), Some(2));
+        assert_eq!(gold_index("dua", &options), Some(1));
+        assert_eq!(gold_index("D", &options), None);
+        assert_eq!(education_level("SMP kelas 8"), "SMP");
+        assert_eq!(education_level("kelas 8"), "kelas 8");
+    }
+
+    #[test]
+    fn indommlu_csv_is_loaded() -> Result<()> {
+        let dir = TempDir::new("indommlu")?;
+        let path = dir.join("IndoMMLU.csv");
+        std::fs::write(
+            &path,
+            "subject,level,kelas,soal,pilihan,jawaban,is_for_fewshot\n\
+             Biologi,SMA,10,Organ pernapasan ikan adalah,\"A. paru-paru\nB. insang\nC. kulit\nD. trakea\",B,0\n\
+             Biologi,SMA,10,Sel tumbuhan memiliki,\"A. dinding sel\nB. sentriol\",A,1\n\
+             Sejarah,SD,5,Proklamasi dibacakan tahun,\"['A. 1945', 'B. 1949', 'C. 1950']\",A,0\n",
+        )?;
+        let (items, examples) = load_items(&path, PromptTemplate::Indonesian)?;
+        assert_eq!(items.len(), 2);
+        assert_eq!(examples.len(), 1);
+        assert_eq!(items[0].id, "1");
+        assert_eq!(items[0].subject, "Biologi");
+        assert_eq!(items[0].level, "SMA kelas 10");
+        assert_eq!(
+            items[0].query,
+            "Organ pernapasan ikan adalah\nA. paru-paru\nB. insang\nC. kulit\nD. trakea\nJawaban:"
+        );
+        assert_eq!(items[0].choices, vec![" A", " B", " C", " D"]);
+        assert_eq!(items[0].gold, 1);
+        assert_eq!(items[1].choices.len(), 3);
+        assert_eq!(items[1].level, "SD kelas 5");
+        assert_eq!(examples[0].gold, 0);
+        assert_eq!(
+            PromptTemplate::Indonesian.intro("Biologi"),
+            "Berikut adalah soal pilihan ganda (beserta jawabannya) tentang Biologi.\n\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+// Modul untuk journal hasil eval: hasil setiap soal ditambahkan ke file JSONL begitu selesai dinilai, dengan kunci file
+// dan nomor baris soal serta hash model dan template prompt. Dengan --resume soal yang sudah ada di journal (dengan
+// hash yang sama) dilewati, sehingga run yang terputus bisa dilanjutkan tanpa menilai ulang dari awal
+use anyhow::Result;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Fungsi untuk mengubah hasil hash menjadi string heksadesimal, dipakai juga oleh manifest setiap binary
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Fungsi untuk menghitung hash dari beberapa bagian teks, dipakai untuk template prompt dan sidik jari model
+pub fn hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts.iter() {
+        hasher.update(part.len().to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hex(&hasher.finalize()[..8])
+}
+
+// Fungsi untuk menghitung sidik jari model dari deskripsi argumen (model, dtype, kuantisasi, LoRA, sampling) serta
+// path dan ukuran file bobot. Isi file tidak di-hash agar --resume tidak perlu membaca ulang bobot berukuran GB
+pub fn model_fingerprint(description: &str, files: &[PathBuf]) -> Result<String> {
+    let mut parts = vec![description.to_string()];
+    for path in files.iter() {
+        let len = std::fs::metadata(path)?.len();
+        parts.push(format!("{} {len}", path.display()));
+    }
+    Ok(hash(&parts.iter().map(|p| p.as_str()).collect::<Vec<_>>()))
+}
+
+// Struct untuk journal yang terbuka, tanpa path semua soal dinilai dan tidak ada yang dicatat
+pub struct Journal {
+    file: Option<std::fs::File>,
+    hash: String,
+    done: HashMap<(String, usize), Value>,
+    stale: usize,
+}
+
+impl Journal {
+    // Membuka journal: tanpa --resume file yang sudah berisi ditolak agar hasil run sebelumnya tidak tertimpa, dengan
+    // --resume baris yang hash-nya sama dimuat. Baris terakhir yang terpotong karena proses mati diabaikan
+    pub fn open(path: Option<&Path>, resume: bool, hash: String) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if resume => anyhow::bail!("--resume needs a journal file"),
+            None => {
+                return Ok(Self {
+                    file: None,
+                    hash,
+                    done: HashMap::new(),
+                    stale: 0,
+                })
+            }
+        };
+        let existing = match std::fs::read_to_string(path) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => anyhow::bail!("cannot read the journal {path:?}: {err}"),
+        };
+        if !resume && !existing.trim().is_empty() {
+            anyhow::bail!("the journal {path:?} already exists, pass --resume to continue it")
+        }
+        let (mut done, mut stale) = (HashMap::new(), 0);
+        for line in existing.lines() {
+            let entry: Value = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let (file, row) = match (entry["file"].as_str(), entry["row"].as_u64()) {
+                (Some(file), Some(row)) => (file.to_string(), row as usize),
+                _ => continue,
+            };
+            if entry["hash"].as_str() != Some(hash.as_str()) {
+                stale += 1;
+                continue;
+            }
+            done.insert((file, row), entry["result"].clone());
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        // Baris yang terpotong diakhiri dulu agar baris berikutnya tetap bisa dibaca
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            writeln!(file)?;
+        }
+        Ok(Self {
+            file: Some(file),
+            hash,
+            done,
+            stale,
+        })
+    }
+
+    // Hasil soal yang sudah dinilai pada run sebelumnya
+    pub fn get(&self, file: &str, row: usize) -> Option<&Value> {
+        self.done.get(&(file.to_string(), row))
+    }
+
+    // Menambahkan hasil satu soal ke journal, langsung ditulis ke file agar tidak hilang jika proses mati
+    pub fn record(&mut self, file: &str, row: usize, result: &Value) -> Result<()> {
+        if let Some(journal) = self.file.as_mut() {
+            let entry = json!({"file": file, "row": row, "hash": self.hash, "result": result});
+            writeln!(journal, "{entry}")?;
+            journal.flush()?;
+        }
+        Ok(())
+    }
+
+    // Ringkasan journal yang dimuat, None jika tidak ada yang dilanjutkan
+    pub fn summary(&self) -> Option<String> {
+        if self.done.is_empty() && self.stale == 0 {
+            return None;
+        }
+        let mut summary = format!("resuming {} results from the journal", self.done.len());
+        if self.stale > 0 {
+            summary.push_str(&format!(
+                ", {} results of another model or prompt template ignored",
+                self.stale
+            ));
+        }
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    #[test]
+    fn resume_skips_recorded_rows() -> Result<()> {
+        let dir = TempDir::new("journal")?;
+        let path = dir.join("journal.jsonl");
+        let mut journal = Journal::open(Some(&path), false, "h1".to_string())?;
+        assert_eq!(journal.summary(), None);
+        journal.record("a.csv", 0, &json!({"correct": true}))?;
+        journal.record("a.csv", 1, &json!({"correct": false}))?;
+        drop(journal);
+        // Proses mati di tengah menulis baris berikutnya
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        write!(file, r#"{{"file": "a.csv", "row": 2, "ha"#)?;
+        drop(file);
+
+        assert!(Journal::open(Some(&path), false, "h1".to_string()).is_err());
+        let mut journal = Journal::open(Some(&path), true, "h1".to_string())?;
+        assert_eq!(journal.get("a.csv", 0), Some(&json!({"correct": true})));
+        assert_eq!(journal.get("a.csv", 1), Some(&json!({"correct": false})));
+        assert_eq!(journal.get("a.csv", 2), None);
+        assert_eq!(journal.get("b.csv", 0), None);
+        journal.record("a.csv", 2, &json!({"correct": true}))?;
+        drop(journal);
+
+        let journal = Journal::open(Some(&path), true, "h1".to_string())?;
+        assert_eq!(journal.get("a.csv", 2), Some(&json!({"correct": true})));
+        // Model atau template lain: hasil lama tidak dipakai
+        let journal = Journal::open(Some(&path), true, "h2".to_string())?;
+        assert_eq!(journal.get("a.csv", 0), None);
+        assert_eq!(
+            journal.summary().as_deref(),
+            Some("resuming 0 results from the journal, 3 results of another model or prompt template ignored")
+        );
+        assert!(Journal::open(None, true, "h1".to_string()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn hash_depends_on_every_part() {
+        assert_eq!(hash(&["a", "b"]), hash(&["a", "b"]));
+        assert_ne!(hash(&["a", "b"]), hash(&["ab", ""]));
+        assert_ne!(hash(&["a", "b"]), hash(&["a", "c"]));
+        assert_eq!(hash(&["a"]).len(), 16);
+    }
+}
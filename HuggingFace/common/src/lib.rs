@@ -0,0 +1,31 @@
+// Library crate common: kode yang tidak bergantung pada model dan dipakai bersama oleh binary phi, mistral dan rwkv.
+// Setiap binary hanya mengimplementasikan worker::Backend untuk modelnya, sisanya (eval, journal, chat, mode worker)
+// ada di sini
+
+// Mencetak log ke stdout, atau ke stderr pada mode --worker karena stdout dipakai untuk response JSON
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if crate::worker::is_active() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+pub mod budget; // Untuk Ctrl-C dan batas waktu generasi
+pub mod chat; // Untuk template chat
+pub mod compare; // Untuk membandingkan dua hasil eval per soal
+pub mod context; // Untuk batas jendela konteks
+pub mod eval; // Untuk evaluasi pilihan ganda dari file JSONL (ARC, HellaSwag, PIQA, COPA) dan CSV IndoMMLU
+pub mod fixtures; // Untuk fixture test offline (direktori sementara, file golden, tokenizer dan bobot acak)
+pub mod gen_eval; // Untuk evaluasi QA generatif (gaya GSM8K) dengan ekstraksi jawaban regex
+pub mod indommlu; // Untuk soal IndoMMLU dari CSV dan template prompt bahasa Indonesia
+pub mod journal; // Untuk journal hasil eval yang bisa dilanjutkan dengan --resume
+#[cfg(feature = "candle")]
+pub mod lora; // Untuk memuat dan menggabungkan adapter LoRA
+pub mod manifest; // Untuk manifest run (hash file, fitur, argumen dan output) dan replay
+pub mod memory; // Untuk perkiraan memori model dari header file bobot sebelum bobot dimuat
+pub mod quantize; // Untuk subcommand quantize dari safetensors ke GGUF
+pub mod token_stream; // Untuk detokenisasi bertahap yang aman untuk UTF-8
+pub mod worker; // Untuk mode --worker (request JSON per baris lewat stdin dan stdout)
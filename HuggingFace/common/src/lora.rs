@@ -0,0 +1,344 @@
+// Modul untuk memuat adapter LoRA (format PEFT) dan menggabungkannya ke bobot model sebelum model dibangun
+use anyhow::Result;
+use clap::Args as ClapArgs;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use candle_core::{DType, Device, Tensor}; // Untuk operasi tensor
+
+// Struct untuk satu adapter LoRA beserta skala penggabungannya
+#[derive(Clone, Debug)]
+pub struct LoraAdapter {
+    dir: PathBuf,
+    scale: f64,
+}
+
+// Fungsi untuk mem-parsing argumen --lora dengan format `dir` atau `dir:scale`
+pub fn parse_lora(s: &str) -> Result<LoraAdapter, String> {
+    // Hanya bagian setelah ':' terakhir yang berupa angka yang dianggap skala (agar path Windows tetap valid)
+    if let Some((dir, scale)) = s.rsplit_once(':') {
+        if let Ok(scale) = scale.parse::<f64>() {
+            return Ok(LoraAdapter {
+                dir: PathBuf::from(dir),
+                scale,
+            });
+        }
+    }
+    Ok(LoraAdapter {
+        dir: PathBuf::from(s),
+        scale: 1.0,
+    })
+}
+
+// Struct untuk isi adapter_config.json yang diperlukan saat penggabungan
+#[derive(Debug, Deserialize)]
+struct AdapterConfig {
+    r: usize,
+    lora_alpha: f64,
+    #[serde(default)]
+    fan_in_fan_out: bool,
+    #[serde(default)]
+    use_rslora: bool,
+}
+
+// Fungsi untuk mengubah nama tensor PEFT menjadi nama modul pada model dasar beserta jenis matriksnya (A atau B)
+fn target_name(name: &str) -> Option<(String, bool)> {
+    let name = name.strip_prefix("base_model.model.").unwrap_or(name);
+    let name = name.replace(".default", "");
+    if let Some(module) = name.strip_suffix(".lora_A.weight") {
+        Some((format!("{module}.weight"), true))
+    } else {
+        name.strip_suffix(".lora_B.weight")
+            .map(|module| (format!("{module}.weight"), false))
+    }
+}
+
+// Fungsi untuk menggabungkan satu adapter LoRA ke dalam bobot, mengembalikan jumlah modul yang digabung
+pub fn merge(tensors: &mut HashMap<String, Tensor>, adapter: &LoraAdapter) -> Result<usize> {
+    let config = std::fs::read_to_string(adapter.dir.join("adapter_config.json"))?;
+    let config: AdapterConfig = serde_json::from_str(&config)?;
+    let rank = if config.use_rslora {
+        (config.r as f64).sqrt()
+    } else {
+        config.r as f64
+    };
+    let scaling = config.lora_alpha / rank * adapter.scale;
+
+    // Mengelompokkan matriks lora_A dan lora_B berdasarkan modul targetnya
+    let weights = candle_core::safetensors::load(
+        adapter.dir.join("adapter_model.safetensors"),
+        &Device::Cpu,
+    )?;
+    let mut pairs: HashMap<String, (Option<Tensor>, Option<Tensor>)> = HashMap::new();
+    for (name, tensor) in weights {
+        if let Some((target, is_a)) = target_name(&name) {
+            let entry = pairs.entry(target).or_default();
+            if is_a {
+                entry.0 = Some(tensor)
+            } else {
+                entry.1 = Some(tensor)
+            }
+        }
+    }
+
+    let mut merged = 0usize;
+    for (target, pair) in pairs {
+        let (a, b) = match pair {
+            (Some(a), Some(b)) => (a, b),
+            _ => anyhow::bail!("incomplete lora weights for {target} in {:?}", adapter.dir),
+        };
+        let weight = match tensors.get(&target) {
+            Some(weight) => weight,
+            None => anyhow::bail!("lora target {target} not found in the base weights"),
+        };
+        // W' = W + scaling * (B @ A)
+        let delta = b.to_dtype(DType::F32)?.matmul(&a.to_dtype(DType::F32)?)?;
+        let delta = if config.fan_in_fan_out {
+            delta.t()?
+        } else {
+            delta
+        };
+        let merged_weight = (weight.to_dtype(DType::F32)? + (delta * scaling)?)?;
+        let merged_weight = merged_weight.to_dtype(weight.dtype())?;
+        tensors.insert(target, merged_weight);
+        merged += 1;
+    }
+    log!(
+        "merged {merged} lora modules from {:?} (scaling {scaling:.3})",
+        adapter.dir
+    );
+    Ok(merged)
+}
+
+// Fungsi untuk memuat bobot dasar lalu menggabungkan semua adapter LoRA secara berurutan
+pub fn load_merged(
+    filenames: &[PathBuf],
+    adapters: &[LoraAdapter],
+) -> Result<HashMap<String, Tensor>> {
+    let mut tensors = HashMap::new();
+    for filename in filenames.iter() {
+        tensors.extend(candle_core::safetensors::load(filename, &Device::Cpu)?);
+    }
+    for adapter in adapters.iter() {
+        merge(&mut tensors, adapter)?;
+    }
+    Ok(tensors)
+}
+
+// Argumen untuk subcommand export-merged
+#[derive(ClapArgs, Debug)]
+pub struct ExportMergedArgs {
+    /// The base safetensors files, comma separated.
+    #[arg(long)]
+    weight_files: String,
+
+    /// LoRA adapter directory (PEFT format), optionally followed by `:<scale>`. Can be repeated.
+    #[arg(long, required = true, value_parser = parse_lora)]
+    lora: Vec<LoraAdapter>,
+
+    /// The safetensors file to write.
+    #[arg(long)]
+    out_file: PathBuf,
+}
+
+// Fungsi utama subcommand export-merged untuk menulis bobot hasil penggabungan ke safetensors
+pub fn export_merged(args: ExportMergedArgs) -> Result<()> {
+    let start = std::time::Instant::now();
+    let filenames = args
+        .weight_files
+        .split(',')
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    let tensors = load_merged(&filenames, &args.lora)?;
+    candle_core::safetensors::save(&tensors, &args.out_file)?;
+    println!(
+        "wrote {} tensors to {:?} in {:?}",
+        tensors.len(),
+        args.out_file,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    // Matriks A (r x in) dan B (out x r) kecil dengan nilai bulat agar hasil penggabungan bisa dibandingkan persis
+    const A: [[f32; 3]; 2] = [[1., 0., 2.], [0., 1., -1.]];
+    const B: [[f32; 2]; 2] = [[1., 2.], [-1., 0.5]];
+    const W: [[f32; 3]; 2] = [[0.5, -1., 0.], [2., 0., 1.]];
+
+    // Fungsi untuk menulis adapter PEFT ke direktori sementara, nama modul memakai prefix dan .default seperti PEFT
+    fn write_adapter(
+        dir: &TempDir,
+        name: &str,
+        config: &str,
+        a: &Tensor,
+        b: &Tensor,
+    ) -> Result<PathBuf> {
+        let path = dir.join(name);
+        std::fs::create_dir_all(&path)?;
+        std::fs::write(path.join("adapter_config.json"), config)?;
+        let prefix = "base_model.model.model.layers.0.self_attn.q_proj";
+        let weights = HashMap::from([
+            (format!("{prefix}.lora_A.default.weight"), a.clone()),
+            (format!("{prefix}.lora_B.default.weight"), b.clone()),
+        ]);
+        candle_core::safetensors::save(&weights, path.join("adapter_model.safetensors"))?;
+        Ok(path)
+    }
+
+    // Fungsi untuk menghitung W + scaling * (B @ A) dengan loop biasa, transpose jika fan_in_fan_out
+    fn expected(w: &[Vec<f32>], scaling: f32, fan_in_fan_out: bool) -> Vec<Vec<f32>> {
+        let mut out = w.to_vec();
+        for (o, b_row) in B.iter().enumerate() {
+            for i in 0..A[0].len() {
+                let delta: f32 = (0..A.len()).map(|k| b_row[k] * A[k][i]).sum();
+                if fan_in_fan_out {
+                    out[i][o] += scaling * delta
+                } else {
+                    out[o][i] += scaling * delta
+                }
+            }
+        }
+        out
+    }
+
+    // Fungsi untuk mem-parsing argumen --lora seperti clap
+    fn adapter(s: &str) -> Result<LoraAdapter> {
+        parse_lora(s).map_err(anyhow::Error::msg)
+    }
+
+    // Bobot dasar dengan modul target q_proj dan modul lain yang tidak disentuh adapter
+    fn base(w: &Tensor) -> HashMap<String, Tensor> {
+        HashMap::from([
+            (
+                "model.layers.0.self_attn.q_proj.weight".to_string(),
+                w.clone(),
+            ),
+            (
+                "model.layers.0.self_attn.k_proj.weight".to_string(),
+                w.clone(),
+            ),
+        ])
+    }
+
+    // Bobot q_proj setelah penggabungan
+    fn merged(tensors: &HashMap<String, Tensor>) -> Result<Vec<Vec<f32>>> {
+        Ok(tensors["model.layers.0.self_attn.q_proj.weight"].to_vec2()?)
+    }
+
+    #[test]
+    fn peft_names_map_to_base_weights() {
+        assert_eq!(
+            target_name("base_model.model.model.layers.3.mlp.fc1.lora_A.default.weight"),
+            Some(("model.layers.3.mlp.fc1.weight".to_string(), true))
+        );
+        assert_eq!(
+            target_name("model.layers.3.self_attn.dense.lora_B.weight"),
+            Some(("model.layers.3.self_attn.dense.weight".to_string(), false))
+        );
+        assert_eq!(target_name("base_model.model.lm_head.weight"), None);
+    }
+
+    #[test]
+    fn merge_adds_scaled_delta() -> Result<()> {
+        let dir = TempDir::new("lora-merge")?;
+        let (a, b) = (
+            Tensor::new(&A, &Device::Cpu)?,
+            Tensor::new(&B, &Device::Cpu)?,
+        );
+        let w = Tensor::new(&W, &Device::Cpu)?;
+        let w_rows = w.to_vec2::<f32>()?;
+
+        // alpha / r = 4 / 2 dikali skala adapter 0.5
+        let plain = write_adapter(&dir, "plain", r#"{"r": 2, "lora_alpha": 4}"#, &a, &b)?;
+        let mut tensors = base(&w);
+        let lora = adapter(&format!("{}:0.5", plain.display()))?;
+        assert_eq!(merge(&mut tensors, &lora)?, 1);
+        assert_eq!(merged(&tensors)?, expected(&w_rows, 1., false));
+        // Modul yang bukan target tidak berubah
+        assert_eq!(
+            tensors["model.layers.0.self_attn.k_proj.weight"].to_vec2::<f32>()?,
+            w_rows
+        );
+
+        // rslora memakai alpha / sqrt(r) = 4 / sqrt(4), r di config hanya dipakai untuk skala
+        let rslora = write_adapter(
+            &dir,
+            "rslora",
+            r#"{"r": 4, "lora_alpha": 4, "use_rslora": true}"#,
+            &a,
+            &b,
+        )?;
+        let mut tensors = base(&w);
+        merge(&mut tensors, &adapter(&rslora.to_string_lossy())?)?;
+        assert_eq!(merged(&tensors)?, expected(&w_rows, 2., false));
+
+        // fan_in_fan_out menyimpan bobot sebagai (in, out) sehingga delta ditranspose
+        let conv1d = write_adapter(
+            &dir,
+            "conv1d",
+            r#"{"r": 2, "lora_alpha": 2, "fan_in_fan_out": true}"#,
+            &a,
+            &b,
+        )?;
+        let w_t = w.t()?.contiguous()?;
+        let mut tensors = base(&w_t);
+        merge(&mut tensors, &adapter(&conv1d.to_string_lossy())?)?;
+        assert_eq!(merged(&tensors)?, expected(&w_t.to_vec2()?, 1., true));
+        Ok(())
+    }
+
+    #[test]
+    fn adapters_are_stacked() -> Result<()> {
+        let dir = TempDir::new("lora-stack")?;
+        let (a, b) = (
+            Tensor::new(&A, &Device::Cpu)?,
+            Tensor::new(&B, &Device::Cpu)?,
+        );
+        let w = Tensor::new(&W, &Device::Cpu)?;
+        let first = write_adapter(&dir, "first", r#"{"r": 2, "lora_alpha": 2}"#, &a, &b)?;
+        let second = write_adapter(&dir, "second", r#"{"r": 2, "lora_alpha": 8}"#, &a, &b)?;
+        let weights = dir.join("model.safetensors");
+        candle_core::safetensors::save(&base(&w), &weights)?;
+
+        let adapters = [
+            adapter(&first.to_string_lossy())?,
+            adapter(&format!("{}:0.5", second.display()))?,
+        ];
+        let tensors = load_merged(&[weights], &adapters)?;
+        // Delta kedua adapter dijumlahkan: skala 2 / 2 = 1 ditambah 8 / 2 * 0.5 = 2
+        assert_eq!(merged(&tensors)?, expected(&w.to_vec2()?, 3., false));
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_or_unknown_targets_are_rejected() -> Result<()> {
+        let dir = TempDir::new("lora-errors")?;
+        let (a, b) = (
+            Tensor::new(&A, &Device::Cpu)?,
+            Tensor::new(&B, &Device::Cpu)?,
+        );
+        let w = Tensor::new(&W, &Device::Cpu)?;
+        let path = write_adapter(&dir, "adapter", r#"{"r": 2, "lora_alpha": 2}"#, &a, &b)?;
+        let lora = adapter(&path.to_string_lossy())?;
+        let mut other = HashMap::from([("model.layers.0.mlp.fc1.weight".to_string(), w)]);
+        let err = merge(&mut other, &lora).unwrap_err().to_string();
+        assert!(err.contains("not found in the base weights"), "{err}");
+
+        candle_core::safetensors::save(
+            &HashMap::from([(
+                "base_model.model.model.layers.0.self_attn.q_proj.lora_A.weight".to_string(),
+                a,
+            )]),
+            path.join("adapter_model.safetensors"),
+        )?;
+        let err = merge(&mut other, &lora).unwrap_err().to_string();
+        assert!(err.contains("incomplete lora weights"), "{err}");
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+// Modul untuk menulis manifest run (hash file, fitur, argumen, dan hash output) dan menjalankan ulang (replay) sebuah manifest
+use anyhow::Result;
+use clap::Args as ClapArgs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::budget::FinishReason;
+use crate::journal::hex;
+
+// Struct untuk hash SHA-256 dari satu file yang dipakai saat run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDigest {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+// Struct manifest yang berisi semua informasi yang diperlukan untuk mereproduksi sebuah run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub crate_name: String,
+    pub crate_version: String,
+    pub candle_version: String,
+    pub cpu_features: String,
+    pub cargo_features: Vec<String>,
+    pub args: Vec<String>,
+    pub files: Vec<FileDigest>,
+    pub output_sha256: String,
+    pub output: String,
+    #[serde(default)]
+    pub finish_reason: String,
+}
+
+// Struct untuk informasi build yang hanya diketahui binary pemanggil: nama dan versi crate, versi candle dari build.rs,
+// fitur CPU candle dan fitur cargo yang aktif
+#[derive(Debug, Clone)]
+pub struct BuildInfo {
+    pub crate_name: &'static str,
+    pub crate_version: &'static str,
+    pub candle_version: &'static str,
+    pub cpu_features: String,
+    pub cargo_features: Vec<String>,
+}
+
+// Fungsi untuk mengambil nama fitur cargo yang aktif dari daftar (nama, cfg!(feature = nama)) milik binary
+pub fn enabled_features(features: &[(&str, bool)]) -> Vec<String> {
+    features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+// Fungsi untuk menghitung hash SHA-256 dari sebuah file secara streaming (file bobot bisa berukuran GB)
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+// Fungsi untuk mengambil argumen baris perintah tanpa --manifest (agar replay tidak menimpa manifest asli)
+fn recorded_args() -> Vec<String> {
+    let mut args = vec![];
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--manifest" {
+            iter.next();
+        } else if !arg.starts_with("--manifest=") {
+            args.push(arg)
+        }
+    }
+    args
+}
+
+impl Manifest {
+    pub fn new(
+        build: &BuildInfo,
+        files: &[PathBuf],
+        output: &str,
+        finish_reason: FinishReason,
+    ) -> Result<Self> {
+        let files = files
+            .iter()
+            .map(|path| {
+                Ok(FileDigest {
+                    path: path.clone(),
+                    sha256: file_sha256(path)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            crate_name: build.crate_name.to_string(),
+            crate_version: build.crate_version.to_string(),
+            candle_version: build.candle_version.to_string(),
+            cpu_features: build.cpu_features.clone(),
+            cargo_features: build.cargo_features.clone(),
+            args: recorded_args(),
+            files,
+            output_sha256: hex(&Sha256::digest(output.as_bytes())),
+            output: output.to_string(),
+            finish_reason: finish_reason.to_string(),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path.as_ref(), serde_json::to_string_pretty(self)?)?;
+        println!("wrote the run manifest to {:?}", path.as_ref());
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+// Argumen untuk subcommand replay
+#[derive(ClapArgs, Debug)]
+pub struct ReplayArgs {
+    /// The manifest written by a previous run with --manifest.
+    manifest: PathBuf,
+}
+
+// Fungsi untuk menampilkan perbedaan pertama antara dua output
+fn print_diff(expected: &str, actual: &str) {
+    let prefix = expected
+        .chars()
+        .zip(actual.chars())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let tail = |s: &str| s.chars().skip(prefix).take(200).collect::<String>();
+    println!("outputs differ after {prefix} characters");
+    println!("expected: {:?}", tail(expected));
+    println!("actual:   {:?}", tail(actual));
+}
+
+// Fungsi untuk membandingkan satu bagian manifest dan menampilkan perbedaannya
+fn differs<T: std::fmt::Debug + PartialEq>(name: &str, expected: &T, actual: &T) -> bool {
+    if expected != actual {
+        println!("{name} differs: {expected:?} vs {actual:?}");
+    }
+    expected != actual
+}
+
+// Fungsi utama subcommand replay: menjalankan ulang binary dengan argumen yang tercatat lalu membandingkan hasilnya
+pub fn replay(args: ReplayArgs) -> Result<()> {
+    let expected = Manifest::load(&args.manifest)?;
+    let replayed_path = args.manifest.with_extension("replay.json");
+    println!(
+        "replaying {:?} with args {:?}",
+        args.manifest, expected.args
+    );
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args(&expected.args)
+        .arg("--manifest")
+        .arg(&replayed_path)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("the replayed run failed with {status}")
+    }
+    let actual = Manifest::load(&replayed_path)?;
+    compare(&expected, &actual)
+}
+
+// Fungsi untuk membandingkan manifest asli dengan manifest hasil replay, error jika hash output berbeda
+fn compare(expected: &Manifest, actual: &Manifest) -> Result<()> {
+    // Membandingkan lingkungan run yang bisa memengaruhi hasil
+    let mismatches = [
+        differs(
+            "candle version",
+            &expected.candle_version,
+            &actual.candle_version,
+        ),
+        differs("cpu features", &expected.cpu_features, &actual.cpu_features),
+        differs(
+            "cargo features",
+            &expected.cargo_features,
+            &actual.cargo_features,
+        ),
+        differs("files", &expected.files, &actual.files),
+    ]
+    .iter()
+    .filter(|d| **d)
+    .count();
+    if mismatches > 0 {
+        println!("{mismatches} environment differences, the output may not be reproducible");
+    }
+    // Run yang dihentikan oleh Ctrl-C atau batas waktu tidak selalu berhenti di token yang sama
+    if expected.finish_reason != actual.finish_reason {
+        println!(
+            "finish reason differs: {:?} vs {:?}",
+            expected.finish_reason, actual.finish_reason
+        );
+    }
+
+    if expected.output_sha256 == actual.output_sha256 {
+        println!("output matches ({})", actual.output_sha256);
+        Ok(())
+    } else {
+        print_diff(&expected.output, &actual.output);
+        anyhow::bail!(
+            "output hash {} differs from the manifest hash {}",
+            actual.output_sha256,
+            expected.output_sha256
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+
+    #[test]
+    fn manifest_round_trips_and_replay_reports_changed_output() -> Result<()> {
+        let dir = TempDir::new("manifest")?;
+        let weights = dir.join("model.safetensors");
+        std::fs::write(&weights, b"bobot")?;
+        let build = BuildInfo {
+            crate_name: "phi",
+            crate_version: "0.1.0",
+            candle_version: "0.4.1",
+            cpu_features: "avx: true, neon: false, simd128: false, f16c: true".to_string(),
+            cargo_features: enabled_features(&[("mkl", true), ("metal", false)]),
+        };
+        let manifest = Manifest::new(
+            &build,
+            std::slice::from_ref(&weights),
+            "Jakarta adalah ibu kota",
+            FinishReason::Length,
+        )?;
+        assert_eq!(manifest.files[0].sha256, hex(&Sha256::digest(b"bobot")));
+        assert_eq!(manifest.finish_reason, "length");
+        assert_eq!(manifest.cargo_features, ["mkl"]);
+
+        let path = dir.join("run.json");
+        manifest.save(&path)?;
+        let loaded = Manifest::load(&path)?;
+        assert_eq!(loaded, manifest);
+        compare(&manifest, &loaded)?;
+
+        // Perbedaan lingkungan hanya dilaporkan, perbedaan output membuat replay gagal
+        let mut replayed = loaded.clone();
+        replayed.files[0].sha256 = hex(&Sha256::digest(b"bobot lain"));
+        replayed.finish_reason = FinishReason::Timeout.to_string();
+        replayed.cpu_features = "avx: false, neon: false, simd128: false, f16c: false".to_string();
+        compare(&manifest, &replayed)?;
+        let output = "Jakarta adalah kota terbesar";
+        replayed.output = output.to_string();
+        replayed.output_sha256 = hex(&Sha256::digest(output.as_bytes()));
+        let err = compare(&manifest, &replayed).unwrap_err().to_string();
+        assert!(err.contains(&replayed.output_sha256), "{err}");
+        assert!(err.contains(&manifest.output_sha256), "{err}");
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+// Modul untuk memperkirakan memori model dari header file bobot (safetensors atau GGUF) sebelum bobot dimuat, lalu
+// membandingkannya dengan memori yang tersedia agar model yang tidak muat ditolak sebelum proses dibunuh OOM killer
+// Fungsi yang memakai tipe candle hanya ada dengan fitur candle, rwkv memakai candle dari git dan menghitung ukuran
+// GGUF sendiri
+use anyhow::Result;
+#[cfg(feature = "candle")]
+use candle_core::{quantized::gguf_file, DType};
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Ruang tambahan untuk aktivasi, tokenizer dan runtime di luar bobot dan cache
+const HEADROOM: u64 = 512 << 20;
+
+// Struct untuk perkiraan memori resident model dalam byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    pub weights: u64,
+    pub cache: u64,
+    // Nama cache di pesan: "cache" untuk kv cache, "state" untuk State RWKV yang ukurannya tetap
+    pub cache_name: &'static str,
+}
+
+impl Estimate {
+    // Total memori yang dibutuhkan termasuk ruang tambahan
+    pub fn total(&self) -> u64 {
+        self.weights + self.cache + HEADROOM
+    }
+}
+
+// Fungsi untuk menampilkan ukuran dalam GiB
+pub fn gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1u64 << 30) as f64)
+}
+
+// Fungsi untuk membaca header JSON file safetensors (nama, dtype, shape dan offset setiap tensor) tanpa membaca
+// bobotnya
+pub fn safetensors_header(path: &Path) -> Result<serde_json::Map<String, Value>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut len = [0u8; 8];
+    file.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    // Header safetensors dibatasi 100 MB, file yang lebih besar hampir pasti bukan safetensors
+    if len > 100_000_000 {
+        anyhow::bail!("{path:?} is not a safetensors file (header of {len} bytes)")
+    }
+    let mut header = vec![0u8; len as usize];
+    file.read_exact(&mut header)?;
+    match serde_json::from_slice(&header)? {
+        Value::Object(header) => Ok(header),
+        _ => anyhow::bail!("{path:?} is not a safetensors file"),
+    }
+}
+
+// Fungsi untuk menghitung jumlah elemen semua tensor di file safetensors dari headernya
+pub fn safetensors_elements(files: &[PathBuf]) -> Result<u64> {
+    let mut elements = 0u64;
+    for file in files.iter() {
+        for (name, info) in safetensors_header(file)?.iter() {
+            if name == "__metadata__" {
+                continue;
+            }
+            let shape = match info["shape"].as_array() {
+                Some(shape) => shape,
+                None => anyhow::bail!("the tensor {name} of {file:?} has no shape"),
+            };
+            elements += shape
+                .iter()
+                .map(|dim| dim.as_u64().unwrap_or(0))
+                .product::<u64>();
+        }
+    }
+    Ok(elements)
+}
+
+// Fungsi untuk menghitung ukuran bobot safetensors setelah dikonversi ke dtype. Bobot di-mmap lalu disalin ke dtype
+// tujuan, sehingga yang tinggal di memori adalah jumlah elemen dikali ukuran dtype, bukan ukuran file
+#[cfg(feature = "candle")]
+pub fn safetensors_bytes(files: &[PathBuf], dtype: DType) -> Result<u64> {
+    Ok(safetensors_elements(files)? * dtype.size_in_bytes() as u64)
+}
+
+// Fungsi untuk menghitung ukuran tensor file GGUF, tensor terkuantisasi dimuat ke memori dalam blok aslinya
+#[cfg(feature = "candle")]
+pub fn gguf_bytes(path: &Path) -> Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let content = gguf_file::Content::read(&mut file)
+        .map_err(|err| anyhow::anyhow!("cannot read the GGUF header of {path:?}: {err}"))?;
+    Ok(content
+        .tensor_infos
+        .values()
+        .map(|info| {
+            let blocks = info.shape.elem_count() / info.ggml_dtype.block_size();
+            (blocks * info.ggml_dtype.type_size()) as u64
+        })
+        .sum())
+}
+
+// Fungsi untuk menghitung ukuran kv cache batch sequence sepanjang context token, kv_width adalah jumlah head kv
+// dikali dimensi head. Generasi batch dan MMLU membuat satu kv cache untuk setiap baris
+#[cfg(feature = "candle")]
+pub fn kv_cache_bytes(
+    layers: usize,
+    kv_width: usize,
+    context: usize,
+    batch: usize,
+    dtype: DType,
+) -> u64 {
+    (2 * layers * kv_width * context * batch * dtype.size_in_bytes()) as u64
+}
+
+// Fungsi untuk membaca MemAvailable dari isi /proc/meminfo, dalam byte
+fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    meminfo.lines().find_map(|line| {
+        let value = line.strip_prefix("MemAvailable:")?;
+        let kb = value
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+        Some(kb * 1024)
+    })
+}
+
+// Fungsi untuk membaca memori yang tersedia, None jika /proc/meminfo tidak ada (bukan Linux)
+pub fn available_bytes() -> Option<u64> {
+    parse_mem_available(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+// Fungsi untuk membandingkan perkiraan dengan memori yang tersedia. Jika tidak muat, error berisi saran (contoh
+// --quantized atau --dtype bf16) dan cara melewati pengecekan
+pub fn check(estimate: &Estimate, available: Option<u64>, suggestions: &[String]) -> Result<()> {
+    let summary = format!(
+        "{} (weights {}, {} {}, headroom {})",
+        gib(estimate.total()),
+        gib(estimate.weights),
+        estimate.cache_name,
+        gib(estimate.cache),
+        gib(HEADROOM)
+    );
+    let available = match available {
+        Some(available) => available,
+        None => {
+            log!("estimated memory: {summary}, available memory unknown");
+            return Ok(());
+        }
+    };
+    if estimate.total() <= available {
+        log!("estimated memory: {summary}, available {}", gib(available));
+        return Ok(());
+    }
+    let mut message = format!(
+        "the model needs about {summary} but only {} is available",
+        gib(available)
+    );
+    if !suggestions.is_empty() {
+        message.push_str(&format!(", try {}", suggestions.join(" or ")));
+    }
+    anyhow::bail!("{message}, or pass --no-memory-check to load it anyway")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "candle")]
+    #[test]
+    fn weights_and_cache_are_estimated_from_headers() -> Result<()> {
+        use crate::fixtures::TempDir;
+        use candle_core::{Device, Tensor};
+        use std::collections::HashMap;
+
+        let dir = TempDir::new("memory")?;
+        let path = dir.join("model.safetensors");
+        let tensors = HashMap::from([
+            (
+                "embed".to_string(),
+                Tensor::zeros((10, 4), DType::F32, &Device::Cpu)?,
+            ),
+            (
+                "norm".to_string(),
+                Tensor::zeros(4, DType::BF16, &Device::Cpu)?,
+            ),
+        ]);
+        candle_core::safetensors::save(&tensors, &path)?;
+        let files = [path];
+        // Ukuran mengikuti dtype tujuan, bukan dtype yang tersimpan di file
+        assert_eq!(safetensors_bytes(&files, DType::F32)?, 44 * 4);
+        assert_eq!(safetensors_bytes(&files, DType::BF16)?, 44 * 2);
+        assert_eq!(safetensors_elements(&files)?, 44);
+        assert!(safetensors_bytes(&[dir.join("missing.safetensors")], DType::F32).is_err());
+        // Phi-2: 32 layer dengan lebar 2560, jendela 2048 token
+        assert_eq!(kv_cache_bytes(32, 2560, 2048, 1, DType::F32), 1280 << 20);
+        // --batch-size 16 membuat 16 kv cache
+        assert_eq!(kv_cache_bytes(32, 2560, 2048, 16, DType::F32), 20 << 30);
+        // Mistral 7B: 32 layer, 8 head kv dengan dimensi 128, jendela 4096 token
+        assert_eq!(kv_cache_bytes(32, 8 * 128, 4096, 1, DType::BF16), 512 << 20);
+        Ok(())
+    }
+
+    #[test]
+    fn check_refuses_models_that_do_not_fit() {
+        let meminfo = "MemTotal:       16318412 kB\nMemFree:          512000 kB\nMemAvailable:    8000000 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(8_000_000 * 1024));
+        assert_eq!(parse_mem_available("MemTotal: 1 kB\n"), None);
+
+        let estimate = Estimate {
+            weights: 4 << 30,
+            cache: 1 << 30,
+            cache_name: "state",
+        };
+        assert!(check(&estimate, Some(8 << 30), &[]).is_ok());
+        assert!(check(&estimate, None, &[]).is_ok());
+        let suggestions = ["--quantized".to_string()];
+        let err = check(&estimate, Some(4 << 30), &suggestions)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("needs about 5.5 GiB"), "{err}");
+        assert!(err.contains("state 1.0 GiB"), "{err}");
+        assert!(err.contains("only 4.0 GiB is available"), "{err}");
+        assert!(err.contains("try --quantized"), "{err}");
+        assert!(err.contains("--no-memory-check"), "{err}");
+    }
+}
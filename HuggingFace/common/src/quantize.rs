@@ -0,0 +1,287 @@
+// Modul untuk subcommand quantize yang mengubah bobot safetensors menjadi file GGUF terkuantisasi. Pilihan tipe per
+// tensor tidak bergantung pada candle, sedangkan penulisan GGUF memakai candle dari crates.io (fitur candle), rwkv
+// menulis GGUF sendiri dengan candle dari git
+use anyhow::Result;
+use clap::{Args as ClapArgs, ValueEnum};
+#[cfg(feature = "candle")]
+use std::collections::HashMap;
+
+#[cfg(feature = "candle")]
+use candle_core::quantized::{gguf_file, GgmlDType, QTensor}; // Untuk kuantisasi dan penulisan GGUF
+#[cfg(feature = "candle")]
+use candle_core::{DType, Device, Tensor}; // Untuk operasi tensor
+
+// Enum untuk tipe kuantisasi yang bisa dipilih oleh pengguna
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+pub enum QuantType {
+    F32,
+    F16,
+    #[value(name = "q4_0")]
+    Q4_0,
+    Q4k,
+    Q5k,
+    #[value(name = "q8_0")]
+    Q8_0,
+}
+
+impl QuantType {
+    // Mengubah QuantType menjadi GgmlDType milik candle
+    #[cfg(feature = "candle")]
+    pub fn dtype(&self) -> GgmlDType {
+        match self {
+            Self::F32 => GgmlDType::F32,
+            Self::F16 => GgmlDType::F16,
+            Self::Q4_0 => GgmlDType::Q4_0,
+            Self::Q4k => GgmlDType::Q4K,
+            Self::Q5k => GgmlDType::Q5K,
+            Self::Q8_0 => GgmlDType::Q8_0,
+        }
+    }
+
+    // Jumlah elemen dalam satu blok ggml, dimensi terakhir tensor harus habis dibagi ukuran ini
+    pub fn block_size(&self) -> usize {
+        match self {
+            Self::F32 | Self::F16 => 1,
+            Self::Q4_0 | Self::Q8_0 => 32,
+            Self::Q4k | Self::Q5k => 256,
+        }
+    }
+
+    // Id tipe file ggml (LLAMA_FTYPE_*) untuk metadata general.file_type. Semua matriks memakai tipe yang sama,
+    // sehingga q4k dan q5k adalah varian _S (tanpa tensor yang dinaikkan ke q6k)
+    pub fn file_type(&self) -> u32 {
+        match self {
+            Self::F32 => 0,
+            Self::F16 => 1,
+            Self::Q4_0 => 2,
+            Self::Q8_0 => 7,
+            Self::Q4k => 14,
+            Self::Q5k => 16,
+        }
+    }
+}
+
+// Fungsi untuk mem-parsing override per tensor dengan format `pola=tipe`, contoh `embed=f16`
+fn parse_override(s: &str) -> Result<(String, QuantType), String> {
+    let (pattern, ty) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <pattern>=<type>, got {s}"))?;
+    let ty = QuantType::from_str(ty, true)?;
+    Ok((pattern.to_string(), ty))
+}
+
+// Argumen untuk subcommand quantize
+#[derive(ClapArgs, Debug)]
+pub struct QuantizeArgs {
+    /// The safetensors files to quantize, comma separated.
+    #[arg(long)]
+    pub weight_files: String,
+
+    /// The GGUF file to write.
+    #[arg(long)]
+    pub out_file: std::path::PathBuf,
+
+    /// The quantization type applied to the weight matrices.
+    #[arg(long, value_enum, default_value = "q4k")]
+    pub quantization: QuantType,
+
+    /// Per-tensor override in the form `<pattern>=<type>`, e.g. `embed=f16`. The first
+    /// override whose pattern is a substring of the tensor name wins.
+    #[arg(long = "override", value_parser = parse_override)]
+    pub overrides: Vec<(String, QuantType)>,
+}
+
+// Fungsi untuk menentukan tipe kuantisasi dari sebuah tensor berdasarkan nama dan bentuknya
+pub fn tensor_type(name: &str, dims: &[usize], args: &QuantizeArgs) -> QuantType {
+    let requested = args
+        .overrides
+        .iter()
+        .find(|(pattern, _)| name.contains(pattern.as_str()))
+        .map(|(_, ty)| *ty);
+    // Hanya matriks bobot yang dikuantisasi, bias dan layer norm tetap f32
+    let requested = match requested {
+        Some(ty) => ty,
+        None if dims.len() == 2 && name.ends_with(".weight") => args.quantization,
+        None => QuantType::F32,
+    };
+    // Jika dimensi terakhir tidak habis dibagi ukuran blok, maka tensor disimpan dalam f16
+    let last_dim = dims.last().copied().unwrap_or(0);
+    if last_dim % requested.block_size() != 0 {
+        println!(
+            "{name}: last dim {last_dim} not divisible by the {requested:?} block size, using f16"
+        );
+        return QuantType::F16;
+    }
+    requested
+}
+
+// Fungsi utama subcommand quantize: architecture ditulis ke general.architecture, rename mengganti nama tensor ke
+// format yang dipakai model terkuantisasi (contoh Phi ke MixFormer), Ok jika namanya sudah sama
+#[cfg(feature = "candle")]
+pub fn run(
+    args: QuantizeArgs,
+    architecture: &str,
+    rename: impl FnOnce(HashMap<String, Tensor>) -> Result<HashMap<String, Tensor>>,
+) -> Result<()> {
+    let start = std::time::Instant::now();
+    let mut tensors = HashMap::new();
+    for file in args.weight_files.split(',') {
+        println!("reading {file}");
+        tensors.extend(candle_core::safetensors::load(file, &Device::Cpu)?);
+    }
+    let tensors = rename(tensors)?;
+    let mut names = tensors.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+
+    // Kuantisasi setiap tensor sesuai tipe yang dipilih
+    let mut qtensors = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let tensor = tensors[name].to_dtype(DType::F32)?;
+        let dtype = tensor_type(name, tensor.dims(), &args).dtype();
+        println!("  {name} {:?} -> {dtype:?}", tensor.shape());
+        qtensors.push((name.as_str(), QTensor::quantize(&tensor, dtype)?));
+    }
+    let qtensors = qtensors
+        .iter()
+        .map(|(name, t)| (*name, t))
+        .collect::<Vec<_>>();
+
+    // Menulis file GGUF beserta metadata arsitektur
+    let architecture = gguf_file::Value::String(architecture.to_string());
+    let file_type = gguf_file::Value::U32(args.quantization.file_type());
+    let metadata = [
+        ("general.architecture", &architecture),
+        ("general.file_type", &file_type),
+    ];
+    let mut out = std::fs::File::create(&args.out_file)?;
+    gguf_file::write(&mut out, &metadata, &qtensors)?;
+    println!(
+        "wrote {} tensors to {:?} in {:?}",
+        qtensors.len(),
+        args.out_file,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Argumen quantize dengan override yang diberikan, file input dan output tidak dipakai
+    fn args(quantization: QuantType, overrides: &[&str]) -> Result<QuantizeArgs> {
+        let overrides = overrides
+            .iter()
+            .map(|s| parse_override(s).map_err(anyhow::Error::msg))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(QuantizeArgs {
+            weight_files: String::new(),
+            out_file: "unused.gguf".into(),
+            quantization,
+            overrides,
+        })
+    }
+
+    #[test]
+    fn overrides_are_parsed() {
+        assert_eq!(
+            parse_override("embed=f16"),
+            Ok(("embed".to_string(), QuantType::F16))
+        );
+        // Tipe tidak peka huruf besar, pola boleh berisi titik
+        assert_eq!(
+            parse_override("layers.0.=Q8_0"),
+            Ok(("layers.0.".to_string(), QuantType::Q8_0))
+        );
+        assert!(parse_override("embed").is_err());
+        assert!(parse_override("embed=q3").is_err());
+    }
+
+    #[test]
+    fn tensor_types_follow_overrides_and_shapes() -> Result<()> {
+        let args = args(
+            QuantType::Q4k,
+            &["embed=f16", "embed_tokens=q8_0", "head=q8_0"],
+        )?;
+        assert_eq!(
+            tensor_type("layers.0.fc1.weight", &[4, 256], &args),
+            QuantType::Q4k
+        );
+        // Override pertama yang cocok yang dipakai
+        assert_eq!(
+            tensor_type("model.embed_tokens.weight", &[4, 256], &args),
+            QuantType::F16
+        );
+        assert_eq!(
+            tensor_type("lm_head.weight", &[4, 256], &args),
+            QuantType::Q8_0
+        );
+        // Bias tetap f32, dimensi yang tidak habis dibagi blok q4k (256) disimpan dalam f16
+        assert_eq!(
+            tensor_type("layers.0.fc1.bias", &[256], &args),
+            QuantType::F32
+        );
+        assert_eq!(
+            tensor_type("layers.0.fc2.weight", &[4, 288], &args),
+            QuantType::F16
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "candle")]
+    #[test]
+    fn block_sizes_match_candle() {
+        for ty in QuantType::value_variants() {
+            assert_eq!(ty.block_size(), ty.dtype().block_size(), "{ty:?}");
+        }
+    }
+
+    #[cfg(feature = "candle")]
+    #[test]
+    fn file_type_is_written_as_ggml_id() -> Result<()> {
+        use crate::fixtures::TempDir;
+
+        let dir = TempDir::new("quantize")?;
+        let weights = dir.join("model.safetensors");
+        let tensors = HashMap::from([
+            (
+                "layers.0.fc1.weight".to_string(),
+                Tensor::ones((4, 32), DType::F32, &Device::Cpu)?,
+            ),
+            (
+                "layers.0.fc1.bias".to_string(),
+                Tensor::ones(4, DType::F32, &Device::Cpu)?,
+            ),
+        ]);
+        candle_core::safetensors::save(&tensors, &weights)?;
+        for (quantization, file_type) in [(QuantType::F16, 1), (QuantType::Q8_0, 7)] {
+            let mut args = args(quantization, &[])?;
+            args.weight_files = weights.to_string_lossy().to_string();
+            args.out_file = dir.join(format!("model-{file_type}.gguf"));
+            let out_file = args.out_file.clone();
+            // Hook rename dipanggil sebelum kuantisasi
+            run(args, "test", |mut tensors| {
+                let bias = tensors.remove("layers.0.fc1.bias").unwrap();
+                tensors.insert("layers.0.fc1.renamed".to_string(), bias);
+                Ok(tensors)
+            })?;
+            let mut file = std::fs::File::open(&out_file)?;
+            let content = gguf_file::Content::read(&mut file)?;
+            assert_eq!(content.metadata["general.file_type"].to_u32()?, file_type);
+            assert_eq!(
+                content.metadata["general.architecture"].to_string()?,
+                "test"
+            );
+            assert_eq!(
+                content.tensor_infos["layers.0.fc1.weight"].ggml_dtype,
+                quantization.dtype()
+            );
+            assert_eq!(
+                content.tensor_infos["layers.0.fc1.renamed"].ggml_dtype,
+                GgmlDType::F32
+            );
+            assert!(!content.tensor_infos.contains_key("layers.0.fc1.bias"));
+        }
+        Ok(())
+    }
+}
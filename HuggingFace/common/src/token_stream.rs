@@ -0,0 +1,126 @@
+// Modul untuk detokenisasi bertahap saat streaming: byte dari karakter multi-byte yang terpotong
+// di beberapa token ditahan dulu, hanya karakter yang sudah lengkap yang dikeluarkan
+use anyhow::Result;
+
+// Karakter pengganti yang muncul saat urutan byte UTF-8 belum lengkap
+const REPLACEMENT: char = '\u{FFFD}';
+
+// Trait untuk tokenizer yang bisa mendekode sekumpulan token menjadi teks (byte tidak valid menjadi U+FFFD),
+// diimplementasikan oleh setiap binary untuk tokenizernya
+pub trait Decode {
+    fn decode_lossy(&self, tokens: &[u32]) -> Result<String>;
+}
+
+// Referensi ke tokenizer juga bisa dipakai, sehingga satu tokenizer bisa dipakai beberapa TokenStream
+impl<T: Decode + ?Sized> Decode for &T {
+    fn decode_lossy(&self, tokens: &[u32]) -> Result<String> {
+        (**self).decode_lossy(tokens)
+    }
+}
+
+// Fungsi untuk mengambil bagian teks setelah teks yang sudah dikeluarkan sebelumnya
+fn new_text(text: String, prev_text: &str) -> String {
+    match text.get(prev_text.len()..) {
+        Some(new) => new.to_string(),
+        None => text,
+    }
+}
+
+// Struct untuk streaming teks dari token yang dihasilkan satu per satu
+pub struct TokenStream<T: Decode> {
+    tokenizer: T,
+    tokens: Vec<u32>,
+    prev_index: usize,
+    current_index: usize,
+}
+
+impl<T: Decode> TokenStream<T> {
+    pub fn new(tokenizer: T) -> Self {
+        Self {
+            tokenizer,
+            tokens: Vec::new(),
+            prev_index: 0,
+            current_index: 0,
+        }
+    }
+
+    // Mengembalikan teks baru jika token ini melengkapi satu atau lebih karakter
+    pub fn next_token(&mut self, token: u32) -> Result<Option<String>> {
+        // Teks dari token yang sudah dikeluarkan, dipakai sebagai konteks agar spasi di awal token tetap benar
+        let prev_text = self
+            .tokenizer
+            .decode_lossy(&self.tokens[self.prev_index..self.current_index])?;
+        self.tokens.push(token);
+        let text = self
+            .tokenizer
+            .decode_lossy(&self.tokens[self.prev_index..])?;
+        // Teks yang berakhir dengan U+FFFD berarti masih menunggu byte dari token berikutnya
+        if text.len() > prev_text.len() && !text.ends_with(REPLACEMENT) {
+            let text = new_text(text, &prev_text);
+            self.prev_index = self.current_index;
+            self.current_index = self.tokens.len();
+            Ok(Some(text))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Mengeluarkan sisa teks yang masih ditahan (dipanggil setelah generasi selesai)
+    pub fn flush(&mut self) -> Result<Option<String>> {
+        let prev_text = self
+            .tokenizer
+            .decode_lossy(&self.tokens[self.prev_index..self.current_index])?;
+        let text = self
+            .tokenizer
+            .decode_lossy(&self.tokens[self.prev_index..])?;
+        self.prev_index = self.tokens.len();
+        self.current_index = self.tokens.len();
+        if text.len() > prev_text.len() {
+            Ok(Some(new_text(text, &prev_text)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tokenizer sederhana dengan satu token per byte, seperti token byte pada tokenizer byte-level
+    struct ByteTokenizer;
+
+    impl Decode for ByteTokenizer {
+        fn decode_lossy(&self, tokens: &[u32]) -> Result<String> {
+            let bytes = tokens.iter().map(|&t| t as u8).collect::<Vec<_>>();
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+
+    fn stream(text: &[u8]) -> Result<Vec<String>> {
+        let mut stream = TokenStream::new(&ByteTokenizer);
+        let mut pieces = vec![];
+        for &byte in text.iter() {
+            pieces.extend(stream.next_token(byte as u32)?);
+        }
+        pieces.extend(stream.flush()?);
+        Ok(pieces)
+    }
+
+    #[test]
+    fn multibyte_characters_are_not_split() -> Result<()> {
+        let text = "Apa kabar? 🙂 日本 é";
+        let pieces = stream(text.as_bytes())?;
+        assert!(pieces.iter().all(|p| !p.contains(REPLACEMENT)));
+        assert!(pieces.contains(&"🙂".to_string()));
+        assert_eq!(pieces.concat(), text);
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_tail_is_flushed() -> Result<()> {
+        let pieces = stream(&"ab🙂".as_bytes()[..4])?;
+        assert_eq!(pieces, vec!["a", "b", "\u{FFFD}"]);
+        Ok(())
+    }
+}
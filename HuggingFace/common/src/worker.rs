@@ -0,0 +1,304 @@
+// Modul untuk mode --worker: request JSON per baris dibaca dari stdin, response dan event token ditulis per baris ke stdout,
+// log untuk manusia ditulis ke stderr
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::budget::{Budget, FinishReason};
+
+// Ditandai saat mode worker aktif agar log dipindah ke stderr
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn activate() {
+    ACTIVE.store(true, Ordering::SeqCst)
+}
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+// Enum untuk method yang didukung beserta parameternya
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+enum Method {
+    Generate {
+        prompt: String,
+        #[serde(default)]
+        max_tokens: Option<usize>,
+        #[serde(default = "default_stream")]
+        stream: bool,
+    },
+    Score {
+        prompt: String,
+        continuations: Vec<String>,
+    },
+    Tokenize {
+        text: String,
+    },
+    Reset,
+}
+
+fn default_stream() -> bool {
+    true
+}
+
+// Struct untuk satu request, id dikembalikan apa adanya di setiap response dan event
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    method: Method,
+}
+
+// Struct untuk hasil generasi satu prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub text: String,
+    pub finish_reason: FinishReason,
+    pub prompt_tokens: usize,
+    pub generated_tokens: usize,
+}
+
+// Trait untuk model yang dilayani oleh worker
+pub trait Backend {
+    // Menghasilkan teks dari prompt, setiap potongan teks baru dikirim ke on_text
+    fn generate(
+        &mut self,
+        prompt: &str,
+        max_tokens: usize,
+        budget: &Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<Generation>;
+
+    // Log-probability dari continuation setelah prompt, beserta jumlah token continuation
+    fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)>;
+
+    // Token id beserta potongan teksnya
+    fn tokenize(&mut self, text: &str) -> Result<Vec<(u32, String)>>;
+
+    // Mengosongkan kv cache dan mengembalikan sampler ke seed awal
+    fn reset(&mut self) -> Result<()>;
+}
+
+// Struct untuk batas default generasi pada worker
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_tokens: usize,
+    pub max_time: Option<f64>,
+    pub max_prompt_time: Option<f64>,
+}
+
+// Fungsi untuk menulis satu pesan JSON per baris
+fn send<W: Write>(output: &mut W, message: &Value) -> Result<()> {
+    writeln!(output, "{message}")?;
+    output.flush()?;
+    Ok(())
+}
+
+// Fungsi untuk menjalankan satu request, event token dikirim langsung dan hasil akhirnya dikembalikan
+fn handle<B: Backend, W: Write>(
+    backend: &mut B,
+    limits: Limits,
+    id: &Value,
+    method: Method,
+    output: &mut W,
+) -> Result<Value> {
+    match method {
+        Method::Generate {
+            prompt,
+            max_tokens,
+            stream,
+        } => {
+            let budget = Budget::new(limits.max_time, limits.max_prompt_time);
+            let max_tokens = max_tokens.unwrap_or(limits.max_tokens);
+            let mut on_text = |text: &str| {
+                if stream {
+                    send(
+                        &mut *output,
+                        &json!({"id": id, "event": "token", "text": text}),
+                    )?;
+                }
+                Ok(())
+            };
+            let generation = backend.generate(&prompt, max_tokens, &budget, &mut on_text)?;
+            Ok(json!({
+                "text": generation.text,
+                "finish_reason": generation.finish_reason.to_string(),
+                "prompt_tokens": generation.prompt_tokens,
+                "generated_tokens": generation.generated_tokens,
+            }))
+        }
+        Method::Score {
+            prompt,
+            continuations,
+        } => {
+            let mut scores = vec![];
+            for continuation in continuations.iter() {
+                let (logprob, tokens) = backend.score(&prompt, continuation)?;
+                scores.push(json!({"logprob": logprob, "tokens": tokens}));
+            }
+            Ok(json!({ "scores": scores }))
+        }
+        Method::Tokenize { text } => {
+            let tokens = backend.tokenize(&text)?;
+            let ids: Vec<u32> = tokens.iter().map(|(id, _)| *id).collect();
+            let pieces: Vec<&str> = tokens.iter().map(|(_, piece)| piece.as_str()).collect();
+            Ok(json!({"ids": ids, "tokens": pieces}))
+        }
+        Method::Reset => {
+            backend.reset()?;
+            Ok(json!({}))
+        }
+    }
+}
+
+// Fungsi untuk melayani request sampai input habis, request yang gagal dijawab dengan error tanpa menghentikan worker
+pub fn serve<B: Backend, R: BufRead, W: Write>(
+    backend: &mut B,
+    limits: Limits,
+    input: R,
+    mut output: W,
+) -> Result<()> {
+    send(&mut output, &json!({"event": "ready"}))?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                // id tetap dikembalikan jika baris masih berupa JSON yang valid
+                let id = serde_json::from_str::<Value>(&line)
+                    .ok()
+                    .and_then(|v| v.get("id").cloned())
+                    .unwrap_or(Value::Null);
+                send(&mut output, &json!({"id": id, "error": err.to_string()}))?;
+                continue;
+            }
+        };
+        let id = request.id;
+        let message = match handle(backend, limits, &id, request.method, &mut output) {
+            Ok(result) => json!({"id": id, "result": result}),
+            Err(err) => {
+                eprintln!("request {id} failed: {err:#}");
+                json!({"id": id, "error": format!("{err:#}")})
+            }
+        };
+        send(&mut output, &message)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Backend palsu yang mengulang prompt per kata
+    struct Echo {
+        resets: usize,
+    }
+
+    impl Backend for Echo {
+        fn generate(
+            &mut self,
+            prompt: &str,
+            max_tokens: usize,
+            _budget: &Budget,
+            on_text: &mut dyn FnMut(&str) -> Result<()>,
+        ) -> Result<Generation> {
+            let words: Vec<&str> = prompt.split(' ').take(max_tokens).collect();
+            for word in words.iter() {
+                on_text(word)?;
+            }
+            Ok(Generation {
+                text: words.concat(),
+                finish_reason: FinishReason::Length,
+                prompt_tokens: 1,
+                generated_tokens: words.len(),
+            })
+        }
+
+        fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)> {
+            if continuation.is_empty() {
+                anyhow::bail!("empty continuation")
+            }
+            Ok((-(prompt.len() as f64), continuation.len()))
+        }
+
+        fn tokenize(&mut self, text: &str) -> Result<Vec<(u32, String)>> {
+            Ok(text.chars().map(|c| (c as u32, c.to_string())).collect())
+        }
+
+        fn reset(&mut self) -> Result<()> {
+            self.resets += 1;
+            Ok(())
+        }
+    }
+
+    fn run(input: &str) -> Result<(Vec<Value>, Echo)> {
+        let mut backend = Echo { resets: 0 };
+        let limits = Limits {
+            max_tokens: 2,
+            max_time: None,
+            max_prompt_time: None,
+        };
+        let mut output = vec![];
+        serve(&mut backend, limits, input.as_bytes(), &mut output)?;
+        let messages = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<serde_json::Result<Vec<Value>>>()?;
+        Ok((messages, backend))
+    }
+
+    #[test]
+    fn generate_streams_tokens_then_result() -> Result<()> {
+        let (messages, _) = run(r#"{"id": 7, "method": "generate", "prompt": "a b c"}"#)?;
+        assert_eq!(messages[0], json!({"event": "ready"}));
+        assert_eq!(messages[1], json!({"id": 7, "event": "token", "text": "a"}));
+        assert_eq!(messages[2], json!({"id": 7, "event": "token", "text": "b"}));
+        assert_eq!(messages[3]["id"], json!(7));
+        assert_eq!(messages[3]["result"]["text"], json!("ab"));
+        assert_eq!(messages[3]["result"]["finish_reason"], json!("length"));
+        assert_eq!(messages.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_keep_the_worker_running() -> Result<()> {
+        let input = [
+            r#"{"id": "a", "method": "unknown"}"#,
+            "not json",
+            r#"{"id": "b", "method": "score", "prompt": "xy", "continuations": ["z", ""]}"#,
+            r#"{"id": "c", "method": "score", "prompt": "xy", "continuations": ["z"]}"#,
+            r#"{"id": "d", "method": "tokenize", "text": "hi"}"#,
+            r#"{"id": "e", "method": "reset"}"#,
+            r#"{"id": "f", "method": "generate", "prompt": "a b", "stream": false}"#,
+        ]
+        .join("\n");
+        let (messages, backend) = run(&input)?;
+        assert_eq!(messages[1]["id"], json!("a"));
+        assert!(messages[1]["error"].is_string());
+        assert_eq!(messages[2]["id"], Value::Null);
+        assert!(messages[2]["error"].is_string());
+        assert_eq!(messages[3]["id"], json!("b"));
+        assert!(messages[3]["error"].is_string());
+        assert_eq!(
+            messages[4]["result"],
+            json!({"scores": [{"logprob": -2.0, "tokens": 1}]})
+        );
+        assert_eq!(
+            messages[5]["result"],
+            json!({"ids": [104, 105], "tokens": ["h", "i"]})
+        );
+        assert_eq!(messages[6], json!({"id": "e", "result": {}}));
+        assert_eq!(messages[7]["result"]["text"], json!("ab"));
+        assert_eq!(messages.len(), 8);
+        assert_eq!(backend.resets, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+// Test Ctrl-C: flag berhenti bersifat global sehingga diuji di proses test sendiri agar tidak menghentikan generasi
+// pada test lain yang berjalan paralel
+use common::budget::{self, Budget, FinishReason};
+
+#[test]
+fn cancel_finishes_generation_until_cleared() {
+    let budget = Budget::new(None, Some(3600.));
+    assert_eq!(budget.check(), None);
+    // Ctrl-C pertama hanya menandai, yang kedua membuat handler keluar
+    assert!(!budget::cancel());
+    assert!(budget::cancel());
+    assert_eq!(budget.check(), Some(FinishReason::Cancelled));
+    assert_eq!(budget.check_prompt(), Some(FinishReason::Cancelled));
+    // Ctrl-C lebih diutamakan daripada stop sequence
+    budget.stop();
+    assert_eq!(budget.check(), Some(FinishReason::Cancelled));
+
+    budget::clear_cancelled();
+    assert_eq!(budget.check(), Some(FinishReason::Stop));
+    assert_eq!(Budget::new(None, None).check(), None);
+    assert!(!budget::cancel());
+    budget::clear_cancelled();
+}
@@ -0,0 +1,157 @@
+#ifndef MISTRAL_H
+#define MISTRAL_H
+
+/* Generated by build.rs with cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Status returned by the `mistral_*` functions. When it is not `MISTRAL_STATUS_OK`,
+ * `mistral_last_error` returns a message describing the failure.
+ */
+typedef enum MistralStatus {
+  MISTRAL_STATUS_OK = 0,
+  /**
+   * A required pointer is null or a string is not valid UTF-8.
+   */
+  MISTRAL_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * The model directory is missing a file or does not contain a valid Mistral model.
+   */
+  MISTRAL_STATUS_LOAD = 2,
+  /**
+   * Tokenization or a forward pass failed.
+   */
+  MISTRAL_STATUS_INFERENCE = 3,
+  /**
+   * The buffer given to `mistral_tokenize` is too small, the required length is written to `len`.
+   */
+  MISTRAL_STATUS_BUFFER_TOO_SMALL = 4,
+  /**
+   * A panic was caught at the API boundary, the handle should not be used anymore.
+   */
+  MISTRAL_STATUS_PANIC = 5,
+} MistralStatus;
+
+/**
+ * Opaque handle to a loaded model, created by `mistral_model_load` and released by
+ * `mistral_model_free`. A handle may be used from several threads, calls on the same handle are
+ * serialized and calls on different handles run in parallel.
+ */
+typedef struct MistralModel MistralModel;
+
+/**
+ * Sampling parameters of `mistral_generate`, start from `mistral_sampling_params_default`.
+ */
+typedef struct MistralSamplingParams {
+  /**
+   * The temperature used to generate samples, 0 or less means greedy decoding.
+   */
+  double temperature;
+  /**
+   * Nucleus sampling probability cutoff, disabled when not between 0 and 1.
+   */
+  double top_p;
+  /**
+   * The seed to use when generating random samples.
+   */
+  uint64_t seed;
+  /**
+   * The maximum number of tokens to generate.
+   */
+  uint32_t max_tokens;
+  /**
+   * Penalty to be applied for repeating tokens, 1 means no penalty.
+   */
+  float repeat_penalty;
+  /**
+   * The context size to consider for the repeat penalty.
+   */
+  uint32_t repeat_last_n;
+} MistralSamplingParams;
+
+/**
+ * Called with each new piece of generated text, a null-terminated UTF-8 string that is only
+ * valid during the call. Return 0 to continue and any other value to stop generating.
+ */
+typedef int32_t (*MistralTokenCallback)(const char *text, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the default sampling parameters: greedy decoding, seed 299792458, 256 tokens and a
+ * repeat penalty of 1.1 over the last 64 tokens.
+ */
+struct MistralSamplingParams mistral_sampling_params_default(void);
+
+/**
+ * Loads a Mistral model from a local directory holding `config.json`, `tokenizer.json` and the
+ * `.safetensors` weights, and writes the new handle to `out`. The model runs on the CPU in f32.
+ *
+ * # Safety
+ *
+ * `dir` must be a null-terminated string and `out` must point to writable memory.
+ */
+enum MistralStatus mistral_model_load(const char *dir, struct MistralModel **out);
+
+/**
+ * Generates text from `prompt` and calls `callback` with each new piece of text until the `</s>`
+ * token, `max_tokens`, the end of the context window or a non-zero return value of the
+ * callback. `params` may be null to use the defaults and `user_data` is passed to the callback
+ * as is.
+ *
+ * # Safety
+ *
+ * `model` must be a handle returned by `mistral_model_load` that has not been freed, `prompt` a
+ * null-terminated string and `params` null or a valid pointer.
+ */
+enum MistralStatus mistral_generate(const struct MistralModel *model,
+                                    const char *prompt,
+                                    const struct MistralSamplingParams *params,
+                                    MistralTokenCallback callback,
+                                    void *user_data);
+
+/**
+ * Tokenizes `text` and writes the token ids to `ids`, which holds `capacity` entries. The number
+ * of tokens is always written to `len`; when it is larger than `capacity`, nothing is written to
+ * `ids` and `MISTRAL_STATUS_BUFFER_TOO_SMALL` is returned, so `ids` may be null with a `capacity`
+ * of 0 to query the length.
+ *
+ * # Safety
+ *
+ * `model` must be a live handle, `text` a null-terminated string, `ids` null or valid for
+ * `capacity` writes and `len` a valid pointer.
+ */
+enum MistralStatus mistral_tokenize(const struct MistralModel *model,
+                                    const char *text,
+                                    uint32_t *ids,
+                                    size_t capacity,
+                                    size_t *len);
+
+/**
+ * Releases a handle returned by `mistral_model_load`, null is ignored. No other call may use the
+ * handle during or after this call.
+ *
+ * # Safety
+ *
+ * `model` must be null or a handle returned by `mistral_model_load` that has not been freed yet.
+ */
+void mistral_model_free(struct MistralModel *model);
+
+/**
+ * Returns the message of the last failed call on the current thread, or an empty string. The
+ * pointer stays valid until the next failed call on the same thread.
+ */
+const char *mistral_last_error(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MISTRAL_H */
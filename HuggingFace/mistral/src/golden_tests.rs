@@ -0,0 +1,200 @@
+// Test golden offline dengan model Mistral acak kecil: bobot dibuat dari seed tetap, ditulis ke safetensors (dan ke
+// GGUF lewat subcommand quantize) lalu dimuat lewat jalur yang sama dengan CLI. Output dibandingkan dengan
+// tests/golden, lihat modul fixtures
+use super::*;
+use crate::fixtures::{self, TempDir};
+use crate::worker::Backend;
+use candle_nn::VarMap;
+use serde_json::{json, Value};
+
+// Direktori file golden crate ini, fixtures ada di crate common sehingga tidak bisa memakai CARGO_MANIFEST_DIR sendiri
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const VOCAB_SIZE: usize = 64;
+const PROMPT: &str = "A B w10 w11 w12";
+
+// Config Mistral kecil dengan grouped-query attention, dimensi kelipatan 32 agar bisa dikuantisasi q8_0
+const CONFIG: &str = r#"{
+    "vocab_size": 64,
+    "hidden_size": 32,
+    "intermediate_size": 64,
+    "num_hidden_layers": 2,
+    "num_attention_heads": 4,
+    "num_key_value_heads": 2,
+    "hidden_act": "silu",
+    "max_position_embeddings": 64,
+    "rms_norm_eps": 1e-5,
+    "rope_theta": 10000.0,
+    "sliding_window": 64
+}"#;
+
+// Struct untuk fixture Mistral di direktori sementara: config.json, tokenizer.json dan model.safetensors
+struct MistralFixture {
+    dir: TempDir,
+    tokenizer: Tokenizer,
+    config: Config,
+}
+
+impl MistralFixture {
+    // Menulis fixture dengan bobot acak dari seed tetap, update dipanggil sebelum bobot ditulis
+    fn new(name: &str, update: impl FnOnce(&VarMap) -> Result<()>) -> Result<Self> {
+        let dir = TempDir::new(name)?;
+        std::fs::write(dir.join("config.json"), CONFIG)?;
+        let tokenizer =
+            fixtures::tokenizer(&dir.join("tokenizer.json"), &["<s>", "</s>"], VOCAB_SIZE)?;
+        let config: Config = serde_json::from_str(CONFIG)?;
+        let varmap = VarMap::new();
+        Mistral::new(
+            &config,
+            VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu),
+        )?;
+        fixtures::randomize(&varmap, 42)?;
+        update(&varmap)?;
+        varmap.save(dir.join("model.safetensors"))?;
+        Ok(Self {
+            dir,
+            tokenizer,
+            config,
+        })
+    }
+
+    fn mistral(&self) -> Result<Model> {
+        let filenames = [self.dir.join("model.safetensors")];
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &Device::Cpu)? };
+        Ok(Model::Mistral(Mistral::new(&self.config, vb)?))
+    }
+
+    // Menjalankan subcommand quantize lewat parser argumen CLI lalu memuat GGUF-nya sebagai QMistral
+    fn quantized(&self, quantization: &str) -> Result<Model> {
+        let weights = self.dir.join("model.safetensors");
+        let out = self.dir.join(format!("model-{quantization}.gguf"));
+        let (weights_str, out_str) = (weights.to_string_lossy(), out.to_string_lossy());
+        let args = Args::try_parse_from([
+            "mistral",
+            "quantize",
+            "--weight-files",
+            &*weights_str,
+            "--out-file",
+            &*out_str,
+            "--quantization",
+            quantization,
+        ])?;
+        match args.command {
+            Some(Command::Quantize(quantize_args)) => quantize::run(quantize_args, "mistral", Ok)?,
+            command => anyhow::bail!("unexpected command {command:?}"),
+        }
+        let vb =
+            candle_transformers::quantized_var_builder::VarBuilder::from_gguf(&out, &Device::Cpu)?;
+        Ok(Model::Quantized(QMistral::new(&self.config, vb)?))
+    }
+
+    // Membuat pipeline greedy (tanpa temperature) dengan repeat penalty yang diberikan
+    fn pipeline(&self, model: Model, repeat_penalty: f32, repeat_last_n: usize) -> TextGeneration {
+        let context = context::ContextWindow {
+            size: 64,
+            truncate: context::Truncate::Error,
+            overflow: context::Overflow::Stop,
+        };
+        TextGeneration::new(
+            model,
+            self.tokenizer.clone(),
+            0,
+            None,
+            None,
+            repeat_penalty,
+            repeat_last_n,
+            context,
+            &Device::Cpu,
+        )
+    }
+}
+
+// Fungsi untuk menjalankan generasi dari PROMPT lewat Backend yang sama dengan mode worker, hasilnya dalam bentuk
+// JSON. Setiap kata tokenizer fixture adalah satu token, sehingga teksnya adalah urutan token yang dihasilkan
+fn generate(pipeline: &mut TextGeneration, sample_len: usize) -> Result<Value> {
+    let budget = budget::Budget::new(None, None);
+    let generation = pipeline.generate(PROMPT, sample_len, &budget, &mut |_| Ok(()))?;
+    Ok(json!({
+        "prompt": PROMPT,
+        "text": generation.text,
+        "generated_tokens": generation.generated_tokens,
+        "finish_reason": generation.finish_reason.to_string(),
+    }))
+}
+
+// Fungsi untuk mengambil logits token terakhir dari satu forward PROMPT
+fn last_logits(mut model: Model, tokenizer: &Tokenizer) -> Result<Vec<f32>> {
+    let tokens = tokenizer.encode(PROMPT, true).map_err(E::msg)?;
+    let input = Tensor::new(tokens.get_ids(), &Device::Cpu)?.unsqueeze(0)?;
+    Ok(model
+        .forward(&input, 0)?
+        .squeeze(0)?
+        .squeeze(0)?
+        .to_vec1()?)
+}
+
+#[test]
+fn greedy_generation_matches_golden() -> Result<()> {
+    let fixture = MistralFixture::new("greedy", |_| Ok(()))?;
+    let mut pipeline = fixture.pipeline(fixture.mistral()?, 1., 64);
+    let first = generate(&mut pipeline, 16)?;
+    // Generasi kedua pada pipeline yang sama tidak boleh terpengaruh kv cache generasi sebelumnya
+    assert_eq!(first, generate(&mut pipeline, 16)?);
+    fixtures::assert_golden(GOLDEN_DIR, "mistral_greedy", &first)
+}
+
+#[test]
+fn repeat_penalty_matches_golden() -> Result<()> {
+    let fixture = MistralFixture::new("repeat-penalty", |_| Ok(()))?;
+    let plain = generate(&mut fixture.pipeline(fixture.mistral()?, 1., 64), 16)?;
+    // Tanpa token di jendela repeat_last_n, penalty tidak mengubah logits
+    let empty_window = generate(&mut fixture.pipeline(fixture.mistral()?, 4., 0), 16)?;
+    assert_eq!(plain, empty_window);
+    let penalized = generate(&mut fixture.pipeline(fixture.mistral()?, 4., 64), 16)?;
+    fixtures::assert_golden(GOLDEN_DIR, "mistral_repeat_penalty", &penalized)
+}
+
+#[test]
+fn eos_stops_generation() -> Result<()> {
+    // lm_head Mistral tidak punya bias: embedding dan norm diisi 1 dan keluaran setiap layer dinolkan, sehingga hidden
+    // state terakhir semua 1 dan logits adalah jumlah baris lm_head. Baris token eos (</s>, id 1) diisi 1
+    let fixture = MistralFixture::new("eos", |varmap| {
+        fixtures::update(varmap, "model.embed_tokens.weight", |w| w.fill(1.))?;
+        fixtures::update(varmap, "model.norm.weight", |w| w.fill(1.))?;
+        for layer in 0..2 {
+            for name in ["self_attn.o_proj", "mlp.down_proj"] {
+                let name = format!("model.layers.{layer}.{name}.weight");
+                fixtures::update(varmap, &name, |w| w.fill(0.))?;
+            }
+        }
+        fixtures::update(varmap, "lm_head.weight", |w| w[32..64].fill(1.))
+    })?;
+    for model in [fixture.mistral()?, fixture.quantized("q8_0")?] {
+        let mut pipeline = fixture.pipeline(model, 1., 64);
+        let budget = budget::Budget::new(None, None);
+        let generation = pipeline.generate(PROMPT, 16, &budget, &mut |_| Ok(()))?;
+        assert_eq!(generation.finish_reason, budget::FinishReason::Eos);
+        assert_eq!(generation.generated_tokens, 1);
+        assert_eq!(generation.text, "");
+    }
+    Ok(())
+}
+
+#[test]
+fn quantized_gguf_matches_golden() -> Result<()> {
+    let fixture = MistralFixture::new("quantized", |_| Ok(()))?;
+    // GGUF f32 berisi bobot yang sama, sehingga logits QMistral harus sama dengan model Mistral
+    let expected = last_logits(fixture.mistral()?, &fixture.tokenizer)?;
+    let actual = last_logits(fixture.quantized("f32")?, &fixture.tokenizer)?;
+    let max_diff = expected
+        .iter()
+        .zip(actual.iter())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0f32, f32::max);
+    assert!(max_diff < 1e-3, "max logit difference {max_diff}");
+    let q8_0 = generate(
+        &mut fixture.pipeline(fixture.quantized("q8_0")?, 1., 64),
+        16,
+    )?;
+    fixtures::assert_golden(GOLDEN_DIR, "mistral_q8_0", &q8_0)
+}
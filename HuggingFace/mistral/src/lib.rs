@@ -0,0 +1,2 @@
+// Library crate mistral: C ABI (cdylib) untuk memakai model Mistral dari aplikasi C dan C++
+pub mod ffi; // Untuk C ABI, header ada di include/mistral.h
@@ -1,14 +1,36 @@
 use anyhow::{Error as E, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+
+// Macro log untuk mencetak ke stdout, atau ke stderr pada mode --worker karena stdout dipakai untuk response JSON
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if crate::worker::is_active() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+#[cfg(test)]
+mod golden_tests; // Modul untuk test golden generasi dan kuantisasi dengan model acak kecil
+
+#[cfg(test)]
+use common::fixtures; // Modul untuk fixture test offline (direktori sementara, tokenizer, bobot acak, file golden)
+use common::{
+    budget, chat, compare, context, eval, gen_eval, journal, lora, manifest, memory, quantize,
+    worker,
+}; // Modul untuk eval, journal, chat, mode worker dan modul lain yang dibagi dengan binary lain
 
 use candle_transformers::models::mistral::{Config, Model as Mistral}; // Import Mistral model
 use candle_transformers::models::quantized_mistral::Model as QMistral; // Import Quantized Mistral model
 
-use candle_core::{DType, Device, Tensor}; // Import DType, Device, Tensor untuk mengolah data
+use candle_core::{DType, Device, Tensor, D}; // Import DType, Device, Tensor untuk mengolah data
 use candle_examples::token_output_stream::TokenOutputStream; // Import TokenOutputStream untuk mengolah token
 use candle_nn::VarBuilder; // Import VarBuilder untuk mengolah variabel
 use candle_transformers::generation::LogitsProcessor; // Import LogitsProcessor untuk mengolah logit
 use hf_hub::{api::sync::Api, Repo, RepoType};// Import Api, Repo, RepoType untuk mengolah model
+use hf_hub::api::sync::{ApiError, ApiRepo}; // Import ApiError dan ApiRepo untuk file opsional di repo
 use tokenizers::Tokenizer; // Import Tokenizer untuk mengolah token
 
 // Enum Model untuk memilih model yang akan digunakan
@@ -17,14 +39,39 @@ enum Model {
     Quantized(QMistral),
 }
 
+// Implementasi Model untuk memanggil forward dan clear_kv_cache pada model yang dipilih
+impl Model {
+    fn forward(&mut self, xs: &Tensor, seqlen_offset: usize) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Mistral(m) => m.forward(xs, seqlen_offset),
+            Self::Quantized(m) => m.forward(xs, seqlen_offset),
+        }
+    }
+
+    fn clear_kv_cache(&mut self) {
+        match self {
+            Self::Mistral(m) => m.clear_kv_cache(),
+            Self::Quantized(m) => m.clear_kv_cache(),
+        }
+    }
+}
+
+// Panjang sliding window pada config Mistral 7B v0.1
+const MISTRAL_7B_WINDOW: usize = 4096;
+
 // Struct TextGeneration untuk mengolah text generation
 struct TextGeneration {
     model: Model,
     device: Device,
     tokenizer: TokenOutputStream,
     logits_processor: LogitsProcessor,
+    // Parameter sampling untuk membuat ulang LogitsProcessor saat reset pada mode worker
+    seed: u64,
+    temp: Option<f64>,
+    top_p: Option<f64>,
     repeat_penalty: f32,
     repeat_last_n: usize,
+    context: context::ContextWindow,
 }
 
 // Implementasi TextGeneration untuk mengolah text generation dengan parameter model, tokenizer, seed, temp, top_p, repeat_penalty, repeat_last_n, dan device yang diambil dari Device untuk mengolah data pada device yang dipilih (CPU atau GPU)
@@ -38,6 +85,7 @@ impl TextGeneration {
         top_p: Option<f64>,
         repeat_penalty: f32,
         repeat_last_n: usize,
+        context: context::ContextWindow,
         device: &Device,
     ) -> Self {
         let logits_processor = LogitsProcessor::new(seed, temp, top_p);
@@ -45,14 +93,23 @@ impl TextGeneration {
             model,
             tokenizer: TokenOutputStream::new(tokenizer),
             logits_processor,
+            seed,
+            temp,
+            top_p,
             repeat_penalty,
             repeat_last_n,
+            context,
             device: device.clone(),
         }
     }
 
-    // Fungsi run untuk menjalankan text generation dengan parameter prompt dan sample_len yang diambil dari Args untuk mengolah prompt dan panjang sample yang dihasilkan dalam token yang dihasilkan dari model yang dipilih (Mistral atau Quantized Mistral)
-    fn run(&mut self, prompt: &str, sample_len: usize) -> Result<()> {
+    // Fungsi run untuk menjalankan text generation dengan parameter prompt dan sample_len yang diambil dari Args untuk mengolah prompt dan panjang sample yang dihasilkan dalam token yang dihasilkan dari model yang dipilih (Mistral atau Quantized Mistral), mengembalikan teks yang dihasilkan
+    fn run(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        budget: &budget::Budget,
+    ) -> Result<(String, budget::FinishReason)> {
         use std::io::Write;
         self.tokenizer.clear();
         let mut tokens = self
@@ -62,6 +119,8 @@ impl TextGeneration {
             .map_err(E::msg)?
             .get_ids()
             .to_vec();
+        // Prompt dipotong jika tidak muat di jendela konteks
+        tokens = self.context.truncate_prompt(tokens)?;
         for &t in tokens.iter() {
             if let Some(t) = self.tokenizer.next_token(t)? {
                 print!("{t}")
@@ -69,21 +128,84 @@ impl TextGeneration {
         }
         std::io::stdout().flush()?;
 
+        let start_gen = std::time::Instant::now();
+        let generation = self.generate_tokens(tokens, sample_len, budget, &mut |text| {
+            print!("{text}");
+            std::io::stdout().flush()?;
+            Ok(())
+        })?;
+        let dt = start_gen.elapsed();
+        println!(
+            "\n{} tokens generated ({:.2} token/s), finish reason: {}",
+            generation.generated_tokens,
+            generation.generated_tokens as f64 / dt.as_secs_f64(),
+            generation.finish_reason,
+        );
+        Ok((generation.text, generation.finish_reason))
+    }
+
+    // Fungsi generate_tokens untuk menghasilkan token dari prompt yang sudah ditokenisasi dan sudah dimasukkan ke
+    // TokenOutputStream, setiap potongan teks baru dikirim ke on_text (stdout pada CLI, event token pada mode --worker)
+    fn generate_tokens(
+        &mut self,
+        mut tokens: Vec<u32>,
+        sample_len: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        // kv cache dari generasi sebelumnya (mode worker) tidak dipakai lagi
+        self.model.clear_kv_cache();
+        let prompt_tokens = tokens.len();
         let mut generated_tokens = 0usize;
+        let mut output = String::new();
         let eos_token = match self.tokenizer.get_token("</s>") {
             Some(token) => token,
             None => anyhow::bail!("cannot find the </s> token"),
         };
-        let start_gen = std::time::Instant::now();
+        let mut finish_reason = budget::FinishReason::Length;
+        // Awal jendela konteks di dalam tokens, berubah saat jendela digeser
+        let mut window_start = 0usize;
+        let mut prefill = true;
         for index in 0..sample_len {
-            let context_size = if index > 0 { 1 } else { tokens.len() };
+            if let Some(reason) = budget.check() {
+                finish_reason = reason;
+                break;
+            }
+            if tokens.len() - window_start > self.context.size {
+                match self.context.overflow {
+                    context::Overflow::Stop => {
+                        finish_reason = budget::FinishReason::Context;
+                        break;
+                    }
+                    context::Overflow::Shift => {
+                        window_start = tokens.len() - self.context.shift_keep();
+                        self.model.clear_kv_cache();
+                        prefill = true;
+                    }
+                }
+            }
+            let context_size = if prefill {
+                tokens.len() - window_start
+            } else {
+                1
+            };
+            prefill = false;
             let start_pos = tokens.len().saturating_sub(context_size);
             let ctxt = &tokens[start_pos..];
+            // Posisi dihitung relatif terhadap awal jendela konteks
+            let seqlen_offset = start_pos - window_start;
             let input = Tensor::new(ctxt, &self.device)?.unsqueeze(0)?;
             let logits = match &mut self.model {
-                Model::Mistral(m) => m.forward(&input, start_pos)?,
-                Model::Quantized(m) => m.forward(&input, start_pos)?,
+                Model::Mistral(m) => m.forward(&input, seqlen_offset)?,
+                Model::Quantized(m) => m.forward(&input, seqlen_offset)?,
             };
+            // Prompt diproses dalam satu forward, sehingga --max-prompt-time diperiksa setelahnya
+            if index == 0 {
+                if let Some(reason) = budget.check_prompt() {
+                    finish_reason = reason;
+                    break;
+                }
+            }
             let logits = logits.squeeze(0)?.squeeze(0)?.to_dtype(DType::F32)?;
             let logits = if self.repeat_penalty == 1. {
                 logits
@@ -100,26 +222,154 @@ impl TextGeneration {
             tokens.push(next_token);
             generated_tokens += 1;
             if next_token == eos_token {
+                finish_reason = budget::FinishReason::Eos;
                 break;
             }
             if let Some(t) = self.tokenizer.next_token(next_token)? {
-                print!("{t}");
-                std::io::stdout().flush()?;
+                on_text(&t)?;
+                output.push_str(&t);
             }
         }
-        let dt = start_gen.elapsed();
         if let Some(rest) = self.tokenizer.decode_rest().map_err(E::msg)? {
-            print!("{rest}");
+            on_text(&rest)?;
+            output.push_str(&rest);
         }
-        std::io::stdout().flush()?;
-        println!(
-            "\n{generated_tokens} tokens generated ({:.2} token/s)",
-            generated_tokens as f64 / dt.as_secs_f64(),
-        );
+        Ok(worker::Generation {
+            text: output,
+            finish_reason,
+            prompt_tokens,
+            generated_tokens,
+        })
+    }
+}
+
+// Implementasi Backend agar TextGeneration bisa dilayani oleh mode --worker
+impl worker::Backend for TextGeneration {
+    fn generate(
+        &mut self,
+        prompt: &str,
+        max_tokens: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        self.tokenizer.clear();
+        let tokens = self
+            .tokenizer
+            .tokenizer()
+            .encode(prompt, true)
+            .map_err(E::msg)?
+            .get_ids()
+            .to_vec();
+        let tokens = self.context.truncate_prompt(tokens)?;
+        // Token prompt tetap dimasukkan ke TokenOutputStream agar spasi di awal teks yang dihasilkan benar
+        for &t in tokens.iter() {
+            self.tokenizer.next_token(t)?;
+        }
+        self.generate_tokens(tokens, max_tokens, budget, on_text)
+    }
+
+    // Prompt diproses sekali, lalu token continuation dimasukkan satu per satu dengan kv cache
+    fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)> {
+        let tokenizer = self.tokenizer.tokenizer();
+        let prompt_tokens = tokenizer.encode(prompt, true).map_err(E::msg)?;
+        let text = format!("{prompt}{continuation}");
+        let tokens = tokenizer.encode(text, true).map_err(E::msg)?;
+        let tokens = tokens.get_ids();
+        // Continuation dimulai dari token pertama yang berbeda dari token prompt
+        let start = prompt_tokens
+            .get_ids()
+            .iter()
+            .zip(tokens.iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .max(1);
+        if start >= tokens.len() {
+            anyhow::bail!("the continuation {continuation:?} adds no tokens to the prompt")
+        }
+        if tokens.len() > self.context.size {
+            anyhow::bail!(
+                "prompt and continuation have {} tokens but the context window is {}",
+                tokens.len(),
+                self.context.size
+            )
+        }
+        self.model.clear_kv_cache();
+        let input = Tensor::new(&tokens[..start], &self.device)?.unsqueeze(0)?;
+        let mut logits = self.model.forward(&input, 0)?;
+        let mut logprob = 0f64;
+        for end in start..tokens.len() {
+            let log_probs = logits.squeeze(0)?.squeeze(0)?.to_dtype(DType::F32)?;
+            let log_probs = candle_nn::ops::log_softmax(&log_probs, D::Minus1)?;
+            logprob += log_probs.get(tokens[end] as usize)?.to_scalar::<f32>()? as f64;
+            if end + 1 < tokens.len() {
+                let input = Tensor::new(&tokens[end..end + 1], &self.device)?.unsqueeze(0)?;
+                logits = self.model.forward(&input, end)?;
+            }
+        }
+        self.model.clear_kv_cache();
+        Ok((logprob, tokens.len() - start))
+    }
+
+    fn tokenize(&mut self, text: &str) -> Result<Vec<(u32, String)>> {
+        let tokens = self
+            .tokenizer
+            .tokenizer()
+            .encode(text, true)
+            .map_err(E::msg)?;
+        Ok(tokens
+            .get_ids()
+            .iter()
+            .zip(tokens.get_tokens().iter())
+            .map(|(id, token)| (*id, token.clone()))
+            .collect())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.model.clear_kv_cache();
+        self.tokenizer.clear();
+        self.logits_processor = LogitsProcessor::new(self.seed, self.temp, self.top_p);
         Ok(())
     }
 }
 
+// Enum WhichDType untuk memilih tipe data komputasi model non-kuantisasi
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+enum WhichDType {
+    F32,
+    F16,
+    Bf16,
+}
+
+impl WhichDType {
+    fn dtype(&self) -> DType {
+        match self {
+            Self::F32 => DType::F32,
+            Self::F16 => DType::F16,
+            Self::Bf16 => DType::BF16,
+        }
+    }
+}
+
+// Enum Command untuk subcommand selain text generation
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Quantize local safetensors weights into a GGUF file usable with --quantized.
+    Quantize(quantize::QuantizeArgs),
+    /// Merge LoRA adapters into the base safetensors weights and write the result.
+    ExportMerged(lora::ExportMergedArgs),
+    /// Re-run the generation recorded in a manifest and diff the output.
+    Replay(manifest::ReplayArgs),
+    /// Evaluate a multiple-choice JSONL task by the length-normalised log-likelihood of each
+    /// answer.
+    Eval(eval::EvalArgs),
+    /// Evaluate a free-form QA JSONL task (GSM8K style) by generating an answer and extracting the
+    /// final answer with regexes.
+    EvalGen(gen_eval::GenEvalArgs),
+    /// Compare two per-question eval results (from eval --output) and fail when the accuracy
+    /// drops more than --max-drop.
+    Compare(compare::CompareArgs),
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -135,7 +385,18 @@ struct Args {
     use_flash_attn: bool,
 
     #[arg(long)]
-    prompt: String,
+    prompt: Option<String>,
+
+    /// JSON file with a list of `{"role": ..., "content": ...}` messages, rendered with the chat
+    /// template and used as the prompt.
+    #[arg(long)]
+    messages: Option<String>,
+
+    /// The chat template used with --messages: a builtin (chatml, mistral-inst, rwkv, alpaca,
+    /// vicuna) or a jinja file. Defaults to the `chat_template` of tokenizer_config.json, then to
+    /// the builtin matching the model.
+    #[arg(long)]
+    chat_template: Option<String>,
 
     /// The temperature used to generate samples.
     #[arg(long)]
@@ -153,6 +414,22 @@ struct Args {
     #[arg(long, short = 'n', default_value_t = 10000)]
     sample_len: usize,
 
+    /// Stop generating after this many seconds of wall-clock time, prompt processing included.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_time: Option<f64>,
+
+    /// Stop if processing the prompt takes longer than this many seconds.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_prompt_time: Option<f64>,
+
+    /// How to shorten a prompt that does not fit in the context window.
+    #[arg(long, value_enum, default_value = "error")]
+    truncate: context::Truncate,
+
+    /// What to do when generation reaches the end of the context window.
+    #[arg(long, value_enum, default_value = "stop")]
+    context_overflow: context::Overflow,
+
     #[arg(long)]
     model_id: Option<String>,
 
@@ -168,6 +445,25 @@ struct Args {
     #[arg(long)]
     quantized: bool,
 
+    /// The dtype used for the weights and activations of the non-quantized model, logits are
+    /// always upcast to f32 before sampling. Defaults to bf16 on cuda and f32 otherwise.
+    #[arg(long, value_enum)]
+    dtype: Option<WhichDType>,
+
+    /// Load the weights even when the estimated memory (weights plus kv cache) is larger than
+    /// the available memory.
+    #[arg(long)]
+    no_memory_check: bool,
+
+    /// LoRA adapter directory (PEFT format) merged into the weights at load time, optionally
+    /// followed by `:<scale>`. Can be repeated.
+    #[arg(long, value_parser = lora::parse_lora)]
+    lora: Vec<lora::LoraAdapter>,
+
+    /// Write a run manifest (file hashes, features, args and output hash) to this file.
+    #[arg(long)]
+    manifest: Option<String>,
+
     /// Penalty to be applied for repeating tokens, 1. means no penalty.
     #[arg(long, default_value_t = 1.1)]
     repeat_penalty: f32,
@@ -175,6 +471,50 @@ struct Args {
     /// The context size to consider for the repeat penalty.
     #[arg(long, default_value_t = 64)]
     repeat_last_n: usize,
+
+    /// Serve newline-delimited JSON requests (generate, score, tokenize, reset) from stdin
+    /// instead of running a prompt. Responses and token events are written to stdout, logs to
+    /// stderr.
+    #[arg(long)]
+    worker: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// Fungsi untuk informasi build yang dicatat di manifest run: versi candle dari build.rs, fitur CPU dan fitur cargo yang aktif
+fn build_info() -> manifest::BuildInfo {
+    manifest::BuildInfo {
+        crate_name: env!("CARGO_PKG_NAME"),
+        crate_version: env!("CARGO_PKG_VERSION"),
+        candle_version: env!("CANDLE_VERSION"),
+        cpu_features: format!(
+            "avx: {}, neon: {}, simd128: {}, f16c: {}",
+            candle_core::utils::with_avx(),
+            candle_core::utils::with_neon(),
+            candle_core::utils::with_simd128(),
+            candle_core::utils::with_f16c()
+        ),
+        cargo_features: manifest::enabled_features(&[
+            ("accelerate", cfg!(feature = "accelerate")),
+            ("cuda", cfg!(feature = "cuda")),
+            ("cudnn", cfg!(feature = "cudnn")),
+            ("flash-attn", cfg!(feature = "flash-attn")),
+            ("mkl", cfg!(feature = "mkl")),
+            ("nccl", cfg!(feature = "nccl")),
+            ("metal", cfg!(feature = "metal")),
+        ]),
+    }
+}
+
+// Fungsi untuk mengambil file opsional dari repo Hugging Face Hub: None hanya jika file tidak ada (404), error
+// jaringan atau autentikasi tetap dilaporkan
+fn hub_get_optional(repo: &ApiRepo, filename: &str) -> Result<Option<std::path::PathBuf>> {
+    match repo.get(filename) {
+        Ok(path) => Ok(Some(path)),
+        Err(ApiError::RequestError(err)) if matches!(*err, ureq::Error::Status(404, _)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
 }
 
 // Fungsi main untuk menjalankan program text generation dengan parameter args yang diambil dari Args untuk mengolah argumen yang diberikan pada program text generation yang dijalankan pada device yang dipilih (CPU atau GPU) dengan model yang dipilih (Mistral atau Quantized Mistral) dan tokenizer yang digunakan untuk mengolah token yang dihasilkan dari model yang dipilih (Mistral atau Quantized Mistral)
@@ -190,14 +530,34 @@ fn main() -> Result<()> {
     } else {
         None
     };
-    println!(
-        "avx: {}, neon: {}, simd128: {}, f16c: {}",
-        candle_core::utils::with_avx(),
-        candle_core::utils::with_neon(),
-        candle_core::utils::with_simd128(),
-        candle_core::utils::with_f16c()
-    );
-    println!(
+    // Jika subcommand diberikan, maka hanya menjalankan subcommand tersebut
+    match args.command {
+        Some(Command::Quantize(quantize_args)) => {
+            return quantize::run(quantize_args, "mistral", Ok)
+        }
+        Some(Command::ExportMerged(export_args)) => return lora::export_merged(export_args),
+        Some(Command::Replay(replay_args)) => return manifest::replay(replay_args),
+        Some(Command::Compare(compare_args)) => return compare::run(&compare_args),
+        Some(Command::Eval(_) | Command::EvalGen(_)) | None => {}
+    }
+    let eval = matches!(args.command, Some(Command::Eval(_) | Command::EvalGen(_)));
+    if args.worker && eval {
+        anyhow::bail!("eval and eval-gen cannot be used with --worker")
+    }
+    if args.worker || eval {
+        if args.prompt.is_some() || args.messages.is_some() {
+            anyhow::bail!("--worker, eval and eval-gen cannot be used with --prompt or --messages")
+        }
+        // Pada mode worker stdout hanya berisi response JSON, log dipindah ke stderr
+        if args.worker {
+            worker::activate();
+        }
+    } else if args.prompt.is_some() == args.messages.is_some() {
+        anyhow::bail!("exactly one of --prompt and --messages must be specified")
+    }
+    let build = build_info();
+    log!("{}", build.cpu_features);
+    log!(
         "temp: {:.2} repeat-penalty: {:.2} repeat-last-n: {}",
         args.temperature.unwrap_or(0.),
         args.repeat_penalty,
@@ -238,31 +598,113 @@ fn main() -> Result<()> {
             }
         }
     };
-    println!("retrieved the files in {:?}", start.elapsed());
-    let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+    log!("retrieved the files in {:?}", start.elapsed());
+    let tokenizer = Tokenizer::from_file(&tokenizer_filename).map_err(E::msg)?;
+
+    // Jika messages diberikan, pesan dirender dengan template chat dan dipakai sebagai prompt
+    let prompt = match (args.prompt, args.messages) {
+        (Some(prompt), _) => Some(prompt),
+        (None, Some(messages)) => {
+            let messages = chat::load_messages(messages)?;
+            let tokenizer_config = match args.chat_template {
+                Some(_) => None,
+                None => hub_get_optional(&repo, "tokenizer_config.json")?,
+            };
+            let template = chat::resolve(
+                args.chat_template.as_deref(),
+                tokenizer_config.as_deref(),
+                chat::BuiltinTemplate::MistralInst,
+                "</s>",
+            )?;
+            Some(template.render(&messages, true)?)
+        }
+        (None, None) => None,
+    };
 
     let start = std::time::Instant::now();
     let config = Config::config_7b_v0_1(args.use_flash_attn);
     let device = candle_examples::device(args.cpu)?;
+    let dtype = match args.dtype {
+        Some(dtype) => dtype.dtype(),
+        None if device.is_cuda() => DType::BF16,
+        None => DType::F32,
+    };
+    // Jendela konteks dibaca dari config.json, config bawaan Mistral 7B v0.1 hanya jika repo tidak memiliki config.json
+    let size = match hub_get_optional(&repo, "config.json")? {
+        Some(config_filename) => context::size_from_config(&config_filename)?,
+        None => MISTRAL_7B_WINDOW,
+    };
+    // Memori yang dibutuhkan diperkirakan dari header file bobot sebelum dimuat. Hanya di CPU, karena bobot di GPU
+    // tidak memakai RAM
+    if device.is_cpu() && !args.no_memory_check {
+        let kv_width =
+            config.num_key_value_heads * (config.hidden_size / config.num_attention_heads);
+        let estimate = |weights: u64, dtype: DType| memory::Estimate {
+            weights,
+            cache: memory::kv_cache_bytes(config.num_hidden_layers, kv_width, size, 1, dtype),
+            cache_name: "cache",
+        };
+        let (estimate, suggestions) = if args.quantized {
+            // Model kuantisasi menyimpan kv cache dalam f32
+            let weights = memory::gguf_bytes(&filenames[0])?;
+            (estimate(weights, DType::F32), vec![])
+        } else {
+            let weights = memory::safetensors_bytes(&filenames, dtype)?;
+            let mut suggestions = vec!["--quantized".to_string()];
+            if dtype == DType::F32 {
+                let bf16 = memory::gib(estimate(weights / 2, DType::BF16).total());
+                suggestions.push(format!("--dtype bf16 (about {bf16})"));
+            }
+            (estimate(weights, dtype), suggestions)
+        };
+        memory::check(&estimate, memory::available_bytes(), &suggestions)?;
+    }
     let (model, device) = if args.quantized {
+        if !args.lora.is_empty() {
+            anyhow::bail!("--lora requires non-quantized weights, use export-merged then quantize")
+        }
         let filename = &filenames[0];
         let vb =
             candle_transformers::quantized_var_builder::VarBuilder::from_gguf(filename, &device)?;
         let model = QMistral::new(&config, vb)?;
         (Model::Quantized(model), device)
     } else {
-        let dtype = if device.is_cuda() {
-            DType::BF16
+        // Jika ada adapter LoRA, bobot dimuat ke memori dan digabung terlebih dahulu
+        let merged = if args.lora.is_empty() {
+            None
         } else {
-            DType::F32
+            Some(lora::load_merged(&filenames, &args.lora)?)
+        };
+        let load = |dtype: DType| -> Result<Model> {
+            let vb = match &merged {
+                Some(tensors) => VarBuilder::from_tensors(tensors.clone(), dtype, &device),
+                None => unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, &device)? },
+            };
+            Ok(Model::Mistral(Mistral::new(&config, vb)?))
         };
-        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, &device)? };
-        let model = Mistral::new(&config, vb)?;
-        (Model::Mistral(model), device)
+        let mut model = load(dtype)?;
+        // Jika tipe data bukan f32, dicoba satu forward untuk memastikan operasi didukung di device ini
+        let model = match probe(&mut model, &device) {
+            Ok(()) => model,
+            Err(err) if dtype != DType::F32 => {
+                log!(
+                    "warning: {dtype:?} is not supported on {device:?} ({err}), falling back to F32"
+                );
+                drop(model);
+                load(DType::F32)?
+            }
+            Err(err) => return Err(err),
+        };
+        (model, device)
     };
 
-    println!("loaded the model in {:?}", start.elapsed());
+    log!("loaded the model in {:?}", start.elapsed());
 
+    let context = context::ContextWindow {
+        size,
+        truncate: args.truncate,
+        overflow: args.context_overflow,
+    };
     let mut pipeline = TextGeneration::new(
         model,
         tokenizer,
@@ -271,8 +713,68 @@ fn main() -> Result<()> {
         args.top_p,
         args.repeat_penalty,
         args.repeat_last_n,
+        context,
         &device,
     );
-    pipeline.run(&args.prompt, args.sample_len)?;
+    // Subcommand eval menilai setiap pilihan jawaban dan eval-gen menghasilkan jawaban bebas, keduanya lewat Backend
+    // yang sama dengan mode worker. Sidik jari model untuk journal dihitung dari path dan ukuran file serta argumen yang
+    // mengubah hasil
+    let fingerprint = || -> Result<String> {
+        let mut files = filenames.clone();
+        files.push(tokenizer_filename.clone());
+        let description = format!(
+            "lora: {:?}, quantized: {}, dtype: {:?}, seed: {}, temperature: {:?}, top_p: {:?}, repeat_penalty: {}, repeat_last_n: {}",
+            args.lora,
+            args.quantized,
+            args.dtype,
+            args.seed,
+            args.temperature,
+            args.top_p,
+            args.repeat_penalty,
+            args.repeat_last_n
+        );
+        journal::model_fingerprint(&description, &files)
+    };
+    match args.command {
+        Some(Command::Eval(eval_args)) => {
+            return eval::run(&mut pipeline, &eval_args, &fingerprint()?)
+        }
+        Some(Command::EvalGen(gen_args)) => {
+            return gen_eval::run(&mut pipeline, &gen_args, &fingerprint()?)
+        }
+        _ => {}
+    }
+    // Pada mode worker model dimuat sekali lalu request dilayani sampai stdin ditutup
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => {
+            let limits = worker::Limits {
+                max_tokens: args.sample_len,
+                max_time: args.max_time,
+                max_prompt_time: args.max_prompt_time,
+            };
+            log!("worker ready, reading requests from stdin");
+            let (stdin, stdout) = (std::io::stdin(), std::io::stdout());
+            return worker::serve(&mut pipeline, limits, stdin.lock(), stdout.lock());
+        }
+    };
+    budget::install_ctrlc_handler()?;
+    let budget = budget::Budget::new(args.max_time, args.max_prompt_time);
+    let (output, finish_reason) = pipeline.run(&prompt, args.sample_len, &budget)?;
+    // Menulis manifest run jika diminta
+    if let Some(path) = args.manifest {
+        let mut files = filenames.clone();
+        files.push(tokenizer_filename);
+        manifest::Manifest::new(&build, &files, &output, finish_reason)?.save(path)?;
+    }
+    Ok(())
+}
+
+// Fungsi probe untuk menjalankan satu forward dengan satu token untuk mengecek apakah tipe data model didukung
+fn probe(model: &mut Model, device: &Device) -> Result<()> {
+    let input = Tensor::new(&[[1u32]], device)?;
+    let logits = model.forward(&input, 0)?.to_dtype(DType::F32)?;
+    logits.sum_all()?.to_scalar::<f32>()?;
+    model.clear_kv_cache();
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+// Smoke test C ABI libmistral: memuat model acak kecil, tokenisasi, generasi dengan callback, lalu membebaskan handle.
+// Dikompilasi dan dijalankan oleh tests/ffi.rs dengan direktori model sebagai argumen
+#include <stdio.h>
+#include <string.h>
+
+#include "mistral.h"
+
+// Callback yang menghitung potongan teks dan berhenti setelah stop_after potongan
+struct Counter {
+  int pieces;
+  int stop_after;
+};
+
+static int32_t on_text(const char *text, void *user_data) {
+  struct Counter *counter = (struct Counter *)user_data;
+  counter->pieces += 1;
+  printf("%s", text);
+  return counter->pieces >= counter->stop_after;
+}
+
+static int fail(const char *what) {
+  fprintf(stderr, "%s failed: %s\n", what, mistral_last_error());
+  return 1;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <model-dir>\n", argv[0]);
+    return 2;
+  }
+
+  MistralModel *missing = NULL;
+  if (mistral_model_load("/nonexistent/mistral-model", &missing) != MISTRAL_STATUS_LOAD || missing != NULL ||
+      strlen(mistral_last_error()) == 0) {
+    return fail("loading a missing model");
+  }
+
+  MistralModel *model = NULL;
+  if (mistral_model_load(argv[1], &model) != MISTRAL_STATUS_OK) {
+    return fail("mistral_model_load");
+  }
+
+  // Panjang token ditanyakan dulu dengan buffer kosong
+  size_t len = 0;
+  if (mistral_tokenize(model, "hello world", NULL, 0, &len) != MISTRAL_STATUS_BUFFER_TOO_SMALL || len != 2) {
+    return fail("mistral_tokenize without a buffer");
+  }
+  uint32_t ids[8];
+  if (mistral_tokenize(model, "hello world", ids, 8, &len) != MISTRAL_STATUS_OK || len != 2 ||
+      ids[0] != 3 || ids[1] != 4) {
+    return fail("mistral_tokenize");
+  }
+
+  MistralSamplingParams params = mistral_sampling_params_default();
+  params.temperature = 0.8;
+  params.max_tokens = 16;
+  struct Counter counter = {0, 1000};
+  if (mistral_generate(model, "hello world", &params, on_text, &counter) != MISTRAL_STATUS_OK) {
+    return fail("mistral_generate");
+  }
+  if (counter.pieces > 16) {
+    fprintf(stderr, "mistral_generate produced %d pieces for 16 tokens\n", counter.pieces);
+    return 1;
+  }
+
+  // Callback yang mengembalikan nilai bukan nol menghentikan generasi
+  struct Counter stopped = {0, 1};
+  if (mistral_generate(model, "hello", NULL, on_text, &stopped) != MISTRAL_STATUS_OK || stopped.pieces > 1) {
+    return fail("stopping mistral_generate from the callback");
+  }
+
+  if (mistral_generate(model, NULL, &params, on_text, &counter) != MISTRAL_STATUS_INVALID_ARGUMENT) {
+    return fail("mistral_generate without a prompt");
+  }
+
+  mistral_model_free(model);
+  mistral_model_free(NULL);
+  printf("\nsmoke test passed\n");
+  return 0;
+}
@@ -0,0 +1,43 @@
+// Script build untuk mencatat versi candle dari Cargo.lock agar bisa ditulis ke manifest run, dan untuk membuat
+// header C ABI dari src/ffi.rs di OUT_DIR. include/phi.h di-commit dan dibandingkan dengan header ini oleh tests/ffi.rs
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let version = std::fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| {
+            let mut lines = lock.lines();
+            while let Some(line) = lines.next() {
+                if line == "name = \"candle-core\"" {
+                    let version = lines.next()?.strip_prefix("version = ")?.trim_matches('"');
+                    // Untuk dependensi git, commit yang dipakai juga dicatat
+                    return match lines.next().and_then(|l| l.strip_prefix("source = \"git+")) {
+                        Some(source) => {
+                            Some(format!("{version} ({})", source.trim_end_matches('"')))
+                        }
+                        None => Some(version.to_string()),
+                    };
+                }
+            }
+            None
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CANDLE_VERSION={version}");
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    // Header hanya ditulis ke OUT_DIR agar build tidak mengubah direktori sumber
+    let path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("phi.h");
+    let header = cbindgen::Config::from_file("cbindgen.toml").and_then(|config| {
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src("src/ffi.rs")
+            .generate()
+            .map_err(|err| err.to_string())
+    });
+    match header {
+        Ok(header) => {
+            header.write_to_file(&path);
+        }
+        Err(err) => println!("cargo:warning=cannot generate {path:?}: {err}"),
+    }
+}
@@ -0,0 +1,301 @@
+// Modul untuk kalibrasi jawaban pilihan ganda: logits pilihan A-D diubah menjadi probabilitas dengan softmax, lalu
+// dihitung expected calibration error (ECE), Brier score, negative log-likelihood, reliability diagram per bin
+// confidence dan akurasi pada ambang confidence. Temperature scaling opsional di-fit pada split dev
+use anyhow::Result;
+use std::path::Path;
+
+// Struct untuk satu soal yang sudah dinilai: logits setiap pilihan dan indeks jawaban benar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scored {
+    pub logits: Vec<f32>,
+    pub gold: usize,
+}
+
+impl Scored {
+    // Membuat soal dari logits dan huruf jawaban benar, None jika hurufnya bukan salah satu pilihan
+    pub fn new(logits: Vec<f32>, answer: &str) -> Option<Self> {
+        let gold = match answer.trim() {
+            "A" => 0,
+            "B" => 1,
+            "C" => 2,
+            "D" => 3,
+            _ => return None,
+        };
+        (gold < logits.len()).then_some(Self { logits, gold })
+    }
+}
+
+// Struct untuk pengaturan laporan kalibrasi
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub bins: usize,
+    pub thresholds: Vec<f64>,
+    pub reliability: Option<String>,
+    pub temperature_scaling: bool,
+}
+
+// Fungsi untuk mengubah logits menjadi probabilitas dengan softmax, logits dibagi temperature terlebih dahulu
+pub fn softmax(logits: &[f32], temperature: f64) -> Vec<f64> {
+    let scaled = logits
+        .iter()
+        .map(|&l| l as f64 / temperature)
+        .collect::<Vec<_>>();
+    let max = scaled.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exp = scaled.iter().map(|s| (s - max).exp()).collect::<Vec<_>>();
+    let sum: f64 = exp.iter().sum();
+    exp.iter().map(|e| e / sum).collect()
+}
+
+// Fungsi untuk mencari indeks probabilitas terbesar, sama dengan pilihan model
+fn argmax(probs: &[f64]) -> usize {
+    let mut best = 0;
+    for (index, p) in probs.iter().enumerate() {
+        if *p > probs[best] {
+            best = index;
+        }
+    }
+    best
+}
+
+// Fungsi untuk menghitung rata-rata negative log-likelihood jawaban benar pada temperature tertentu
+pub fn nll(items: &[Scored], temperature: f64) -> f64 {
+    let total: f64 = items
+        .iter()
+        .map(|item| {
+            -softmax(&item.logits, temperature)[item.gold]
+                .max(1e-12)
+                .ln()
+        })
+        .sum();
+    total / items.len().max(1) as f64
+}
+
+// Fungsi untuk mencari temperature dengan NLL terkecil lewat golden-section search pada log temperature di antara 0.05
+// dan 20. NLL sebagai fungsi temperature cukup mulus sehingga 60 iterasi sudah lebih dari cukup
+pub fn fit_temperature(items: &[Scored]) -> f64 {
+    let ratio = (5f64.sqrt() - 1.) / 2.;
+    let (mut lo, mut hi) = (0.05f64.ln(), 20f64.ln());
+    let f = |log_t: f64| nll(items, log_t.exp());
+    let mut a = hi - ratio * (hi - lo);
+    let mut b = lo + ratio * (hi - lo);
+    let (mut fa, mut fb) = (f(a), f(b));
+    for _ in 0..60 {
+        if fa < fb {
+            hi = b;
+            (b, fb) = (a, fa);
+            a = hi - ratio * (hi - lo);
+            fa = f(a);
+        } else {
+            lo = a;
+            (a, fa) = (b, fb);
+            b = lo + ratio * (hi - lo);
+            fb = f(b);
+        }
+    }
+    ((lo + hi) / 2.).exp()
+}
+
+// Struct untuk satu bin reliability diagram: rentang confidence, jumlah soal, rata-rata confidence dan akurasinya
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub confidence: f64,
+    pub accuracy: f64,
+}
+
+// Struct untuk akurasi soal dengan confidence minimal threshold, coverage adalah bagian soal yang dijawab
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    pub threshold: f64,
+    pub count: usize,
+    pub coverage: f64,
+    pub accuracy: f64,
+}
+
+// Struct untuk laporan kalibrasi pada satu temperature
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub temperature: f64,
+    pub total: usize,
+    pub accuracy: f64,
+    pub ece: f64,
+    pub brier: f64,
+    pub nll: f64,
+    pub bins: Vec<Bin>,
+    pub thresholds: Vec<Threshold>,
+}
+
+// Fungsi untuk membuat laporan kalibrasi. ECE adalah rata-rata selisih akurasi dan confidence setiap bin, dibobot
+// jumlah soal bin tersebut. Brier score adalah jumlah kuadrat selisih probabilitas dengan jawaban one-hot
+pub fn report(items: &[Scored], temperature: f64, bins: usize, thresholds: &[f64]) -> Report {
+    let bins = bins.max(1);
+    let mut sums = vec![(0usize, 0f64, 0usize); bins];
+    let (mut correct, mut brier) = (0usize, 0f64);
+    let mut answers = vec![];
+    for item in items.iter() {
+        let probs = softmax(&item.logits, temperature);
+        let pred = argmax(&probs);
+        let confidence = probs[pred];
+        let is_correct = pred == item.gold;
+        correct += is_correct as usize;
+        brier += probs
+            .iter()
+            .enumerate()
+            .map(|(index, p)| (p - (index == item.gold) as usize as f64).powi(2))
+            .sum::<f64>();
+        let bin = ((confidence * bins as f64) as usize).min(bins - 1);
+        sums[bin].0 += 1;
+        sums[bin].1 += confidence;
+        sums[bin].2 += is_correct as usize;
+        answers.push((confidence, is_correct));
+    }
+    let total = items.len().max(1) as f64;
+    let bins = sums
+        .iter()
+        .enumerate()
+        .map(|(index, &(count, confidence, correct))| {
+            let n = count.max(1) as f64;
+            Bin {
+                lower: index as f64 / bins as f64,
+                upper: (index + 1) as f64 / bins as f64,
+                count,
+                confidence: confidence / n,
+                accuracy: correct as f64 / n,
+            }
+        })
+        .collect::<Vec<_>>();
+    let ece = bins
+        .iter()
+        .map(|bin| bin.count as f64 / total * (bin.accuracy - bin.confidence).abs())
+        .sum();
+    let thresholds = thresholds
+        .iter()
+        .map(|&threshold| {
+            let kept = answers
+                .iter()
+                .filter(|(confidence, _)| *confidence >= threshold)
+                .collect::<Vec<_>>();
+            let count = kept.len();
+            Threshold {
+                threshold,
+                count,
+                coverage: count as f64 / total,
+                accuracy: kept.iter().filter(|(_, c)| *c).count() as f64 / count.max(1) as f64,
+            }
+        })
+        .collect();
+    Report {
+        temperature,
+        total: items.len(),
+        accuracy: correct as f64 / total,
+        ece,
+        brier: brier / total,
+        nll: nll(items, temperature),
+        bins,
+        thresholds,
+    }
+}
+
+impl Report {
+    // Mencetak ringkasan kalibrasi dan akurasi pada setiap ambang confidence
+    pub fn print(&self) {
+        println!(
+            "calibration (temperature {:.3}): ece {:.4} brier {:.4} nll {:.4} over {} questions",
+            self.temperature, self.ece, self.brier, self.nll, self.total
+        );
+        for t in self.thresholds.iter() {
+            println!(
+                "  confidence >= {:.2}: accuracy {:.4} coverage {:.4} ({}/{})",
+                t.threshold, t.accuracy, t.coverage, t.count, self.total
+            );
+        }
+    }
+}
+
+// Fungsi untuk menulis reliability diagram dari satu atau beberapa laporan (contoh sebelum dan sesudah temperature
+// scaling) sebagai CSV, satu baris per bin
+pub fn write_reliability<P: AsRef<Path>>(path: P, reports: &[Report]) -> Result<()> {
+    let mut csv = "temperature,bin_lower,bin_upper,count,confidence,accuracy\n".to_string();
+    for report in reports.iter() {
+        for bin in report.bins.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.6},{:.6}\n",
+                report.temperature, bin.lower, bin.upper, bin.count, bin.confidence, bin.accuracy
+            ));
+        }
+    }
+    std::fs::write(path, csv)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::compare::SplitMix;
+
+    fn item(logits: &[f32], gold: usize) -> Scored {
+        Scored {
+            logits: logits.to_vec(),
+            gold,
+        }
+    }
+
+    #[test]
+    fn metrics_of_a_small_set() {
+        assert_eq!(Scored::new(vec![0.; 4], " C"), Some(item(&[0.; 4], 2)));
+        assert_eq!(Scored::new(vec![0.; 4], "E"), None);
+        let probs = softmax(&[0., 0., 0., 0.], 1.);
+        assert_eq!(probs, vec![0.25; 4]);
+        // ln 3 membuat probabilitas pilihan pertama 0.5 dan tiga lainnya 1/6
+        let l = 3f32.ln();
+        let items = [item(&[l, 0., 0., 0.], 0), item(&[l, 0., 0., 0.], 1)];
+        let report = report(&items, 1., 10, &[0.4, 0.6]);
+        assert_eq!(report.accuracy, 0.5);
+        // Kedua soal masuk bin yang sama dengan confidence 0.5 dan akurasi 0.5
+        assert!(report.ece < 1e-6, "ece {}", report.ece);
+        assert_eq!(report.bins.iter().filter(|bin| bin.count == 2).count(), 1);
+        let brier_right = 0.25 + 3. / 36.;
+        let brier_wrong = 0.25 + 25. / 36. + 2. / 36.;
+        assert!((report.brier - (brier_right + brier_wrong) / 2.).abs() < 1e-6);
+        assert!((report.nll - (0.5f64.ln() + (1. / 6f64).ln()) / -2.).abs() < 1e-6);
+        assert_eq!(report.thresholds[0].count, 2);
+        assert_eq!(report.thresholds[0].accuracy, 0.5);
+        assert_eq!(report.thresholds[1].count, 0);
+        assert_eq!(report.thresholds[1].coverage, 0.);
+    }
+
+    #[test]
+    fn temperature_scaling_recovers_the_temperature() {
+        // Jawaban benar diambil dari softmax(logits / 2), sehingga temperature terbaik sekitar 2
+        let mut rng = SplitMix(7);
+        let items = (0..4000)
+            .map(|_| {
+                let logits = (0..4)
+                    .map(|_| (rng.uniform() - 0.5) * 12.)
+                    .collect::<Vec<_>>();
+                let probs = softmax(&logits, 2.);
+                let u = rng.uniform() as f64;
+                let mut gold = 3;
+                let mut cumulative = 0.;
+                for (index, p) in probs.iter().enumerate() {
+                    cumulative += p;
+                    if u < cumulative {
+                        gold = index;
+                        break;
+                    }
+                }
+                item(&logits, gold)
+            })
+            .collect::<Vec<_>>();
+        let temperature = fit_temperature(&items);
+        assert!((temperature - 2.).abs() < 0.25, "temperature {temperature}");
+        let before = report(&items, 1., 10, &[]);
+        let after = report(&items, temperature, 10, &[]);
+        assert!(after.nll < before.nll);
+        assert!(after.ece < before.ece);
+        // Temperature tidak mengubah pilihan model
+        assert_eq!(after.accuracy, before.accuracy);
+    }
+}
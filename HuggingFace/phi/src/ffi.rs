@@ -0,0 +1,409 @@
+// Modul C ABI untuk memakai model Phi dari C dan C++, header include/phi.h dibuat dari modul ini oleh build.rs (cbindgen).
+// Satu handle boleh dipakai bersamaan dari beberapa thread karena generasi hanya membaca bobot model, kv cache dan sampler
+// dibuat baru di setiap panggilan. Pesan error terakhir disimpan per thread
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use candle_core::{DType, Device, Tensor}; // Untuk operasi tensor
+use candle_nn::VarBuilder; // Untuk memuat bobot
+use candle_transformers::generation::LogitsProcessor; // Untuk sampling token
+use tokenizers::Tokenizer; // Untuk tokenisasi
+
+use crate::lora_phi::{Config, KvCache, LoraPhi};
+use crate::token_stream;
+
+/// Status returned by the `phi_*` functions. When it is not `PHI_STATUS_OK`, `phi_last_error`
+/// returns a message describing the failure.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhiStatus {
+    Ok = 0,
+    /// A required pointer is null or a string is not valid UTF-8.
+    InvalidArgument = 1,
+    /// The model directory is missing a file or does not contain a valid phi model.
+    Load = 2,
+    /// Tokenization or a forward pass failed.
+    Inference = 3,
+    /// The buffer given to `phi_tokenize` is too small, the required length is written to `len`.
+    BufferTooSmall = 4,
+    /// A panic was caught at the API boundary, the handle should not be used anymore.
+    Panic = 5,
+}
+
+/// Sampling parameters of `phi_generate`, start from `phi_sampling_params_default`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PhiSamplingParams {
+    /// The temperature used to generate samples, 0 or less means greedy decoding.
+    pub temperature: f64,
+    /// Nucleus sampling probability cutoff, disabled when not between 0 and 1.
+    pub top_p: f64,
+    /// The seed to use when generating random samples.
+    pub seed: u64,
+    /// The maximum number of tokens to generate.
+    pub max_tokens: u32,
+    /// Penalty to be applied for repeating tokens, 1 means no penalty.
+    pub repeat_penalty: f32,
+    /// The context size to consider for the repeat penalty.
+    pub repeat_last_n: u32,
+}
+
+/// Called with each new piece of generated text, a null-terminated UTF-8 string that is only
+/// valid during the call. Return 0 to continue and any other value to stop generating.
+pub type PhiTokenCallback =
+    Option<unsafe extern "C" fn(text: *const c_char, user_data: *mut c_void) -> i32>;
+
+/// Opaque handle to a loaded model, created by `phi_model_load` and released by
+/// `phi_model_free`. A handle may be used from several threads at once, every call keeps its own
+/// kv cache and sampler.
+pub struct PhiModel {
+    model: LoraPhi,
+    tokenizer: Tokenizer,
+    device: Device,
+    eos_token: u32,
+    context_size: usize,
+}
+
+// Struct untuk error beserta status yang dikembalikan ke pemanggil C
+struct Error {
+    status: PhiStatus,
+    message: String,
+}
+
+impl Error {
+    fn new(status: PhiStatus, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    // Pesan error terakhir di thread ini, dikembalikan oleh phi_last_error
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+// Fungsi untuk menjalankan isi fungsi C ABI: panic ditangkap agar tidak melewati batas FFI dan pesan error disimpan
+fn call(f: impl FnOnce() -> Result<(), Error>) -> PhiStatus {
+    let result = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = match panic.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match panic.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+            Err(Error::new(PhiStatus::Panic, message))
+        }
+    };
+    match result {
+        Ok(()) => PhiStatus::Ok,
+        Err(err) => {
+            // Karakter nul di tengah pesan diganti agar pesan bisa menjadi string C
+            let message = CString::new(err.message.replace('\0', " ")).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = message);
+            err.status
+        }
+    }
+}
+
+// Fungsi untuk membaca argumen string C sebagai &str
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if ptr.is_null() {
+        return Err(Error::new(
+            PhiStatus::InvalidArgument,
+            format!("{name} is null"),
+        ));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|err| {
+        Error::new(
+            PhiStatus::InvalidArgument,
+            format!("{name} is not valid UTF-8: {err}"),
+        )
+    })
+}
+
+// Fungsi untuk membaca handle model dari pointer
+unsafe fn model_arg<'a>(model: *const PhiModel) -> Result<&'a PhiModel, Error> {
+    match model.as_ref() {
+        Some(model) => Ok(model),
+        None => Err(Error::new(PhiStatus::InvalidArgument, "model is null")),
+    }
+}
+
+impl PhiModel {
+    // Fungsi untuk memuat config.json, tokenizer.json dan semua file .safetensors dari direktori lokal
+    fn load(dir: &Path) -> anyhow::Result<Self> {
+        let device = Device::Cpu;
+        let config = std::fs::read_to_string(dir.join("config.json"))?;
+        let context_size = serde_json::from_str::<serde_json::Value>(&config)?
+            .get("max_position_embeddings")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(2048) as usize;
+        let config: Config = serde_json::from_str(&config)?;
+        let tokenizer =
+            Tokenizer::from_file(dir.join("tokenizer.json")).map_err(anyhow::Error::msg)?;
+        let eos_token = match tokenizer.get_vocab(true).get("<|endoftext|>") {
+            Some(token) => *token,
+            None => anyhow::bail!("cannot find the endoftext token"),
+        };
+        let mut filenames = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|v| v.to_str()) == Some("safetensors") {
+                filenames.push(path);
+            }
+        }
+        if filenames.is_empty() {
+            anyhow::bail!("no .safetensors files in {dir:?}")
+        }
+        filenames.sort();
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &device)? };
+        let model = LoraPhi::load(&config, vb)?;
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            eos_token,
+            context_size,
+        })
+    }
+
+    // Fungsi untuk menghasilkan teks dari prompt, setiap potongan teks baru dikirim ke on_text yang mengembalikan
+    // false untuk berhenti
+    fn generate(
+        &self,
+        prompt: &str,
+        params: &PhiSamplingParams,
+        on_text: &mut dyn FnMut(&str) -> bool,
+    ) -> anyhow::Result<()> {
+        let tokens = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(anyhow::Error::msg)?;
+        let mut tokens = tokens.get_ids().to_vec();
+        if tokens.is_empty() {
+            anyhow::bail!("Empty prompts are not supported in the phi model.")
+        }
+        if tokens.len() >= self.context_size {
+            anyhow::bail!(
+                "prompt has {} tokens but the context window is {}",
+                tokens.len(),
+                self.context_size
+            )
+        }
+        let temperature = (params.temperature > 0.).then_some(params.temperature);
+        let top_p = (params.top_p > 0. && params.top_p < 1.).then_some(params.top_p);
+        let mut logits_processor = LogitsProcessor::new(params.seed, temperature, top_p);
+        let mut cache = KvCache::default();
+        let mut stream = token_stream::stream(&self.tokenizer);
+        // Prompt diproses dalam satu forward, setelah itu hanya token terakhir
+        let mut input = tokens.clone();
+        for _ in 0..params.max_tokens {
+            if tokens.len() >= self.context_size {
+                break;
+            }
+            let xs = Tensor::new(input.as_slice(), &self.device)?.unsqueeze(0)?;
+            let logits = self.model.forward_cached(&xs, &mut cache)?;
+            let logits = logits.squeeze(0)?.to_dtype(DType::F32)?;
+            let logits = if params.repeat_penalty == 1. {
+                logits
+            } else {
+                let start_at = tokens.len().saturating_sub(params.repeat_last_n as usize);
+                candle_transformers::utils::apply_repeat_penalty(
+                    &logits,
+                    params.repeat_penalty,
+                    &tokens[start_at..],
+                )?
+            };
+            let next_token = logits_processor.sample(&logits)?;
+            if next_token == self.eos_token {
+                break;
+            }
+            tokens.push(next_token);
+            input = vec![next_token];
+            if let Some(text) = stream.next_token(next_token)? {
+                if !on_text(&text) {
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(text) = stream.flush()? {
+            on_text(&text);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the default sampling parameters: greedy decoding, seed 299792458, 256 tokens and a
+/// repeat penalty of 1.1 over the last 64 tokens.
+#[no_mangle]
+pub extern "C" fn phi_sampling_params_default() -> PhiSamplingParams {
+    PhiSamplingParams {
+        temperature: 0.,
+        top_p: 0.,
+        seed: 299792458,
+        max_tokens: 256,
+        repeat_penalty: 1.1,
+        repeat_last_n: 64,
+    }
+}
+
+/// Loads a phi 1, 1.5 or 2 model from a local directory holding `config.json`, `tokenizer.json`
+/// and the `.safetensors` weights, and writes the new handle to `out`. The model runs on the CPU
+/// in f32.
+///
+/// # Safety
+///
+/// `dir` must be a null-terminated string and `out` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn phi_model_load(dir: *const c_char, out: *mut *mut PhiModel) -> PhiStatus {
+    call(|| {
+        if out.is_null() {
+            return Err(Error::new(PhiStatus::InvalidArgument, "out is null"));
+        }
+        let dir = str_arg(dir, "dir")?;
+        let model = PhiModel::load(Path::new(dir))
+            .map_err(|err| Error::new(PhiStatus::Load, format!("{err:#}")))?;
+        *out = Box::into_raw(Box::new(model));
+        Ok(())
+    })
+}
+
+/// Generates text from `prompt` and calls `callback` with each new piece of text until the end
+/// of text token, `max_tokens`, the end of the context window or a non-zero return value of the
+/// callback. `params` may be null to use the defaults and `user_data` is passed to the callback
+/// as is.
+///
+/// # Safety
+///
+/// `model` must be a handle returned by `phi_model_load` that has not been freed, `prompt` a
+/// null-terminated string and `params` null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn phi_generate(
+    model: *const PhiModel,
+    prompt: *const c_char,
+    params: *const PhiSamplingParams,
+    callback: PhiTokenCallback,
+    user_data: *mut c_void,
+) -> PhiStatus {
+    call(|| {
+        let model = model_arg(model)?;
+        let prompt = str_arg(prompt, "prompt")?;
+        let params = match params.as_ref() {
+            Some(params) => *params,
+            None => phi_sampling_params_default(),
+        };
+        let mut on_text = |text: &str| match callback {
+            Some(callback) => {
+                let text = CString::new(text.replace('\0', " ")).unwrap_or_default();
+                callback(text.as_ptr(), user_data) == 0
+            }
+            None => true,
+        };
+        model
+            .generate(prompt, &params, &mut on_text)
+            .map_err(|err| Error::new(PhiStatus::Inference, format!("{err:#}")))
+    })
+}
+
+/// Tokenizes `text` and writes the token ids to `ids`, which holds `capacity` entries. The number
+/// of tokens is always written to `len`; when it is larger than `capacity`, nothing is written to
+/// `ids` and `PHI_STATUS_BUFFER_TOO_SMALL` is returned, so `ids` may be null with a `capacity` of 0
+/// to query the length.
+///
+/// # Safety
+///
+/// `model` must be a live handle, `text` a null-terminated string, `ids` null or valid for
+/// `capacity` writes and `len` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn phi_tokenize(
+    model: *const PhiModel,
+    text: *const c_char,
+    ids: *mut u32,
+    capacity: usize,
+    len: *mut usize,
+) -> PhiStatus {
+    call(|| {
+        let model = model_arg(model)?;
+        let text = str_arg(text, "text")?;
+        if len.is_null() {
+            return Err(Error::new(PhiStatus::InvalidArgument, "len is null"));
+        }
+        let tokens = model
+            .tokenizer
+            .encode(text, true)
+            .map_err(|err| Error::new(PhiStatus::Inference, err))?;
+        let tokens = tokens.get_ids();
+        *len = tokens.len();
+        if tokens.len() > capacity {
+            return Err(Error::new(
+                PhiStatus::BufferTooSmall,
+                format!("{} tokens do not fit in {capacity} entries", tokens.len()),
+            ));
+        }
+        if !tokens.is_empty() {
+            if ids.is_null() {
+                return Err(Error::new(PhiStatus::InvalidArgument, "ids is null"));
+            }
+            std::ptr::copy_nonoverlapping(tokens.as_ptr(), ids, tokens.len());
+        }
+        Ok(())
+    })
+}
+
+/// Releases a handle returned by `phi_model_load`, null is ignored. No other call may use the
+/// handle during or after this call.
+///
+/// # Safety
+///
+/// `model` must be null or a handle returned by `phi_model_load` that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn phi_model_free(model: *mut PhiModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// Returns the message of the last failed call on the current thread, or an empty string. The
+/// pointer stays valid until the next failed call on the same thread.
+#[no_mangle]
+pub extern "C" fn phi_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handle_can_be_shared_between_threads() {
+        fn check<T: Send + Sync>() {}
+        check::<PhiModel>();
+    }
+
+    #[test]
+    fn errors_are_reported_per_thread() {
+        let mut model = std::ptr::null_mut();
+        let status = unsafe { phi_model_load(std::ptr::null(), &mut model) };
+        assert_eq!(status, PhiStatus::InvalidArgument);
+        assert!(model.is_null());
+        let message = unsafe { CStr::from_ptr(phi_last_error()) };
+        assert_eq!(message.to_str(), Ok("dir is null"));
+
+        let dir = CString::new("/nonexistent/phi-model").unwrap();
+        let status = unsafe { phi_model_load(dir.as_ptr(), &mut model) };
+        assert_eq!(status, PhiStatus::Load);
+        std::thread::spawn(|| {
+            let message = unsafe { CStr::from_ptr(phi_last_error()) };
+            assert!(message.to_bytes().is_empty());
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+// Modul untuk fine-tuning LoRA pada model Phi (format transformers) di CPU tanpa Python
+use anyhow::{Error as E, Result};
+use clap::Args as ClapArgs;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use candle_core::backprop::GradStore; // Untuk akumulasi gradien
+use candle_core::{DType, Device}; // Untuk operasi tensor
+use candle_nn::{AdamW, Optimizer, ParamsAdamW, VarBuilder, VarMap}; // Untuk parameter yang bisa dilatih
+use tokenizers::Tokenizer; // Untuk tokenisasi
+
+use crate::lora_phi::{Config, LoraPhi}; // Untuk model Phi dengan LoRA
+
+// Argumen untuk subcommand finetune
+#[derive(ClapArgs, Debug)]
+pub struct FinetuneArgs {
+    /// JSONL file with one `{"prompt": ..., "response": ...}` object per line.
+    #[arg(long)]
+    data: PathBuf,
+
+    /// The directory where the PEFT-compatible adapter is written.
+    #[arg(long)]
+    out_dir: PathBuf,
+
+    /// The rank of the LoRA matrices.
+    #[arg(long, default_value_t = 8)]
+    rank: usize,
+
+    /// The LoRA alpha, the update is scaled by alpha / rank.
+    #[arg(long, default_value_t = 16.)]
+    alpha: f64,
+
+    /// The modules receiving LoRA matrices, comma separated.
+    #[arg(long, default_value = "q_proj,k_proj,v_proj,dense")]
+    target_modules: String,
+
+    /// The AdamW learning rate.
+    #[arg(long, default_value_t = 2e-4)]
+    learning_rate: f64,
+
+    /// The AdamW weight decay.
+    #[arg(long, default_value_t = 0.0)]
+    weight_decay: f64,
+
+    /// The number of passes over the dataset.
+    #[arg(long, default_value_t = 1)]
+    epochs: usize,
+
+    /// The number of examples whose gradients are accumulated before each optimizer step.
+    #[arg(long, default_value_t = 8)]
+    grad_accum: usize,
+
+    /// Examples longer than this (in tokens) are truncated.
+    #[arg(long, default_value_t = 512)]
+    max_len: usize,
+
+    /// Write a checkpoint adapter every n optimizer steps, 0 disables checkpoints.
+    #[arg(long, default_value_t = 100)]
+    checkpoint_every: usize,
+
+    /// Start from the weights of a previously saved adapter directory (e.g. a checkpoint) instead
+    /// of a fresh LoRA initialization. This is not a true resume: the step count, the AdamW
+    /// moments start over, and the whole dataset is trained again.
+    /// The rank and target modules must match the adapter.
+    #[arg(long)]
+    init_adapter: Option<PathBuf>,
+}
+
+// Struct untuk satu contoh data pelatihan di file JSONL
+#[derive(Debug, Deserialize)]
+struct Example {
+    prompt: String,
+    response: String,
+}
+
+// Struct untuk contoh yang sudah ditokenisasi beserta mask loss-nya
+pub struct TrainItem {
+    pub input_ids: Vec<u32>,
+    pub targets: Vec<u32>,
+    pub mask: Vec<f32>,
+}
+
+// Fungsi untuk membuat item training dari token prompt dan response, loss hanya dihitung pada response
+pub fn train_item(prompt: &[u32], response: &[u32], max_len: usize) -> Option<TrainItem> {
+    let mut tokens = prompt.to_vec();
+    tokens.extend_from_slice(response);
+    tokens.truncate(max_len + 1);
+    if tokens.len() < 2 || prompt.len() >= tokens.len() {
+        return None;
+    }
+    let input_ids = tokens[..tokens.len() - 1].to_vec();
+    let targets = tokens[1..].to_vec();
+    // Posisi i memprediksi token i + 1, sehingga mask aktif jika token i + 1 berada di response
+    let mask = (0..targets.len())
+        .map(|i| if i + 1 >= prompt.len() { 1. } else { 0. })
+        .collect();
+    Some(TrainItem {
+        input_ids,
+        targets,
+        mask,
+    })
+}
+
+// Fungsi untuk membaca dan mentokenisasi dataset JSONL
+fn load_dataset(
+    path: &Path,
+    tokenizer: &Tokenizer,
+    eos_token: u32,
+    max_len: usize,
+) -> Result<Vec<TrainItem>> {
+    let mut items = vec![];
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let example: Example = serde_json::from_str(line)
+            .map_err(|err| anyhow::anyhow!("{path:?} line {}: {err}", index + 1))?;
+        let prompt = tokenizer.encode(example.prompt, true).map_err(E::msg)?;
+        let response = tokenizer.encode(example.response, false).map_err(E::msg)?;
+        let mut response = response.get_ids().to_vec();
+        response.push(eos_token);
+        match train_item(prompt.get_ids(), &response, max_len) {
+            Some(item) => items.push(item),
+            None => println!(
+                "skipping line {}, the prompt fills the whole context",
+                index + 1
+            ),
+        }
+    }
+    Ok(items)
+}
+
+// Fungsi untuk menambahkan gradien baru ke akumulator gradien
+fn accumulate(
+    acc: &mut Option<GradStore>,
+    grads: GradStore,
+    vars: &[candle_core::Var],
+) -> Result<()> {
+    match acc {
+        None => *acc = Some(grads),
+        Some(acc) => {
+            for var in vars.iter() {
+                if let Some(grad) = grads.get(var) {
+                    let sum = match acc.get(var) {
+                        Some(prev) => (prev + grad)?,
+                        None => grad.clone(),
+                    };
+                    acc.insert(var, sum);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Fungsi untuk menyimpan adapter dalam format PEFT (adapter_model.safetensors dan adapter_config.json)
+fn save_adapter(
+    varmap: &VarMap,
+    dir: &Path,
+    args: &FinetuneArgs,
+    targets: &[String],
+) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    varmap.save(dir.join("adapter_model.safetensors"))?;
+    let config = serde_json::json!({
+        "peft_type": "LORA",
+        "task_type": "CAUSAL_LM",
+        "r": args.rank,
+        "lora_alpha": args.alpha,
+        "lora_dropout": 0.0,
+        "target_modules": targets,
+        "bias": "none",
+        "fan_in_fan_out": false,
+        "inference_mode": true,
+    });
+    std::fs::write(
+        dir.join("adapter_config.json"),
+        serde_json::to_string_pretty(&config)?,
+    )?;
+    println!("saved the adapter to {dir:?}");
+    Ok(())
+}
+
+// Fungsi utama subcommand finetune
+pub fn run(
+    args: FinetuneArgs,
+    filenames: &[PathBuf],
+    config: &Config,
+    tokenizer: &Tokenizer,
+    device: &Device,
+) -> Result<()> {
+    let eos_token = match tokenizer.get_vocab(true).get("<|endoftext|>") {
+        Some(token) => *token,
+        None => anyhow::bail!("cannot find the endoftext token"),
+    };
+    let items = load_dataset(&args.data, tokenizer, eos_token, args.max_len)?;
+    if items.is_empty() {
+        anyhow::bail!("no training examples in {:?}", args.data)
+    }
+    println!("loaded {} training examples", items.len());
+
+    // Bobot dasar dimuat dalam f32 dan tidak ikut dilatih, hanya matriks LoRA di VarMap yang dilatih
+    let targets = args
+        .target_modules
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .collect::<Vec<_>>();
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(filenames, DType::F32, device)? };
+    let mut varmap = VarMap::new();
+    let lora_vb = VarBuilder::from_varmap(&varmap, DType::F32, device);
+    let model = LoraPhi::new(config, vb, lora_vb, args.rank, args.alpha, &targets)?;
+    // Hanya bobot adapter yang dimuat, state optimizer (momen AdamW) dan jumlah langkah dimulai dari awal
+    if let Some(dir) = args.init_adapter.as_ref() {
+        varmap.load(dir.join("adapter_model.safetensors"))?;
+        println!("initialized the adapter from {dir:?}, the optimizer state starts over");
+    }
+    let vars = varmap.all_vars();
+    let params = ParamsAdamW {
+        lr: args.learning_rate,
+        weight_decay: args.weight_decay,
+        ..Default::default()
+    };
+    let mut optimizer = AdamW::new(vars.clone(), params)?;
+
+    // Loop training dengan akumulasi gradien
+    let start = std::time::Instant::now();
+    let mut step = 0usize;
+    let mut acc_grads = None;
+    let (mut acc_count, mut acc_loss) = (0usize, 0f32);
+    let total = items.len() * args.epochs;
+    for (index, item) in (0..args.epochs).flat_map(|_| items.iter()).enumerate() {
+        let loss = model.loss(&item.input_ids, &item.targets, &item.mask)?;
+        acc_loss += loss.to_scalar::<f32>()?;
+        accumulate(&mut acc_grads, loss.backward()?, &vars)?;
+        acc_count += 1;
+        if acc_count < args.grad_accum && index + 1 < total {
+            continue;
+        }
+        // Rata-rata gradien lalu jalankan satu langkah optimizer
+        let mut grads = acc_grads.take().expect("gradients were accumulated");
+        for var in vars.iter() {
+            if let Some(grad) = grads.remove(var) {
+                grads.insert(var, (grad / acc_count as f64)?);
+            }
+        }
+        optimizer.step(&grads)?;
+        step += 1;
+        println!(
+            "step {step} ({}/{total} examples): loss {:.4} in {:?}",
+            index + 1,
+            acc_loss / acc_count as f32,
+            start.elapsed()
+        );
+        (acc_count, acc_loss) = (0, 0.);
+        if args.checkpoint_every > 0 && step.is_multiple_of(args.checkpoint_every) {
+            let dir = args.out_dir.join(format!("checkpoint-{step}"));
+            save_adapter(&varmap, &dir, &args, &targets)?;
+        }
+    }
+    save_adapter(&varmap, &args.out_dir, &args, &targets)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Konfigurasi Phi kecil dengan bobot acak agar test bisa berjalan cepat tanpa unduhan
+    fn tiny_config() -> Config {
+        Config {
+            vocab_size: 32,
+            hidden_size: 16,
+            intermediate_size: 32,
+            num_hidden_layers: 2,
+            num_attention_heads: 2,
+            num_key_value_heads: None,
+            layer_norm_eps: 1e-5,
+            rope_theta: 10000.,
+            partial_rotary_factor: 0.5,
+            qk_layernorm: false,
+        }
+    }
+
+    #[test]
+    fn prompt_tokens_are_masked() {
+        let item = train_item(&[1, 2, 3], &[4, 5], 16).unwrap();
+        assert_eq!(item.input_ids, [1, 2, 3, 4]);
+        assert_eq!(item.targets, [2, 3, 4, 5]);
+        assert_eq!(item.mask, [0., 0., 1., 1.]);
+    }
+
+    #[test]
+    fn loss_decreases_on_single_example() -> Result<()> {
+        let device = Device::Cpu;
+        let cfg = tiny_config();
+        let targets = [
+            "q_proj".to_string(),
+            "v_proj".to_string(),
+            "fc1".to_string(),
+        ];
+        let base_map = VarMap::new();
+        let base_vb = VarBuilder::from_varmap(&base_map, DType::F32, &device);
+        let varmap = VarMap::new();
+        let lora_vb = VarBuilder::from_varmap(&varmap, DType::F32, &device);
+        let model = LoraPhi::new(&cfg, base_vb, lora_vb, 4, 8., &targets)?;
+        assert_eq!(varmap.all_vars().len(), 2 * 2 * targets.len());
+
+        let item = train_item(&[1, 2, 3, 4], &[5, 6, 7, 8], 16).unwrap();
+        let mut optimizer = AdamW::new_lr(varmap.all_vars(), 1e-2)?;
+        let first = model.loss(&item.input_ids, &item.targets, &item.mask)?;
+        let first_value = first.to_scalar::<f32>()?;
+        optimizer.backward_step(&first)?;
+        for _ in 0..10 {
+            let loss = model.loss(&item.input_ids, &item.targets, &item.mask)?;
+            optimizer.backward_step(&loss)?;
+        }
+        let last = model.loss(&item.input_ids, &item.targets, &item.mask)?;
+        assert!(last.to_scalar::<f32>()? < first_value);
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+// Test golden offline dengan model Phi dan MixFormer acak kecil: bobot dibuat dari seed tetap, ditulis ke safetensors
+// (dan ke GGUF lewat subcommand quantize) lalu dimuat lewat jalur yang sama dengan CLI. Output dibandingkan dengan
+// tests/golden, lihat modul fixtures
+use super::*;
+use crate::fixtures::{self, TempDir};
+use candle_nn::VarMap;
+use serde_json::{json, Value};
+
+// Direktori file golden crate ini, fixtures ada di crate common sehingga tidak bisa memakai CARGO_MANIFEST_DIR sendiri
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const VOCAB_SIZE: usize = 64;
+const PROMPT: &str = "A B w10 w11 w12";
+// Seed bobot acak fixture Phi, dipilih agar generasi greedy dari PROMPT tidak langsung berulang
+const SEED: u64 = 45;
+
+// Config Phi dengan field candle phi dan lora_phi, dimensi kelipatan 32 agar bisa dikuantisasi q8_0
+const PHI_CONFIG: &str = r#"{
+    "vocab_size": 64,
+    "hidden_size": 32,
+    "intermediate_size": 64,
+    "num_hidden_layers": 2,
+    "num_attention_heads": 4,
+    "num_key_value_heads": null,
+    "hidden_act": "gelu_new",
+    "max_position_embeddings": 64,
+    "layer_norm_eps": 1e-5,
+    "tie_word_embeddings": false,
+    "rope_theta": 10000.0,
+    "partial_rotary_factor": 0.5,
+    "qk_layernorm": false
+}"#;
+
+// Config MixFormer dengan dimensi yang sama (rotary_dim = partial_rotary_factor * ukuran head)
+const MIXFORMER_CONFIG: &str = r#"{
+    "vocab_size": 64,
+    "n_positions": 64,
+    "n_embd": 32,
+    "n_layer": 2,
+    "n_inner": 64,
+    "n_head": 4,
+    "rotary_dim": 4,
+    "activation_function": "gelu_new",
+    "layer_norm_epsilon": 1e-5,
+    "tie_word_embeddings": false,
+    "pad_vocab_size_multiple": 64
+}"#;
+
+// Struct untuk fixture Phi di direktori sementara: config.json, tokenizer.json dan model.safetensors
+struct PhiFixture {
+    dir: TempDir,
+    tokenizer: Tokenizer,
+}
+
+impl PhiFixture {
+    // Menulis fixture dengan bobot acak dari seed tetap, update dipanggil sebelum bobot ditulis
+    fn new(name: &str, update: impl FnOnce(&VarMap) -> Result<()>) -> Result<Self> {
+        let dir = TempDir::new(name)?;
+        std::fs::write(dir.join("config.json"), PHI_CONFIG)?;
+        let tokenizer =
+            fixtures::tokenizer(&dir.join("tokenizer.json"), &["<|endoftext|>"], VOCAB_SIZE)?;
+        let config: lora_phi::Config = serde_json::from_str(PHI_CONFIG)?;
+        let varmap = VarMap::new();
+        lora_phi::LoraPhi::load(
+            &config,
+            VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu),
+        )?;
+        fixtures::randomize(&varmap, SEED)?;
+        update(&varmap)?;
+        varmap.save(dir.join("model.safetensors"))?;
+        Ok(Self { dir, tokenizer })
+    }
+
+    fn vb(&self) -> Result<VarBuilder<'static>> {
+        let filenames = [self.dir.join("model.safetensors")];
+        Ok(unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &Device::Cpu)? })
+    }
+
+    fn phi(&self) -> Result<Model> {
+        let config: PhiConfig = serde_json::from_str(PHI_CONFIG)?;
+        Ok(Model::Phi(Phi::new(&config, self.vb()?)?))
+    }
+
+    fn cached(&self) -> Result<Model> {
+        let config: lora_phi::Config = serde_json::from_str(PHI_CONFIG)?;
+        let phi = lora_phi::LoraPhi::load(&config, self.vb()?)?;
+        Ok(Model::Cached(phi, lora_phi::KvCache::default()))
+    }
+
+    // Menjalankan subcommand quantize lewat parser argumen CLI lalu memuat GGUF-nya sebagai QMixFormer v2
+    fn quantized(&self, quantization: &str) -> Result<Model> {
+        let weights = self.dir.join("model.safetensors");
+        let out = self.dir.join(format!("model-{quantization}.gguf"));
+        let (weights_str, out_str) = (weights.to_string_lossy(), out.to_string_lossy());
+        let args = Args::try_parse_from([
+            "phi",
+            "--model",
+            "2",
+            "quantize",
+            "--weight-files",
+            &*weights_str,
+            "--out-file",
+            &*out_str,
+            "--quantization",
+            quantization,
+        ])?;
+        match args.command {
+            Some(Command::Quantize(quantize_args)) => quantize::run(quantize_args, true)?,
+            command => anyhow::bail!("unexpected command {command:?}"),
+        }
+        let config: Config = serde_json::from_str(MIXFORMER_CONFIG)?;
+        let vb =
+            candle_transformers::quantized_var_builder::VarBuilder::from_gguf(&out, &Device::Cpu)?;
+        Ok(Model::Quantized(QMixFormer::new_v2(&config, vb)?))
+    }
+}
+
+// Fungsi untuk membuat pipeline greedy (tanpa temperature) dengan repeat penalty yang diberikan
+fn pipeline(
+    model: Model,
+    tokenizer: &Tokenizer,
+    repeat_penalty: f32,
+    repeat_last_n: usize,
+) -> TextGeneration {
+    let context = context::ContextWindow {
+        size: 64,
+        truncate: context::Truncate::Error,
+        overflow: context::Overflow::Stop,
+    };
+    TextGeneration::new(
+        model,
+        tokenizer.clone(),
+        0,
+        None,
+        None,
+        repeat_penalty,
+        repeat_last_n,
+        false,
+        context,
+        &Device::Cpu,
+    )
+}
+
+// Fungsi untuk menjalankan generasi dari PROMPT, hasilnya (teks, jumlah token, alasan berhenti) dalam bentuk JSON.
+// Setiap kata tokenizer fixture adalah satu token, sehingga teksnya adalah urutan token yang dihasilkan
+fn generate(pipeline: &mut TextGeneration, sample_len: usize) -> Result<Value> {
+    let tokens = pipeline.tokenizer.encode(PROMPT, true).map_err(E::msg)?;
+    let budget = budget::Budget::new(None, None);
+    let generation =
+        pipeline.generate_tokens(tokens.get_ids().to_vec(), sample_len, &budget, &mut |_| {
+            Ok(())
+        })?;
+    Ok(json!({
+        "prompt": PROMPT,
+        "text": generation.text,
+        "generated_tokens": generation.generated_tokens,
+        "finish_reason": generation.finish_reason.to_string(),
+    }))
+}
+
+// Fungsi untuk mengambil logits token terakhir dari satu forward PROMPT
+fn last_logits(mut model: Model, tokenizer: &Tokenizer) -> Result<Vec<f32>> {
+    let tokens = tokenizer.encode(PROMPT, true).map_err(E::msg)?;
+    let input = Tensor::new(tokens.get_ids(), &Device::Cpu)?.unsqueeze(0)?;
+    Ok(model.forward(&input)?.squeeze(0)?.to_vec1()?)
+}
+
+#[test]
+fn greedy_generation_matches_golden() -> Result<()> {
+    let fixture = PhiFixture::new("greedy", |_| Ok(()))?;
+    let phi = generate(
+        &mut pipeline(fixture.phi()?, &fixture.tokenizer, 1., 64),
+        16,
+    )?;
+    // Model Phi dengan kv cache yang bisa dipotong harus menghasilkan token yang sama dengan model Phi candle
+    let cached = generate(
+        &mut pipeline(fixture.cached()?, &fixture.tokenizer, 1., 64),
+        16,
+    )?;
+    assert_eq!(phi, cached);
+    fixtures::assert_golden(GOLDEN_DIR, "phi_greedy", &phi)
+}
+
+#[test]
+fn repeat_penalty_matches_golden() -> Result<()> {
+    let fixture = PhiFixture::new("repeat-penalty", |_| Ok(()))?;
+    let plain = generate(
+        &mut pipeline(fixture.phi()?, &fixture.tokenizer, 1., 64),
+        16,
+    )?;
+    // Tanpa token di jendela repeat_last_n, penalty tidak mengubah logits
+    let empty_window = generate(&mut pipeline(fixture.phi()?, &fixture.tokenizer, 4., 0), 16)?;
+    assert_eq!(plain, empty_window);
+    let penalized = generate(
+        &mut pipeline(fixture.phi()?, &fixture.tokenizer, 4., 64),
+        16,
+    )?;
+    fixtures::assert_golden(GOLDEN_DIR, "phi_repeat_penalty", &penalized)
+}
+
+#[test]
+fn eos_stops_generation() -> Result<()> {
+    // Bias lm_head yang besar pada token eos (id 0) membuat token pertama selalu eos
+    let fixture = PhiFixture::new("eos", |varmap| {
+        fixtures::update(varmap, "lm_head.bias", |bias| bias[0] = 100.)
+    })?;
+    for model in [
+        fixture.phi()?,
+        fixture.cached()?,
+        fixture.quantized("q8_0")?,
+    ] {
+        let mut generation_pipeline = pipeline(model, &fixture.tokenizer, 1., 64);
+        let tokens = fixture.tokenizer.encode(PROMPT, true).map_err(E::msg)?;
+        let budget = budget::Budget::new(None, None);
+        let generation = generation_pipeline.generate_tokens(
+            tokens.get_ids().to_vec(),
+            16,
+            &budget,
+            &mut |_| Ok(()),
+        )?;
+        assert_eq!(generation.finish_reason, budget::FinishReason::Eos);
+        assert_eq!(generation.generated_tokens, 1);
+        assert_eq!(generation.text, "");
+    }
+    Ok(())
+}
+
+#[test]
+fn quantized_gguf_matches_golden() -> Result<()> {
+    let fixture = PhiFixture::new("quantized", |_| Ok(()))?;
+    // GGUF f32 berisi bobot yang sama dengan nama MixFormer, sehingga logits-nya harus sama dengan model Phi
+    let expected = last_logits(fixture.phi()?, &fixture.tokenizer)?;
+    let actual = last_logits(fixture.quantized("f32")?, &fixture.tokenizer)?;
+    let max_diff = expected
+        .iter()
+        .zip(actual.iter())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0f32, f32::max);
+    assert!(max_diff < 1e-3, "max logit difference {max_diff}");
+    let q8_0 = generate(
+        &mut pipeline(fixture.quantized("q8_0")?, &fixture.tokenizer, 1., 64),
+        16,
+    )?;
+    fixtures::assert_golden(GOLDEN_DIR, "phi_q8_0", &q8_0)
+}
+
+#[test]
+fn mixformer_greedy_generation_matches_golden() -> Result<()> {
+    let dir = TempDir::new("mixformer")?;
+    let tokenizer =
+        fixtures::tokenizer(&dir.join("tokenizer.json"), &["<|endoftext|>"], VOCAB_SIZE)?;
+    let config: Config = serde_json::from_str(MIXFORMER_CONFIG)?;
+    let varmap = VarMap::new();
+    MixFormer::new_v2(
+        &config,
+        VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu),
+    )?;
+    fixtures::randomize(&varmap, 42)?;
+    varmap.save(dir.join("model.safetensors"))?;
+    let filenames = [dir.join("model.safetensors")];
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &Device::Cpu)? };
+    let model = Model::MixFormer(MixFormer::new_v2(&config, vb)?);
+    let generation = generate(&mut pipeline(model, &tokenizer, 1., 64), 16)?;
+    fixtures::assert_golden(GOLDEN_DIR, "mixformer_greedy", &generation)
+}
+
+#[test]
+fn score_matches_full_forward() -> Result<()> {
+    use crate::worker::Backend;
+
+    let fixture = PhiFixture::new("score", |_| Ok(()))?;
+    let config: lora_phi::Config = serde_json::from_str(PHI_CONFIG)?;
+    let reference = lora_phi::LoraPhi::load(&config, fixture.vb()?)?;
+    let mut phi = pipeline(fixture.phi()?, &fixture.tokenizer, 1., 64);
+    let mut cached = pipeline(fixture.cached()?, &fixture.tokenizer, 1., 64);
+    // Dua continuation berturut-turut agar prefix cache dari continuation pertama ikut diuji
+    for continuation in [" w13 w14 w15", " w20"] {
+        let prompt = fixture.tokenizer.encode(PROMPT, true).map_err(E::msg)?;
+        let text = format!("{PROMPT}{continuation}");
+        let tokens = fixture.tokenizer.encode(text, true).map_err(E::msg)?;
+        let (prompt, tokens) = (prompt.get_ids(), tokens.get_ids());
+        // Log-probabilitas dari satu forward seluruh urutan, logits posisi i memprediksi token i + 1
+        let input = Tensor::new(tokens, &Device::Cpu)?.unsqueeze(0)?;
+        let log_probs =
+            candle_nn::ops::log_softmax(&reference.forward(&input)?.squeeze(0)?, D::Minus1)?;
+        let mut expected = 0f64;
+        for (end, token) in tokens.iter().enumerate().skip(prompt.len()) {
+            expected += log_probs
+                .get(end - 1)?
+                .get(*token as usize)?
+                .to_scalar::<f32>()? as f64;
+        }
+        for backend in [&mut phi, &mut cached] {
+            let (logprob, count) = backend.score(PROMPT, continuation)?;
+            assert_eq!(count, tokens.len() - prompt.len());
+            assert!((logprob - expected).abs() < 1e-3, "{logprob} vs {expected}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn mmlu_scoring_matches_golden() -> Result<()> {
+    let fixture = PhiFixture::new("mmlu", |_| Ok(()))?;
+    let test_dir = fixture.dir.join("data/test");
+    let dev_dir = fixture.dir.join("data/dev");
+    std::fs::create_dir_all(&test_dir)?;
+    std::fs::create_dir_all(&dev_dir)?;
+    std::fs::write(
+        test_dir.join("fixture_test.csv"),
+        "w10 w11 w12,w13,w14,w15,w16,A\nw20 w21,w22,w23,w24,w25,C\nw30 w31 w32,w34,w35,w36,w37,D\n",
+    )?;
+    std::fs::write(
+        dev_dir.join("fixture_dev.csv"),
+        "w40 w41,w42,w43,w44,w45,B\n",
+    )?;
+    let reliability = fixture.dir.join("reliability.csv");
+    let options = calibration::Options {
+        bins: 4,
+        thresholds: vec![0.3, 0.9],
+        reliability: reliability.to_str().map(String::from),
+        temperature_scaling: true,
+    };
+    let run = |batch_size: usize, journal: journal::Journal| -> Result<Vec<Value>> {
+        let output = fixture.dir.join(format!("mmlu-{batch_size}.jsonl"));
+        let tokenizer = fixture.tokenizer.clone();
+        mmlu(
+            fixture.cached()?,
+            tokenizer,
+            &Device::Cpu,
+            &test_dir,
+            1,
+            indommlu::PromptTemplate::English,
+            batch_size,
+            output.to_str(),
+            journal,
+            &options,
+        )?;
+        let results = std::fs::read_to_string(&output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        Ok(results)
+    };
+    // Logits dipisah dari hasil lainnya: padding kiri pada batch bisa mengubah logits sedikit
+    let split = |results: &[Value]| -> (Vec<Value>, Vec<f64>) {
+        let mut logits = vec![];
+        let mut rest = vec![];
+        for result in results.iter() {
+            let mut result = result.clone();
+            if let Some(values) = result.as_object_mut().and_then(|r| r.remove("logits")) {
+                logits.extend(
+                    values
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| v.as_f64()),
+                );
+            }
+            rest.push(result);
+        }
+        (rest, logits)
+    };
+    let no_journal = || journal::Journal::open(None, false, String::new());
+    let results = run(1, no_journal()?)?;
+    assert_eq!(results.len(), 3);
+    let (answers, logits) = split(&results);
+    assert_eq!(logits.len(), 12);
+    // Reliability diagram berisi satu baris per bin untuk temperature 1 dan temperature hasil fit
+    assert_eq!(
+        std::fs::read_to_string(&reliability)?.lines().count(),
+        1 + 2 * 4
+    );
+
+    // Soal yang diproses per batch dengan padding kiri harus mendapat jawaban yang sama
+    let (batched_answers, batched_logits) = split(&run(2, no_journal()?)?);
+    assert_eq!(answers, batched_answers);
+    for (a, b) in logits.iter().zip(batched_logits.iter()) {
+        assert!((a - b).abs() < 1e-3, "logit {a} vs {b}");
+    }
+
+    // Run yang terputus setelah dua soal dilanjutkan dengan --resume: hanya soal ketiga yang dinilai
+    let path = fixture.dir.join("journal.jsonl");
+    run(
+        1,
+        journal::Journal::open(Some(&path), false, "model".to_string())?,
+    )?;
+    let recorded = std::fs::read_to_string(&path)?;
+    let first_two = recorded.lines().take(2).collect::<Vec<_>>().join("\n");
+    std::fs::write(&path, first_two)?;
+    let resumed = journal::Journal::open(Some(&path), true, "model".to_string())?;
+    assert_eq!(
+        resumed.summary().as_deref(),
+        Some("resuming 2 results from the journal")
+    );
+    let (resumed_answers, resumed_logits) = split(&run(2, resumed)?);
+    assert_eq!(answers, resumed_answers);
+    // Dua soal pertama diambil dari journal apa adanya
+    assert_eq!(logits[..8], resumed_logits[..8]);
+    assert!((logits[8] - resumed_logits[8]).abs() < 1e-3);
+    assert_eq!(std::fs::read_to_string(&path)?.lines().count(), 3);
+    // Model acak memilih huruf yang sama untuk setiap soal, sehingga golden juga menyimpan log-probabilitas keempat
+    // pilihan (dibulatkan) agar perubahan skor per soal ikut terdeteksi
+    let golden = answers
+        .into_iter()
+        .zip(logits.chunks(4))
+        .map(|(mut answer, logits)| {
+            let max = logits.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let log_sum = logits.iter().map(|l| (l - max).exp()).sum::<f64>().ln() + max;
+            let log_probs = logits
+                .iter()
+                .map(|l| ((l - log_sum) * 1000.).round() / 1000.)
+                .collect::<Vec<_>>();
+            answer["log_probs"] = json!(log_probs);
+            answer
+        })
+        .collect();
+    fixtures::assert_golden(GOLDEN_DIR, "phi_mmlu", &Value::Array(golden))
+}
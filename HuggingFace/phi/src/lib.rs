@@ -0,0 +1,4 @@
+// Library crate phi: model Phi dengan LoRA dan kv cache, detokenisasi bertahap, dan C ABI (cdylib) untuk aplikasi C dan C++
+pub mod ffi; // Untuk C ABI, header ada di include/phi.h
+pub mod lora_phi; // Untuk model Phi dengan LoRA dan kv cache yang bisa dipotong
+pub mod token_stream; // Untuk detokenisasi bertahap tokenizer Hugging Face dengan common::token_stream
@@ -0,0 +1,579 @@
+// Modul untuk model Phi (format transformers) dengan LoRA opsional dan kv cache eksternal yang bisa dipotong,
+// dipakai untuk training LoRA dan untuk memakai ulang kv cache dari prefix prompt yang sama
+use anyhow::Result;
+use serde::Deserialize;
+
+use candle_core::{DType, Module, Tensor, D}; // Untuk operasi tensor
+use candle_nn::{Init, VarBuilder}; // Untuk memuat bobot dan parameter LoRA
+
+// Struct untuk konfigurasi Phi yang dibaca dari config.json (field di candle tidak bisa diakses dari luar)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    pub vocab_size: usize,
+    pub hidden_size: usize,
+    pub intermediate_size: usize,
+    pub num_hidden_layers: usize,
+    pub num_attention_heads: usize,
+    pub num_key_value_heads: Option<usize>,
+    pub layer_norm_eps: f64,
+    pub rope_theta: f32,
+    pub partial_rotary_factor: f64,
+    #[serde(default)]
+    pub qk_layernorm: bool,
+}
+
+// Struct untuk layer linear beku yang bisa ditambah matriks LoRA (A dan B) yang dilatih
+struct LoraLinear {
+    base: candle_nn::Linear,
+    lora: Option<(Tensor, Tensor)>,
+    scaling: f64,
+}
+
+impl LoraLinear {
+    fn new(
+        in_dim: usize,
+        out_dim: usize,
+        vb: VarBuilder,
+        lora_vb: Option<VarBuilder>,
+        rank: usize,
+        scaling: f64,
+    ) -> Result<Self> {
+        let weight = vb.get_with_hints(
+            (out_dim, in_dim),
+            "weight",
+            Init::Randn {
+                mean: 0.,
+                stdev: 0.02,
+            },
+        )?;
+        let bias = vb.get_with_hints(out_dim, "bias", Init::Const(0.))?;
+        let base = candle_nn::Linear::new(weight, Some(bias));
+        // Matriks A diinisialisasi kaiming dan B dengan nol sehingga model awal sama dengan model dasar
+        let lora = match lora_vb {
+            None => None,
+            Some(vb) => {
+                let a = vb.get_with_hints(
+                    (rank, in_dim),
+                    "lora_A.weight",
+                    candle_nn::init::DEFAULT_KAIMING_UNIFORM,
+                )?;
+                let b = vb.get_with_hints((out_dim, rank), "lora_B.weight", Init::Const(0.))?;
+                Some((a, b))
+            }
+        };
+        Ok(Self {
+            base,
+            lora,
+            scaling,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let ys = self.base.forward(xs)?;
+        match &self.lora {
+            None => Ok(ys),
+            Some((a, b)) => {
+                let delta = xs.broadcast_matmul(&a.t()?)?.broadcast_matmul(&b.t()?)?;
+                Ok((ys + (delta * self.scaling)?)?)
+            }
+        }
+    }
+}
+
+// Struct untuk layer norm yang dihitung dengan operasi dasar agar gradien bisa mengalir
+struct LayerNorm {
+    weight: Tensor,
+    bias: Tensor,
+    eps: f64,
+}
+
+impl LayerNorm {
+    fn new(size: usize, eps: f64, vb: VarBuilder) -> Result<Self> {
+        let weight = vb.get_with_hints(size, "weight", Init::Const(1.))?;
+        let bias = vb.get_with_hints(size, "bias", Init::Const(0.))?;
+        Ok(Self { weight, bias, eps })
+    }
+
+    // Dihitung dalam f32 agar tetap stabil saat bobot dimuat dalam f16 atau bf16
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let dtype = xs.dtype();
+        let xs = xs.to_dtype(DType::F32)?;
+        let mean = xs.mean_keepdim(D::Minus1)?;
+        let xs = xs.broadcast_sub(&mean)?;
+        let var = xs.sqr()?.mean_keepdim(D::Minus1)?;
+        let xs = xs.broadcast_div(&(var + self.eps)?.sqrt()?)?;
+        let xs = xs.to_dtype(dtype)?;
+        Ok(xs.broadcast_mul(&self.weight)?.broadcast_add(&self.bias)?)
+    }
+}
+
+// Struct untuk satu blok Phi (attention dan MLP paralel)
+struct Block {
+    input_layernorm: LayerNorm,
+    q_proj: LoraLinear,
+    k_proj: LoraLinear,
+    v_proj: LoraLinear,
+    dense: LoraLinear,
+    fc1: LoraLinear,
+    fc2: LoraLinear,
+}
+
+// Struct untuk kv cache per layer dengan bentuk (b, heads, seq, head_dim), disimpan di luar model
+// sehingga bisa diklon dan dipotong ke panjang prefix tertentu
+#[derive(Debug, Clone, Default)]
+pub struct KvCache {
+    layers: Vec<(Tensor, Tensor)>,
+}
+
+impl KvCache {
+    // Jumlah token yang tersimpan di cache
+    pub fn len(&self) -> usize {
+        match self.layers.first() {
+            Some((k, _)) => k.dims().get(2).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.layers.clear()
+    }
+
+    // Mengulang cache dengan batch 1 menjadi batch size baris, dipakai untuk prefix yang sama di semua baris
+    pub fn repeat(&self, size: usize) -> Result<Self> {
+        let layers = self
+            .layers
+            .iter()
+            .map(|(k, v)| Ok((k.repeat((size, 1, 1, 1))?, v.repeat((size, 1, 1, 1))?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { layers })
+    }
+
+    // Memotong cache sehingga hanya len token pertama yang tersisa
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        if len == 0 {
+            self.clear();
+            return Ok(());
+        }
+        for (k, v) in self.layers.iter_mut() {
+            *k = k.narrow(2, 0, len)?.contiguous()?;
+            *v = v.narrow(2, 0, len)?.contiguous()?;
+        }
+        Ok(())
+    }
+}
+
+// Struct untuk model Phi dengan LoRA opsional, tanpa LoRA model ini sama dengan model Phi dasar
+pub struct LoraPhi {
+    embed_tokens: Tensor,
+    blocks: Vec<Block>,
+    final_layernorm: LayerNorm,
+    lm_head: LoraLinear,
+    num_heads: usize,
+    head_dim: usize,
+    rotary_dim: usize,
+    rope_theta: f32,
+}
+
+// Fungsi untuk memutar setengah dimensi terakhir (dipakai pada rotary embedding)
+fn rotate_half(xs: &Tensor) -> Result<Tensor> {
+    let half = xs.dim(D::Minus1)? / 2;
+    let x1 = xs.narrow(D::Minus1, 0, half)?;
+    let x2 = xs.narrow(D::Minus1, half, half)?;
+    Ok(Tensor::cat(&[&x2.neg()?, &x1], D::Minus1)?)
+}
+
+impl LoraPhi {
+    // Membuat model, vb berisi bobot dasar (beku) dan lora_vb berisi matriks LoRA dari VarMap
+    pub fn new(
+        cfg: &Config,
+        vb: VarBuilder,
+        lora_vb: VarBuilder,
+        rank: usize,
+        alpha: f64,
+        target_modules: &[String],
+    ) -> Result<Self> {
+        if cfg.qk_layernorm {
+            anyhow::bail!("qk_layernorm is not supported by finetune")
+        }
+        if cfg.num_key_value_heads.unwrap_or(cfg.num_attention_heads) != cfg.num_attention_heads {
+            anyhow::bail!("grouped query attention is not supported by finetune")
+        }
+        let scaling = alpha / rank as f64;
+        let head_dim = cfg.hidden_size / cfg.num_attention_heads;
+        let vb_m = vb.pp("model");
+        let lora_m = lora_vb.pp("base_model.model.model");
+        let embed_tokens = vb_m.pp("embed_tokens").get_with_hints(
+            (cfg.vocab_size, cfg.hidden_size),
+            "weight",
+            Init::Randn {
+                mean: 0.,
+                stdev: 0.02,
+            },
+        )?;
+        let mut blocks = Vec::with_capacity(cfg.num_hidden_layers);
+        for i in 0..cfg.num_hidden_layers {
+            let vb_l = vb_m.pp("layers").pp(i);
+            let lora_l = lora_m.pp("layers").pp(i);
+            // Fungsi untuk membuat linear dengan LoRA hanya jika modulnya termasuk target
+            let linear = |in_dim, out_dim, group: &str, name: &str| {
+                let lora_vb = target_modules
+                    .iter()
+                    .any(|m| m == name)
+                    .then(|| lora_l.pp(group).pp(name));
+                LoraLinear::new(
+                    in_dim,
+                    out_dim,
+                    vb_l.pp(group).pp(name),
+                    lora_vb,
+                    rank,
+                    scaling,
+                )
+            };
+            let (h, i_size) = (cfg.hidden_size, cfg.intermediate_size);
+            blocks.push(Block {
+                input_layernorm: LayerNorm::new(h, cfg.layer_norm_eps, vb_l.pp("input_layernorm"))?,
+                q_proj: linear(h, h, "self_attn", "q_proj")?,
+                k_proj: linear(h, h, "self_attn", "k_proj")?,
+                v_proj: linear(h, h, "self_attn", "v_proj")?,
+                dense: linear(h, h, "self_attn", "dense")?,
+                fc1: linear(h, i_size, "mlp", "fc1")?,
+                fc2: linear(i_size, h, "mlp", "fc2")?,
+            })
+        }
+        let final_layernorm = LayerNorm::new(
+            cfg.hidden_size,
+            cfg.layer_norm_eps,
+            vb_m.pp("final_layernorm"),
+        )?;
+        let lm_head = LoraLinear::new(
+            cfg.hidden_size,
+            cfg.vocab_size,
+            vb.pp("lm_head"),
+            None,
+            rank,
+            scaling,
+        )?;
+        Ok(Self {
+            embed_tokens,
+            blocks,
+            final_layernorm,
+            lm_head,
+            num_heads: cfg.num_attention_heads,
+            head_dim,
+            rotary_dim: (cfg.partial_rotary_factor * head_dim as f64) as usize,
+            rope_theta: cfg.rope_theta,
+        })
+    }
+
+    // Membuat model tanpa LoRA untuk inferensi
+    pub fn load(cfg: &Config, vb: VarBuilder) -> Result<Self> {
+        let lora_vb = VarBuilder::zeros(vb.dtype(), vb.device());
+        Self::new(cfg, vb, lora_vb, 1, 1., &[])
+    }
+
+    // Fungsi untuk menerapkan rotary embedding parsial pada q atau k dengan bentuk (b, heads, seq, head_dim)
+    fn apply_rotary(&self, xs: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<Tensor> {
+        let xs_rot = xs.narrow(D::Minus1, 0, self.rotary_dim)?;
+        let xs_pass = xs.narrow(D::Minus1, self.rotary_dim, self.head_dim - self.rotary_dim)?;
+        let xs_rot = (xs_rot.broadcast_mul(cos)? + rotate_half(&xs_rot)?.broadcast_mul(sin)?)?;
+        Ok(Tensor::cat(&[&xs_rot, &xs_pass], D::Minus1)?)
+    }
+
+    // Fungsi untuk menghitung hidden state (b, seq, hidden) dari input (b, seq) yang melanjutkan token di cache,
+    // key dan value dari input ditambahkan ke cache. pad berisi jumlah token padding di kiri setiap baris
+    // (dihitung dari awal cache), kosong jika tidak ada padding
+    fn hidden_states(
+        &self,
+        input_ids: &Tensor,
+        cache: &mut KvCache,
+        pad: &[usize],
+    ) -> Result<Tensor> {
+        let (b_size, seq_len) = input_ids.dims2()?;
+        let device = input_ids.device();
+        let offset = cache.len();
+        let total_len = offset + seq_len;
+        let pad = |row: usize| pad.get(row).copied().unwrap_or(0);
+        let mut xs = self
+            .embed_tokens
+            .index_select(&input_ids.flatten_all()?, 0)?
+            .reshape((b_size, seq_len, ()))?;
+        let dtype = xs.dtype();
+
+        // Menyiapkan cos dan sin untuk rotary embedding, posisi setiap baris dihitung setelah padding-nya
+        let inv_freq: Vec<f32> = (0..self.rotary_dim)
+            .step_by(2)
+            .map(|i| 1f32 / self.rope_theta.powf(i as f32 / self.rotary_dim as f32))
+            .collect();
+        let inv_freq = Tensor::new(inv_freq, device)?;
+        let positions: Vec<f32> = (0..b_size)
+            .flat_map(|row| (offset..total_len).map(move |col| col.saturating_sub(pad(row)) as f32))
+            .collect();
+        let positions = Tensor::from_slice(&positions, (b_size, seq_len, 1), device)?;
+        let freqs = positions.broadcast_mul(&inv_freq.reshape((1, 1, ()))?)?;
+        let freqs = Tensor::cat(&[&freqs, &freqs], D::Minus1)?.unsqueeze(1)?;
+        let (cos, sin) = (freqs.cos()?.to_dtype(dtype)?, freqs.sin()?.to_dtype(dtype)?);
+
+        // Mask kausal agar token tidak bisa melihat token setelahnya maupun padding, token padding hanya melihat
+        // dirinya sendiri agar softmax tidak menghasilkan NaN
+        let mask: Vec<f32> = (0..b_size)
+            .flat_map(|row| {
+                (offset..total_len).flat_map(move |query| {
+                    (0..total_len).map(move |key| {
+                        if key > query || (key < pad(row) && key != query) {
+                            f32::NEG_INFINITY
+                        } else {
+                            0.
+                        }
+                    })
+                })
+            })
+            .collect();
+        let mask = Tensor::from_slice(&mask, (b_size, 1, seq_len, total_len), device)?;
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let hs = block.input_layernorm.forward(&xs)?;
+            let shape = (b_size, seq_len, self.num_heads, self.head_dim);
+            let q = block.q_proj.forward(&hs)?.reshape(shape)?.transpose(1, 2)?;
+            let k = block.k_proj.forward(&hs)?.reshape(shape)?.transpose(1, 2)?;
+            let v = block.v_proj.forward(&hs)?.reshape(shape)?.transpose(1, 2)?;
+            let q = self.apply_rotary(&q, &cos, &sin)?;
+            let k = self.apply_rotary(&k, &cos, &sin)?;
+            let (k, v) = match cache.layers.get(index) {
+                Some((prev_k, prev_v)) => (
+                    Tensor::cat(&[prev_k, &k], 2)?,
+                    Tensor::cat(&[prev_v, &v], 2)?,
+                ),
+                None => (k, v),
+            };
+            if index < cache.layers.len() {
+                cache.layers[index] = (k.clone(), v.clone());
+            } else {
+                cache.layers.push((k.clone(), v.clone()));
+            }
+            let att =
+                (q.contiguous()?.matmul(&k.t()?.contiguous()?)? / (self.head_dim as f64).sqrt())?;
+            // Softmax dihitung dalam f32
+            let att = att.to_dtype(DType::F32)?.broadcast_add(&mask)?;
+            let att = candle_nn::ops::softmax(&att, D::Minus1)?.to_dtype(dtype)?;
+            let attn_output =
+                att.matmul(&v.contiguous()?)?
+                    .transpose(1, 2)?
+                    .reshape((b_size, seq_len, ()))?;
+            let attn_output = block.dense.forward(&attn_output)?;
+            let ff = block.fc2.forward(&block.fc1.forward(&hs)?.gelu()?)?;
+            xs = ((xs + attn_output)? + ff)?;
+        }
+        self.final_layernorm.forward(&xs)
+    }
+
+    // Fungsi forward untuk input dengan bentuk (b, seq), menghasilkan logits (b, seq, vocab)
+    pub fn forward(&self, input_ids: &Tensor) -> Result<Tensor> {
+        let xs = self.hidden_states(input_ids, &mut KvCache::default(), &[])?;
+        self.lm_head.forward(&xs)
+    }
+
+    // Fungsi forward yang melanjutkan cache, menghasilkan logits token terakhir saja (b, vocab)
+    pub fn forward_cached(&self, input_ids: &Tensor, cache: &mut KvCache) -> Result<Tensor> {
+        self.forward_padded(input_ids, cache, &[])
+    }
+
+    // Sama dengan forward_cached untuk batch dengan padding di kiri (pad token per baris)
+    pub fn forward_padded(
+        &self,
+        input_ids: &Tensor,
+        cache: &mut KvCache,
+        pad: &[usize],
+    ) -> Result<Tensor> {
+        let xs = self.hidden_states(input_ids, cache, pad)?;
+        let seq_len = xs.dim(1)?;
+        let xs = xs.narrow(1, seq_len - 1, 1)?.squeeze(1)?;
+        self.lm_head.forward(&xs)
+    }
+
+    // Fungsi forward untuk batch dengan padding di kanan, menghasilkan logits pada indeks last[row] setiap baris (b, vocab).
+    // Mask kausal sudah mencegah token asli melihat padding di kanannya
+    pub fn forward_at(
+        &self,
+        input_ids: &Tensor,
+        cache: &mut KvCache,
+        last: &[usize],
+    ) -> Result<Tensor> {
+        let xs = self.hidden_states(input_ids, cache, &[])?;
+        let rows = last
+            .iter()
+            .enumerate()
+            .map(|(row, &index)| xs.get(row)?.get(index))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        self.lm_head.forward(&Tensor::stack(&rows, 0)?)
+    }
+
+    // Fungsi loss cross-entropy, token dengan mask 0 (bagian prompt) tidak dihitung
+    pub fn loss(&self, input_ids: &[u32], targets: &[u32], mask: &[f32]) -> Result<Tensor> {
+        let device = self.embed_tokens.device();
+        let input = Tensor::new(input_ids, device)?.unsqueeze(0)?;
+        let logits = self.forward(&input)?.squeeze(0)?;
+        let log_probs = candle_nn::ops::log_softmax(&logits, D::Minus1)?;
+        let targets = Tensor::new(targets, device)?.unsqueeze(1)?;
+        let log_probs = log_probs.gather(&targets, 1)?.squeeze(1)?;
+        let mask = Tensor::new(mask, device)?;
+        let n_tokens = mask.sum_all()?;
+        let loss = (log_probs * &mask)?.sum_all()?.neg()?;
+        Ok(loss.broadcast_div(&n_tokens)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::{Device, IndexOp};
+    use candle_nn::VarMap;
+
+    // Model kecil dengan bobot acak untuk pengujian
+    fn tiny_model(varmap: &VarMap, device: &Device) -> Result<LoraPhi> {
+        let cfg = Config {
+            vocab_size: 32,
+            hidden_size: 16,
+            intermediate_size: 32,
+            num_hidden_layers: 2,
+            num_attention_heads: 2,
+            num_key_value_heads: None,
+            layer_norm_eps: 1e-5,
+            rope_theta: 10000.,
+            partial_rotary_factor: 0.5,
+            qk_layernorm: false,
+        };
+        LoraPhi::load(&cfg, VarBuilder::from_varmap(varmap, DType::F32, device))
+    }
+
+    #[test]
+    fn cached_forward_matches_full_forward() -> Result<()> {
+        let device = Device::Cpu;
+        let varmap = VarMap::new();
+        let model = tiny_model(&varmap, &device)?;
+        let tokens = [3u32, 1, 4, 1, 5, 9, 2, 6];
+        let full = model.forward(&Tensor::new(&tokens, &device)?.unsqueeze(0)?)?;
+        let expected = full.i((0, tokens.len() - 1))?;
+
+        // Prefix lalu suffix, lalu cache dipotong dan suffix yang sama diproses ulang
+        let mut cache = KvCache::default();
+        model.forward_cached(
+            &Tensor::new(&tokens[..5], &device)?.unsqueeze(0)?,
+            &mut cache,
+        )?;
+        assert_eq!(cache.len(), 5);
+        let suffix = Tensor::new(&tokens[5..], &device)?.unsqueeze(0)?;
+        let mut truncated = cache.clone();
+        let cached = model.forward_cached(&suffix, &mut cache)?.i(0)?;
+        truncated.truncate(3)?;
+        let rest = Tensor::new(&tokens[3..], &device)?.unsqueeze(0)?;
+        let reprocessed = model.forward_cached(&rest, &mut truncated)?.i(0)?;
+        assert_eq!(truncated.len(), tokens.len());
+
+        for logits in [cached, reprocessed] {
+            let diff = (logits - &expected)?.abs()?.max(0)?.to_scalar::<f32>()?;
+            assert!(diff < 1e-4, "max diff {diff}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn padded_batch_matches_single_rows() -> Result<()> {
+        let device = Device::Cpu;
+        let varmap = VarMap::new();
+        let model = tiny_model(&varmap, &device)?;
+        let long = [3u32, 1, 4, 1, 5, 9];
+        let short = [2u32, 7, 1];
+        let single = |tokens: &[u32]| -> Result<Tensor> {
+            let input = Tensor::new(tokens, &device)?.unsqueeze(0)?;
+            Ok(model
+                .forward_cached(&input, &mut KvCache::default())?
+                .i(0)?)
+        };
+
+        // Padding di kiri untuk generasi, lalu satu langkah lanjutan dengan cache batch
+        let padded = [0u32, 0, 0, 2, 7, 1];
+        let input = Tensor::new(&[long, padded], &device)?;
+        let mut cache = KvCache::default();
+        let logits = model.forward_padded(&input, &mut cache, &[0, 3])?;
+        let step = Tensor::new(&[[8u32], [8]], &device)?;
+        let next = model.forward_padded(&step, &mut cache, &[0, 3])?;
+        let expected = [
+            single(&long)?,
+            single(&short)?,
+            single(&[3, 1, 4, 1, 5, 9, 8])?,
+            single(&[2, 7, 1, 8])?,
+        ];
+        // Padding di kanan untuk penilaian prompt
+        let input = Tensor::new(&[long, [2u32, 7, 1, 0, 0, 0]], &device)?;
+        let scored = model.forward_at(&input, &mut KvCache::default(), &[5, 2])?;
+        let actual = [
+            logits.i(0)?,
+            logits.i(1)?,
+            next.i(0)?,
+            next.i(1)?,
+            scored.i(0)?,
+            scored.i(1)?,
+        ];
+        let expected = [&expected[..], &expected[..2]].concat();
+
+        for (logits, expected) in actual.iter().zip(expected.iter()) {
+            let diff = (logits - expected)?.abs()?.max(0)?.to_scalar::<f32>()?;
+            assert!(diff < 1e-4, "max diff {diff}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn logits_match_candle_phi() -> Result<()> {
+        use candle_transformers::models::phi;
+
+        let device = Device::Cpu;
+        let varmap = VarMap::new();
+        let model = tiny_model(&varmap, &device)?;
+        // Bobot diacak setelah dibuat agar layer norm dan bias tidak bernilai 1 dan 0, lalu model candle dibuat dari
+        // VarMap yang sama sehingga memakai bobot yang persis sama
+        common::fixtures::randomize(&varmap, 7)?;
+        let cfg: phi::Config = serde_json::from_str(
+            r#"{
+                "vocab_size": 32,
+                "hidden_size": 16,
+                "intermediate_size": 32,
+                "num_hidden_layers": 2,
+                "num_attention_heads": 2,
+                "num_key_value_heads": null,
+                "hidden_act": "gelu_new",
+                "max_position_embeddings": 64,
+                "layer_norm_eps": 1e-5,
+                "tie_word_embeddings": false,
+                "rope_theta": 10000.0,
+                "partial_rotary_factor": 0.5,
+                "qk_layernorm": false
+            }"#,
+        )?;
+        let vars = varmap.all_vars().len();
+        let mut reference =
+            phi::Model::new(&cfg, VarBuilder::from_varmap(&varmap, DType::F32, &device))?;
+        // Nama atau shape bobot yang berbeda akan membuat variabel baru di VarMap
+        assert_eq!(
+            varmap.all_vars().len(),
+            vars,
+            "candle phi has weights that LoraPhi does not load"
+        );
+
+        // Prompt dalam satu forward lalu dua token satu per satu, membandingkan logits token terakhir setiap langkah
+        let tokens = [3u32, 1, 4, 1, 5, 9, 2, 6];
+        let mut cache = KvCache::default();
+        for (start, end) in [(0, 6), (6, 7), (7, 8)] {
+            let input = Tensor::new(&tokens[start..end], &device)?.unsqueeze(0)?;
+            let expected = reference.forward(&input)?.i(0)?;
+            let actual = model.forward_cached(&input, &mut cache)?.i(0)?;
+            let diff = (actual - expected)?.abs()?.max(0)?.to_scalar::<f32>()?;
+            assert!(diff < 1e-4, "max diff {diff} after {end} tokens");
+        }
+        Ok(())
+    }
+}
@@ -6,34 +6,107 @@ extern crate accelerate_src;
 
 // Import beberapa library yang diperlukan
 use anyhow::{Error as E, Result}; // Untuk error handling
-use clap::{Parser, ValueEnum}; // Untuk parsing argumen
+use clap::{Parser, Subcommand, ValueEnum}; // Untuk parsing argumen
+
+// Mencetak log ke stdout, atau ke stderr pada mode --worker karena stdout dipakai untuk response JSON
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if crate::worker::is_active() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+mod calibration; // Untuk laporan kalibrasi MMLU (ECE, Brier score, reliability diagram, temperature scaling)
+mod finetune; // Untuk fine-tuning LoRA
+#[cfg(test)]
+mod golden_tests; // Untuk test golden generasi, kuantisasi dan MMLU dengan model acak kecil
+mod prefix_cache; // Untuk memakai ulang kv cache dari prefix prompt yang sama
+mod quantize; // Untuk kuantisasi bobot safetensors ke GGUF
+
+#[cfg(test)]
+use common::fixtures; // Untuk fixture test offline (direktori sementara, tokenizer, bobot acak, file golden)
+use common::{
+    budget, chat, compare, context, eval, gen_eval, indommlu, journal, lora, manifest, memory,
+    worker,
+}; // Untuk eval, journal, chat, mode worker dan modul lain yang dibagi dengan binary lain
+use phi::{lora_phi, token_stream}; // Untuk model Phi dengan LoRA dan detokenisasi bertahap, dibagi dengan C ABI
 
 use candle_transformers::models::mixformer::{Config, MixFormerSequentialForCausalLM as MixFormer}; // Untuk model MixFormer
 use candle_transformers::models::phi::{Config as PhiConfig, Model as Phi}; // Untuk model Phi
 use candle_transformers::models::quantized_mixformer::MixFormerSequentialForCausalLM as QMixFormer; // Untuk model QMixFormer
 
-use candle_core::{DType, Device, Tensor}; // Untuk operasi tensor
+use candle_core::{DType, Device, Tensor, D}; // Untuk operasi tensor
 use candle_nn::VarBuilder; // Untuk membangun variabel
 use candle_transformers::generation::LogitsProcessor; // Untuk memproses logit
-use hf_hub::{api::sync::Api, Repo, RepoType}; // Untuk mengakses model dari Hugging Face Hub
+use hf_hub::api::sync::{Api, ApiError, ApiRepo}; // Untuk mengakses model dari Hugging Face Hub
+use hf_hub::{Repo, RepoType}; // Untuk memilih repo model dan revisinya
 use tokenizers::Tokenizer; // Untuk tokenisasi
 
-// Enum untuk model yang digunakan (MixFormer, Phi, atau Quantized)
+// Enum untuk model yang digunakan (MixFormer, Phi, Quantized, atau Phi dengan kv cache yang bisa dipotong)
 enum Model {
     MixFormer(MixFormer),
     Phi(Phi),
     Quantized(QMixFormer),
+    Cached(lora_phi::LoraPhi, lora_phi::KvCache),
+}
+
+// Implementasi Model untuk memanggil forward dan clear_kv_cache tanpa perlu match di setiap tempat
+impl Model {
+    fn forward(&mut self, xs: &Tensor) -> Result<Tensor> {
+        match self {
+            Self::MixFormer(m) => Ok(m.forward(xs)?),
+            Self::Phi(m) => Ok(m.forward(xs)?),
+            Self::Quantized(m) => Ok(m.forward(xs)?),
+            Self::Cached(m, cache) => m.forward_cached(xs, cache),
+        }
+    }
+
+    fn clear_kv_cache(&mut self) {
+        match self {
+            Self::MixFormer(m) => m.clear_kv_cache(),
+            Self::Phi(m) => m.clear_kv_cache(),
+            Self::Quantized(m) => m.clear_kv_cache(),
+            Self::Cached(_, cache) => cache.clear(),
+        }
+    }
+
+    // Memotong kv cache ke len token pertama, mengembalikan jumlah token yang tersisa di cache
+    // (model candle hanya bisa mengosongkan cache)
+    fn truncate_kv_cache(&mut self, len: usize) -> Result<usize> {
+        match self {
+            Self::Cached(_, cache) => {
+                cache.truncate(len)?;
+                Ok(len)
+            }
+            _ => {
+                self.clear_kv_cache();
+                Ok(0)
+            }
+        }
+    }
 }
 
+// Panjang jendela konteks (n_positions) pada semua config MixFormer
+const MIXFORMER_POSITIONS: usize = 2048;
+
 // Struct untuk melakukan generasi teks
 struct TextGeneration {
     model: Model,
     device: Device,
     tokenizer: Tokenizer,
     logits_processor: LogitsProcessor,
+    // Parameter sampling untuk membuat LogitsProcessor setiap baris pada generasi batch
+    seed: u64,
+    temp: Option<f64>,
+    top_p: Option<f64>,
     repeat_penalty: f32,
     repeat_last_n: usize,
     verbose_prompt: bool,
+    context: context::ContextWindow,
+    prefix: prefix_cache::PrefixCache,
 }
 
 // Implementasi untuk TextGeneration (untuk generasi teks)
@@ -49,6 +122,7 @@ impl TextGeneration {
         repeat_penalty: f32,
         repeat_last_n: usize,
         verbose_prompt: bool,
+        context: context::ContextWindow,
         device: &Device,
     ) -> Self {
         // Membuat instance baru dari LogitsProcessor
@@ -57,15 +131,25 @@ impl TextGeneration {
             model,
             tokenizer,
             logits_processor,
+            seed,
+            temp,
+            top_p,
             repeat_penalty,
             repeat_last_n,
             verbose_prompt,
+            context,
+            prefix: prefix_cache::PrefixCache::new(),
             device: device.clone(),
         }
     }
 
-    // Fungsi untuk menjalankan generasi teks berdasarkan prompt yang diberikan dan panjang sample yang diinginkan (dalam token)
-    fn run(&mut self, prompt: &str, sample_len: usize) -> Result<()> {
+    // Fungsi untuk menjalankan generasi teks berdasarkan prompt yang diberikan dan panjang sample yang diinginkan (dalam token), mengembalikan teks yang dihasilkan
+    fn run(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        budget: &budget::Budget,
+    ) -> Result<(String, budget::FinishReason)> {
         // Import beberapa library yang diperlukan
         use std::io::Write;
         println!("starting the inference loop");
@@ -81,29 +165,91 @@ impl TextGeneration {
                 println!("{id:7} -> '{token}'");
             }
         }
-        // Mendapatkan token dari prompt
-        let mut tokens = tokens.get_ids().to_vec();
-        // Jika model adalah MixFormer, maka akan menjalankan MixFormer
+        // Mendapatkan token dari prompt, dipotong jika tidak muat di jendela konteks
+        let prompt_len = tokens.len();
+        let tokens = self.context.truncate_prompt(tokens.get_ids().to_vec())?;
+        if tokens.len() == prompt_len {
+            print!("{prompt}");
+        } else {
+            print!("{}", self.tokenizer.decode(&tokens, false).map_err(E::msg)?);
+        }
+        // Flush output ke stdout (standar output)
+        std::io::stdout().flush()?;
+        // Memulai generasi teks dan menghitung waktu yang diperlukan untuk generasi teks tersebut (dalam detik)
+        let start_gen = std::time::Instant::now();
+        let generation = self.generate_tokens(tokens, sample_len, budget, &mut |text| {
+            print!("{text}");
+            std::io::stdout().flush()?;
+            Ok(())
+        })?;
+        let dt = start_gen.elapsed();
+        println!(
+            "\n{} tokens generated ({:.2} token/s), finish reason: {}",
+            generation.generated_tokens,
+            generation.generated_tokens as f64 / dt.as_secs_f64(),
+            generation.finish_reason,
+        );
+        Ok((generation.text, generation.finish_reason))
+    }
+
+    // Fungsi untuk menghasilkan token dari prompt yang sudah ditokenisasi, setiap potongan teks baru dikirim ke on_text
+    // (ditulis ke stdout pada CLI, atau dikirim sebagai event token pada mode --worker)
+    fn generate_tokens(
+        &mut self,
+        mut tokens: Vec<u32>,
+        sample_len: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        let prompt_tokens = tokens.len();
         let mut generated_tokens = 0usize;
         // Mendapatkan token eos (end of sentence)
         let eos_token = match self.tokenizer.get_vocab(true).get("<|endoftext|>") {
             Some(token) => *token,
             None => anyhow::bail!("cannot find the endoftext token"),
         };
-        print!("{prompt}");
-        // Flush output ke stdout (standar output)
-        std::io::stdout().flush()?;
-        // Memulai generasi teks (menggunakan MixFormer) dan menghitung waktu yang diperlukan untuk generasi teks tersebut (dalam detik)
-        let start_gen = std::time::Instant::now();
+        let mut output = String::new();
+        let mut stream = token_stream::stream(&self.tokenizer);
+        let mut finish_reason = budget::FinishReason::Length;
+        // Awal jendela konteks di dalam tokens, berubah saat jendela digeser
+        let mut window_start = 0usize;
+        let mut prefill = true;
         for index in 0..sample_len {
-            let context_size = if index > 0 { 1 } else { tokens.len() }; // Jika index > 0, maka context_size = 1, jika tidak, maka context_size = panjang tokens
-            let ctxt = &tokens[tokens.len().saturating_sub(context_size)..]; // Mendapatkan context dari tokens
-            let input = Tensor::new(ctxt, &self.device)?.unsqueeze(0)?; // Membuat tensor baru dari context
-            let logits = match &mut self.model {
-                Model::MixFormer(m) => m.forward(&input)?,
-                Model::Phi(m) => m.forward(&input)?,
-                Model::Quantized(m) => m.forward(&input)?,
+            if let Some(reason) = budget.check() {
+                finish_reason = reason;
+                break;
+            }
+            if tokens.len() - window_start > self.context.size {
+                match self.context.overflow {
+                    context::Overflow::Stop => {
+                        finish_reason = budget::FinishReason::Context;
+                        break;
+                    }
+                    context::Overflow::Shift => {
+                        window_start = tokens.len() - self.context.shift_keep();
+                        prefill = true;
+                    }
+                }
+            }
+            // Saat prefill seluruh jendela konteks diproses (memakai ulang prefix yang sudah ada di kv cache), jika tidak hanya token terakhir
+            let logits = if prefill {
+                prefill = false;
+                let window = &tokens[window_start..];
+                self.prefix.prefill(&mut self.model, window, &self.device)?
+            } else {
+                let last = tokens[tokens.len() - 1];
+                let input = Tensor::new(&[last], &self.device)?.unsqueeze(0)?; // Membuat tensor baru dari token terakhir
+                let logits = self.model.forward(&input)?;
+                self.prefix.push(last);
+                logits
             };
+            // Prompt diproses dalam satu forward, sehingga --max-prompt-time diperiksa setelahnya
+            if index == 0 {
+                if let Some(reason) = budget.check_prompt() {
+                    finish_reason = reason;
+                    break;
+                }
+            }
             let logits = logits.squeeze(0)?.to_dtype(DType::F32)?; // Mengubah logits ke tipe data f32
             // Jika repeat_penalty = 1, maka tidak ada penalty yang diterapkan pada logits, jika tidak, maka akan diterapkan penalty pada logits berdasarkan repeat_penalty dan repeat_last_n yang diberikan (dalam tokens)
             let logits = if self.repeat_penalty == 1. {
@@ -122,17 +268,228 @@ impl TextGeneration {
             generated_tokens += 1;
             // Jika token yang dihasilkan adalah token eos, maka akan menghentikan generasi teks dan menampilkan hasil generasi teks tersebut (dalam token) beserta waktu yang diperlukan untuk generasi teks tersebut (dalam detik)
             if next_token == eos_token {
+                finish_reason = budget::FinishReason::Eos;
                 break;
             }
-            let token = self.tokenizer.decode(&[next_token], true).map_err(E::msg)?;
-            print!("{token}");
-            std::io::stdout().flush()?;
+            if let Some(text) = stream.next_token(next_token)? {
+                on_text(&text)?;
+                output.push_str(&text);
+            }
+        }
+        if let Some(text) = stream.flush()? {
+            on_text(&text)?;
+            output.push_str(&text);
+        }
+        Ok(worker::Generation {
+            text: output,
+            finish_reason,
+            prompt_tokens,
+            generated_tokens,
+        })
+    }
+
+    // Fungsi untuk menjalankan generasi beberapa prompt sekaligus dalam satu batch (hanya model Phi dengan kv cache
+    // yang bisa dipotong), prompt diberi padding di kiri dengan token eos dan setiap baris disampling sendiri.
+    // Mengembalikan teks dan alasan berhenti setiap prompt
+    fn run_batch(
+        &self,
+        prompts: &[String],
+        sample_len: usize,
+        budget: &budget::Budget,
+    ) -> Result<Vec<(String, budget::FinishReason)>> {
+        let phi = match &self.model {
+            Model::Cached(phi, _) => phi,
+            _ => anyhow::bail!("batched generation requires phi 1, 1.5 or 2 without --quantized"),
+        };
+        let eos_token = match self.tokenizer.get_vocab(true).get("<|endoftext|>") {
+            Some(token) => *token,
+            None => anyhow::bail!("cannot find the endoftext token"),
+        };
+        let mut rows = vec![];
+        for prompt in prompts {
+            let tokens = self
+                .tokenizer
+                .encode(prompt.as_str(), true)
+                .map_err(E::msg)?;
+            if tokens.is_empty() {
+                anyhow::bail!("Empty prompts are not supported in the phi model.")
+            }
+            rows.push(self.context.truncate_prompt(tokens.get_ids().to_vec())?);
+        }
+        let prompt_len = rows.iter().map(|tokens| tokens.len()).max().unwrap_or(0);
+        // Jumlah token padding di kiri setiap baris
+        let pad: Vec<usize> = rows
+            .iter()
+            .map(|tokens| prompt_len - tokens.len())
+            .collect();
+        let mut input = vec![];
+        for (tokens, pad) in rows.iter().zip(pad.iter()) {
+            input.extend(std::iter::repeat_n(eos_token, *pad));
+            input.extend_from_slice(tokens);
+        }
+        let mut input = Tensor::from_vec(input, (rows.len(), prompt_len), &self.device)?;
+        let mut processors: Vec<LogitsProcessor> = (0..rows.len())
+            .map(|row| LogitsProcessor::new(self.seed + row as u64, self.temp, self.top_p))
+            .collect();
+        let mut streams: Vec<_> = (0..rows.len())
+            .map(|_| token_stream::stream(&self.tokenizer))
+            .collect();
+        let mut outputs = vec![String::new(); rows.len()];
+        let mut finished: Vec<Option<budget::FinishReason>> = vec![None; rows.len()];
+        let mut cache = lora_phi::KvCache::default();
+        let mut generated_tokens = 0usize;
+        let start_gen = std::time::Instant::now();
+        for index in 0..sample_len {
+            // Alasan berhenti yang berlaku untuk semua baris yang belum selesai
+            let stop = match budget.check() {
+                Some(reason) => Some(reason),
+                None if cache.len() + input.dim(1)? > self.context.size => {
+                    Some(budget::FinishReason::Context)
+                }
+                None => None,
+            };
+            if let Some(reason) = stop {
+                for finish_reason in finished.iter_mut().filter(|r| r.is_none()) {
+                    *finish_reason = Some(reason);
+                }
+                break;
+            }
+            let logits = phi.forward_padded(&input, &mut cache, &pad)?;
+            let logits = logits.to_dtype(DType::F32)?;
+            if index == 0 {
+                if let Some(reason) = budget.check_prompt() {
+                    for finish_reason in finished.iter_mut() {
+                        *finish_reason = Some(reason);
+                    }
+                    break;
+                }
+            }
+            // Baris yang sudah selesai tetap diberi token eos agar bentuk batch tidak berubah
+            let mut next_tokens = vec![eos_token; rows.len()];
+            for (row, tokens) in rows.iter_mut().enumerate() {
+                if finished[row].is_some() {
+                    continue;
+                }
+                let logits = logits.get(row)?;
+                let logits = if self.repeat_penalty == 1. {
+                    logits
+                } else {
+                    let start_at = tokens.len().saturating_sub(self.repeat_last_n);
+                    candle_transformers::utils::apply_repeat_penalty(
+                        &logits,
+                        self.repeat_penalty,
+                        &tokens[start_at..],
+                    )?
+                };
+                let next_token = processors[row].sample(&logits)?;
+                tokens.push(next_token);
+                generated_tokens += 1;
+                next_tokens[row] = next_token;
+                if next_token == eos_token {
+                    finished[row] = Some(budget::FinishReason::Eos);
+                } else if let Some(text) = streams[row].next_token(next_token)? {
+                    outputs[row].push_str(&text);
+                }
+            }
+            if finished.iter().all(|r| r.is_some()) {
+                break;
+            }
+            input = Tensor::from_vec(next_tokens, (rows.len(), 1), &self.device)?;
         }
         let dt = start_gen.elapsed();
+        let mut results = vec![];
+        for (row, prompt) in prompts.iter().enumerate() {
+            if let Some(text) = streams[row].flush()? {
+                outputs[row].push_str(&text);
+            }
+            let finish_reason = finished[row].unwrap_or(budget::FinishReason::Length);
+            println!("{prompt}{}\nfinish reason: {finish_reason}\n", outputs[row]);
+            results.push((std::mem::take(&mut outputs[row]), finish_reason));
+        }
         println!(
-            "\n{generated_tokens} tokens generated ({:.2} token/s)",
+            "{generated_tokens} tokens generated for {} prompts ({:.2} token/s)",
+            prompts.len(),
             generated_tokens as f64 / dt.as_secs_f64(),
         );
+        Ok(results)
+    }
+}
+
+// Implementasi Backend agar TextGeneration bisa dilayani oleh mode --worker
+impl worker::Backend for TextGeneration {
+    fn generate(
+        &mut self,
+        prompt: &str,
+        max_tokens: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        let tokens = self.tokenizer.encode(prompt, true).map_err(E::msg)?;
+        if tokens.is_empty() {
+            anyhow::bail!("Empty prompts are not supported in the phi model.")
+        }
+        let tokens = self.context.truncate_prompt(tokens.get_ids().to_vec())?;
+        self.generate_tokens(tokens, max_tokens, budget, on_text)
+    }
+
+    // Log-probability dihitung token demi token, prefix cache membuat setiap langkah hanya memproses satu token baru
+    // pada model Phi dengan kv cache yang bisa dipotong
+    fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)> {
+        let prompt_tokens = self.tokenizer.encode(prompt, true).map_err(E::msg)?;
+        let text = format!("{prompt}{continuation}");
+        let tokens = self.tokenizer.encode(text, true).map_err(E::msg)?;
+        let tokens = tokens.get_ids();
+        // Continuation dimulai dari token pertama yang berbeda dari token prompt
+        let start = prompt_tokens
+            .get_ids()
+            .iter()
+            .zip(tokens.iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .max(1);
+        if start >= tokens.len() {
+            anyhow::bail!("the continuation {continuation:?} adds no tokens to the prompt")
+        }
+        if tokens.len() > self.context.size {
+            anyhow::bail!(
+                "prompt and continuation have {} tokens but the context window is {}",
+                tokens.len(),
+                self.context.size
+            )
+        }
+        // Prompt diproses sekali, lalu setiap token continuation ditambahkan ke kv cache satu per satu seperti saat
+        // generasi, sehingga model candle yang hanya bisa mengosongkan cache tidak memproses ulang prompt per token
+        let mut logits = self
+            .prefix
+            .prefill(&mut self.model, &tokens[..start], &self.device)?;
+        let mut logprob = 0f64;
+        for end in start..tokens.len() {
+            if end > start {
+                let input = Tensor::new(&[tokens[end - 1]], &self.device)?.unsqueeze(0)?;
+                logits = self.model.forward(&input)?;
+                self.prefix.push(tokens[end - 1]);
+            }
+            let log_probs = logits.squeeze(0)?.to_dtype(DType::F32)?;
+            let log_probs = candle_nn::ops::log_softmax(&log_probs, D::Minus1)?;
+            logprob += log_probs.get(tokens[end] as usize)?.to_scalar::<f32>()? as f64;
+        }
+        Ok((logprob, tokens.len() - start))
+    }
+
+    fn tokenize(&mut self, text: &str) -> Result<Vec<(u32, String)>> {
+        let tokens = self.tokenizer.encode(text, true).map_err(E::msg)?;
+        Ok(tokens
+            .get_ids()
+            .iter()
+            .zip(tokens.get_tokens().iter())
+            .map(|(id, token)| (*id, token.clone()))
+            .collect())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.model.clear_kv_cache();
+        self.prefix = prefix_cache::PrefixCache::new();
+        self.logits_processor = LogitsProcessor::new(self.seed, self.temp, self.top_p);
         Ok(())
     }
 }
@@ -152,6 +509,56 @@ enum WhichModel {
     PhiHermes,
 }
 
+impl WhichModel {
+    // Jumlah layer dan lebar kv cache (n_embd) setiap model, dipakai untuk perkiraan memori sebelum bobot dimuat
+    fn kv_shape(&self) -> (usize, usize) {
+        match self {
+            Self::V2 | Self::V2Old => (32, 2560),
+            Self::V1 | Self::V1_5 | Self::PuffinPhiV2 | Self::PhiHermes => (24, 2048),
+        }
+    }
+}
+
+// Enum untuk tipe data komputasi model non-kuantisasi (f32, f16, atau bf16)
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+enum WhichDType {
+    F32,
+    F16,
+    Bf16,
+}
+
+impl WhichDType {
+    fn dtype(&self) -> DType {
+        match self {
+            Self::F32 => DType::F32,
+            Self::F16 => DType::F16,
+            Self::Bf16 => DType::BF16,
+        }
+    }
+}
+
+// Enum untuk subcommand yang tersedia selain generasi teks
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Quantize local safetensors weights into a GGUF file usable with --quantized.
+    Quantize(quantize::QuantizeArgs),
+    /// Merge LoRA adapters into the base safetensors weights and write the result.
+    ExportMerged(lora::ExportMergedArgs),
+    /// Train a LoRA adapter on a JSONL prompt/response dataset (phi 1, 1.5 and 2 only).
+    Finetune(finetune::FinetuneArgs),
+    /// Re-run the generation recorded in a manifest and diff the output.
+    Replay(manifest::ReplayArgs),
+    /// Evaluate a multiple-choice JSONL task by the length-normalised log-likelihood of each
+    /// answer.
+    Eval(eval::EvalArgs),
+    /// Evaluate a free-form QA JSONL task (GSM8K style) by generating an answer and extracting the
+    /// final answer with regexes.
+    EvalGen(gen_eval::GenEvalArgs),
+    /// Compare two per-question eval results (from eval --output or --mmlu-output) and fail when
+    /// the accuracy drops more than --max-drop.
+    Compare(compare::CompareArgs),
+}
+
 // Struct untuk argumen yang diperlukan (untuk parsing argumen)
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -171,9 +578,78 @@ struct Args {
     #[arg(long)]
     prompt: Option<String>,
 
+    /// JSON file with a list of `{"role": ..., "content": ...}` messages, rendered with the chat
+    /// template and used as the prompt.
+    #[arg(long)]
+    messages: Option<String>,
+
+    /// The chat template used with --messages: a builtin (chatml, mistral-inst, rwkv, alpaca,
+    /// vicuna) or a jinja file. Defaults to the `chat_template` of tokenizer_config.json, then to
+    /// the builtin matching the model.
+    #[arg(long)]
+    chat_template: Option<String>,
+
     #[arg(long)]
     mmlu_dir: Option<String>,
 
+    /// The number of few-shot examples taken from the `dev` directory next to --mmlu-dir.
+    #[arg(long, default_value_t = 0)]
+    mmlu_shots: usize,
+
+    /// The language of the MMLU prompt: the intro sentence of every subject and the answer cue
+    /// ("Answer:" or "Jawaban:").
+    #[arg(long, value_enum, default_value = "english")]
+    mmlu_template: indommlu::PromptTemplate,
+
+    /// JSONL file where the per-question MMLU results (subject, answer, prediction) are written,
+    /// to be used with the compare subcommand.
+    #[arg(long)]
+    mmlu_output: Option<String>,
+
+    /// JSONL journal where every MMLU result is appended as soon as the question is scored, keyed
+    /// by the CSV file and row with a hash of the model and prompt template.
+    #[arg(long)]
+    mmlu_journal: Option<String>,
+
+    /// Continue an interrupted MMLU run: the questions already in --mmlu-journal with the same
+    /// model and prompt template are not scored again.
+    #[arg(long, requires = "mmlu_journal")]
+    resume: bool,
+
+    /// The number of equal-width confidence bins of the MMLU calibration report (expected
+    /// calibration error and reliability diagram).
+    #[arg(long, default_value_t = 10)]
+    mmlu_bins: usize,
+
+    /// Report the MMLU accuracy and coverage of the answers with at least these confidences.
+    #[arg(long, value_delimiter = ',', default_value = "0.5,0.7,0.9")]
+    mmlu_thresholds: Vec<f64>,
+
+    /// CSV file where the MMLU reliability diagram (confidence and accuracy per bin) is written.
+    #[arg(long)]
+    mmlu_reliability: Option<String>,
+
+    /// Fit a softmax temperature on the `dev` questions next to --mmlu-dir and also report the
+    /// calibration with that temperature.
+    #[arg(long)]
+    mmlu_temperature_scaling: bool,
+
+    /// JSONL file with one `{"prompt": ...}` object per line, the prompts are run one after the
+    /// other and shared prompt prefixes are not processed again.
+    #[arg(long)]
+    batch_file: Option<String>,
+
+    /// The number of prompts of --batch-file (or questions of --mmlu-dir) processed together in one
+    /// batch, left-padded with per-prompt attention masks and sampling (phi 1, 1.5 and 2 only).
+    /// Batched generation stops at the end of the context window, --context-overflow shift is
+    /// rejected.
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+
+    /// JSONL file where the batch results (prompt, output and finish reason) are written.
+    #[arg(long)]
+    batch_output: Option<String>,
+
     /// The temperature used to generate samples.
     #[arg(long)]
     temperature: Option<f64>,
@@ -190,6 +666,22 @@ struct Args {
     #[arg(long, short = 'n', default_value_t = 5000)]
     sample_len: usize,
 
+    /// Stop generating after this many seconds of wall-clock time, prompt processing included.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_time: Option<f64>,
+
+    /// Stop if processing the prompt takes longer than this many seconds.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_prompt_time: Option<f64>,
+
+    /// How to shorten a prompt that does not fit in the context window.
+    #[arg(long, value_enum, default_value = "error")]
+    truncate: context::Truncate,
+
+    /// What to do when generation reaches the end of the context window.
+    #[arg(long, value_enum, default_value = "stop")]
+    context_overflow: context::Overflow,
+
     #[arg(long)]
     model_id: Option<String>,
 
@@ -208,6 +700,25 @@ struct Args {
     #[arg(long)]
     quantized: bool,
 
+    /// The dtype used for the weights and activations of non-quantized models, logits are
+    /// always upcast to f32 before sampling.
+    #[arg(long, value_enum, default_value = "f32")]
+    dtype: WhichDType,
+
+    /// Load the weights even when the estimated memory (weights plus kv cache) is larger than
+    /// the available memory.
+    #[arg(long)]
+    no_memory_check: bool,
+
+    /// LoRA adapter directory (PEFT format) merged into the weights at load time, optionally
+    /// followed by `:<scale>`. Can be repeated.
+    #[arg(long, value_parser = lora::parse_lora)]
+    lora: Vec<lora::LoraAdapter>,
+
+    /// Write a run manifest (file hashes, features, args and output hash) to this file.
+    #[arg(long)]
+    manifest: Option<String>,
+
     /// Penalty to be applied for repeating tokens, 1. means no penalty.
     #[arg(long, default_value_t = 1.1)]
     repeat_penalty: f32,
@@ -215,8 +726,49 @@ struct Args {
     /// The context size to consider for the repeat penalty.
     #[arg(long, default_value_t = 64)]
     repeat_last_n: usize,
+
+    /// Serve newline-delimited JSON requests (generate, score, tokenize, reset) from stdin
+    /// instead of running a prompt. Responses and token events are written to stdout, logs to
+    /// stderr.
+    #[arg(long)]
+    worker: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
+// Fungsi untuk informasi build yang dicatat di manifest run: versi candle dari build.rs, fitur CPU dan fitur cargo yang aktif
+fn build_info() -> manifest::BuildInfo {
+    manifest::BuildInfo {
+        crate_name: env!("CARGO_PKG_NAME"),
+        crate_version: env!("CARGO_PKG_VERSION"),
+        candle_version: env!("CANDLE_VERSION"),
+        cpu_features: format!(
+            "avx: {}, neon: {}, simd128: {}, f16c: {}",
+            candle_core::utils::with_avx(),
+            candle_core::utils::with_neon(),
+            candle_core::utils::with_simd128(),
+            candle_core::utils::with_f16c()
+        ),
+        cargo_features: manifest::enabled_features(&[
+            ("accelerate", cfg!(feature = "accelerate")),
+            ("cudnn", cfg!(feature = "cudnn")),
+            ("flash-attn", cfg!(feature = "flash-attn")),
+            ("mkl", cfg!(feature = "mkl")),
+            ("metal", cfg!(feature = "metal")),
+        ]),
+    }
+}
+
+// Fungsi untuk mengambil file opsional dari repo Hugging Face Hub: None hanya jika file tidak ada (404), error
+// jaringan atau autentikasi tetap dilaporkan
+fn hub_get_optional(repo: &ApiRepo, filename: &str) -> Result<Option<std::path::PathBuf>> {
+    match repo.get(filename) {
+        Ok(path) => Ok(Some(path)),
+        Err(ApiError::RequestError(err)) if matches!(*err, ureq::Error::Status(404, _)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
 
 // Fungsi main untuk menjalankan program utama (generasi teks)
 fn main() -> Result<()> {
@@ -232,14 +784,32 @@ fn main() -> Result<()> {
     } else {
         None
     };
-    println!(
-        "avx: {}, neon: {}, simd128: {}, f16c: {}",
-        candle_core::utils::with_avx(),
-        candle_core::utils::with_neon(),
-        candle_core::utils::with_simd128(),
-        candle_core::utils::with_f16c()
-    );
-    println!(
+    // Jika subcommand diberikan, maka hanya menjalankan subcommand tersebut (tanpa generasi teks)
+    let command = match args.command {
+        Some(Command::Quantize(quantize_args)) => {
+            let v2 = matches!(args.model, WhichModel::V2 | WhichModel::V2Old);
+            return quantize::run(quantize_args, v2);
+        }
+        Some(Command::ExportMerged(export_args)) => return lora::export_merged(export_args),
+        Some(Command::Replay(replay_args)) => return manifest::replay(replay_args),
+        Some(Command::Compare(compare_args)) => return compare::run(&compare_args),
+        command => command,
+    };
+    // Generasi batch memakai satu kv cache untuk semua baris sehingga jendela tidak bisa digeser per baris,
+    // ditolak sebelum model dimuat
+    if args.batch_size > 1
+        && args.batch_file.is_some()
+        && args.context_overflow == context::Overflow::Shift
+    {
+        anyhow::bail!("--context-overflow shift cannot be used with --batch-size > 1, use stop")
+    }
+    // Pada mode worker stdout hanya berisi response JSON, log dipindah ke stderr
+    if args.worker {
+        worker::activate();
+    }
+    let build = build_info();
+    log!("{}", build.cpu_features);
+    log!(
         "temp: {:.2} repeat-penalty: {:.2} repeat-last-n: {}",
         args.temperature.unwrap_or(0.),
         args.repeat_penalty,
@@ -325,9 +895,9 @@ fn main() -> Result<()> {
             }
         }
     };
-    println!("retrieved the files in {:?}", start.elapsed());
+    log!("retrieved the files in {:?}", start.elapsed());
     // tokenizer berisi Tokenizer yang digunakan (berdasarkan tokenizer file yang diberikan oleh pengguna) dan menampilkan informasi tentang tokenizer yang digunakan
-    let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+    let tokenizer = Tokenizer::from_file(&tokenizer_filename).map_err(E::msg)?;
 
     let start = std::time::Instant::now(); // Menghitung waktu yang diperlukan untuk proses generasi teks (dalam detik) 
     // config berisi konfigurasi yang digunakan (berdasarkan model yang digunakan) dan menampilkan informasi tentang konfigurasi yang digunakan
@@ -340,8 +910,66 @@ fn main() -> Result<()> {
     };
     // device berisi device yang digunakan (berdasarkan argumen yang diberikan oleh pengguna) dan menampilkan informasi tentang device yang digunakan
     let device = candle_examples::device(args.cpu)?;
+    // Jika subcommand finetune diberikan, maka melatih adapter LoRA di atas bobot model yang sudah diunduh
+    if let Some(Command::Finetune(finetune_args)) = command {
+        let supported = matches!(
+            args.model,
+            WhichModel::V1 | WhichModel::V1_5 | WhichModel::V2
+        );
+        if args.quantized || !supported {
+            anyhow::bail!("finetune only supports the non-quantized phi 1, 1.5 and 2 models")
+        }
+        let config = std::fs::read_to_string(repo.get("config.json")?)?;
+        let config: lora_phi::Config = serde_json::from_str(&config)?;
+        return finetune::run(finetune_args, &filenames, &config, &tokenizer, &device);
+    }
+    let phi = !args.quantized
+        && matches!(
+            args.model,
+            WhichModel::V1 | WhichModel::V1_5 | WhichModel::V2
+        );
+    // Jendela konteks dibaca dari config.json untuk model Phi, model MixFormer memakai n_positions
+    let size = if phi {
+        context::size_from_config(&repo.get("config.json")?)?
+    } else {
+        MIXFORMER_POSITIONS
+    };
+    // Memori yang dibutuhkan diperkirakan dari header file bobot sebelum dimuat. Hanya di CPU, karena bobot di GPU
+    // tidak memakai RAM
+    if device.is_cpu() && !args.no_memory_check {
+        let (layers, width) = args.model.kv_shape();
+        // Generasi batch dan MMLU membuat satu kv cache untuk setiap baris batch
+        let rows = if args.batch_file.is_some() || args.mmlu_dir.is_some() {
+            args.batch_size.max(1)
+        } else {
+            1
+        };
+        let estimate = |weights: u64, dtype: DType| memory::Estimate {
+            weights,
+            cache: memory::kv_cache_bytes(layers, width, size, rows, dtype),
+            cache_name: "cache",
+        };
+        let (estimate, suggestions) = if args.quantized {
+            // Model kuantisasi menyimpan kv cache dalam f32
+            let weights = memory::gguf_bytes(&filenames[0])?;
+            (estimate(weights, DType::F32), vec![])
+        } else {
+            let dtype = args.dtype.dtype();
+            let weights = memory::safetensors_bytes(&filenames, dtype)?;
+            let mut suggestions = vec!["--quantized".to_string()];
+            if dtype == DType::F32 {
+                let bf16 = memory::gib(estimate(weights / 2, DType::BF16).total());
+                suggestions.push(format!("--dtype bf16 (about {bf16})"));
+            }
+            (estimate(weights, dtype), suggestions)
+        };
+        memory::check(&estimate, memory::available_bytes(), &suggestions)?;
+    }
     // model berisi model yang digunakan (berdasarkan model yang digunakan) dan menampilkan informasi tentang model yang digunakan
     let model = if args.quantized {
+        if !args.lora.is_empty() {
+            anyhow::bail!("--lora requires non-quantized weights, use export-merged then quantize")
+        }
         let config = config();
         let vb = candle_transformers::quantized_var_builder::VarBuilder::from_gguf(
             &filenames[0],
@@ -353,132 +981,571 @@ fn main() -> Result<()> {
         };
         Model::Quantized(model)
     } else {
-        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &device)? };
-        match args.model {
-            WhichModel::V1 | WhichModel::V1_5 | WhichModel::V2 => {
-                let config_filename = repo.get("config.json")?;
-                let config = std::fs::read_to_string(config_filename)?;
-                let config: PhiConfig = serde_json::from_str(&config)?;
-                let phi = Phi::new(&config, vb)?;
-                Model::Phi(phi)
-            }
-            WhichModel::V2Old => {
-                let config = config();
-                Model::MixFormer(MixFormer::new_v2(&config, vb)?)
-            }
-            WhichModel::PhiHermes | WhichModel::PuffinPhiV2 => {
-                let config = config();
-                Model::MixFormer(MixFormer::new(&config, vb)?)
+        // Jika ada adapter LoRA, bobot dimuat ke memori dan digabung terlebih dahulu
+        let merged = if args.lora.is_empty() {
+            None
+        } else {
+            Some(lora::load_merged(&filenames, &args.lora)?)
+        };
+        // Fungsi untuk memuat model non-kuantisasi dengan tipe data tertentu
+        let prefix_reuse = args.mmlu_dir.is_some()
+            || args.batch_file.is_some()
+            || args.worker
+            || matches!(command, Some(Command::Eval(_) | Command::EvalGen(_)));
+        let load = |dtype: DType| -> Result<Model> {
+            let vb = match &merged {
+                Some(tensors) => VarBuilder::from_tensors(tensors.clone(), dtype, &device),
+                None => unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, &device)? },
+            };
+            let model = match args.model {
+                // MMLU, file batch, dan worker memakai model Phi dengan kv cache yang bisa dipotong agar prefix yang sama tidak diproses ulang dan prompt bisa diproses per batch
+                WhichModel::V1 | WhichModel::V1_5 | WhichModel::V2 if prefix_reuse => {
+                    let config = std::fs::read_to_string(repo.get("config.json")?)?;
+                    let config: lora_phi::Config = serde_json::from_str(&config)?;
+                    let phi = lora_phi::LoraPhi::load(&config, vb)?;
+                    Model::Cached(phi, lora_phi::KvCache::default())
+                }
+                WhichModel::V1 | WhichModel::V1_5 | WhichModel::V2 => {
+                    let config_filename = repo.get("config.json")?;
+                    let config = std::fs::read_to_string(config_filename)?;
+                    let config: PhiConfig = serde_json::from_str(&config)?;
+                    let phi = Phi::new(&config, vb)?;
+                    Model::Phi(phi)
+                }
+                WhichModel::V2Old => {
+                    let config = config();
+                    Model::MixFormer(MixFormer::new_v2(&config, vb)?)
+                }
+                WhichModel::PhiHermes | WhichModel::PuffinPhiV2 => {
+                    let config = config();
+                    Model::MixFormer(MixFormer::new(&config, vb)?)
+                }
+            };
+            Ok(model)
+        };
+        let dtype = args.dtype.dtype();
+        let mut model = load(dtype)?;
+        // Jika tipe data bukan f32, maka dicoba satu forward untuk memastikan semua operasi didukung di device ini
+        match probe(&mut model, &device) {
+            Ok(()) => model,
+            Err(err) if dtype != DType::F32 => {
+                log!(
+                    "warning: {dtype:?} is not supported on {device:?} ({err}), falling back to F32"
+                );
+                drop(model);
+                load(DType::F32)?
             }
+            Err(err) => return Err(err),
         }
     };
-    println!("loaded the model in {:?}", start.elapsed());
+    log!("loaded the model in {:?}", start.elapsed());
+
+    // Jika messages diberikan, maka pesan dirender dengan template chat dan dipakai sebagai prompt
+    let prompt = match (args.prompt, args.messages) {
+        (Some(_), Some(_)) => anyhow::bail!("--prompt and --messages cannot be used together"),
+        (prompt, None) => prompt,
+        (None, Some(messages)) => {
+            let messages = chat::load_messages(messages)?;
+            let default = match args.model {
+                WhichModel::PuffinPhiV2 => chat::BuiltinTemplate::Vicuna,
+                _ => chat::BuiltinTemplate::Alpaca,
+            };
+            let tokenizer_config = match args.chat_template {
+                Some(_) => None,
+                None => hub_get_optional(&repo, "tokenizer_config.json")?,
+            };
+            let template = chat::resolve(
+                args.chat_template.as_deref(),
+                tokenizer_config.as_deref(),
+                default,
+                "<|endoftext|>",
+            )?;
+            Some(template.render(&messages, true)?)
+        }
+    };
+
+    // Prompt tunggal dijalankan sebagai batch dengan satu prompt
+    let prompts = match (prompt, args.batch_file.as_ref()) {
+        (Some(_), Some(_)) => {
+            anyhow::bail!("--prompt (or --messages) and --batch-file cannot be used together")
+        }
+        (Some(prompt), None) => Some(vec![prompt]),
+        (None, Some(batch_file)) => Some(load_batch(batch_file)?),
+        (None, None) => None,
+    };
+
+    let context = context::ContextWindow {
+        size,
+        truncate: args.truncate,
+        overflow: args.context_overflow,
+    };
+    // Fungsi untuk membuat pipeline generasi teks dari argumen yang diberikan
+    let new_pipeline = |model: Model, tokenizer: Tokenizer| {
+        TextGeneration::new(
+            model,
+            tokenizer,
+            args.seed,
+            args.temperature,
+            args.top_p,
+            args.repeat_penalty,
+            args.repeat_last_n,
+            args.verbose_prompt,
+            context,
+            &device,
+        )
+    };
+
+    // Subcommand eval menilai setiap pilihan jawaban dan eval-gen menghasilkan jawaban bebas, keduanya lewat Backend
+    // yang sama dengan mode worker
+    if matches!(command, Some(Command::Eval(_) | Command::EvalGen(_)))
+        && (prompts.is_some() || args.mmlu_dir.is_some() || args.worker)
+    {
+        anyhow::bail!(
+            "eval and eval-gen cannot be used with --prompt, --messages, --batch-file, --mmlu-dir or --worker"
+        )
+    }
+    // Sidik jari model untuk journal eval dan MMLU dihitung dari path dan ukuran file serta argumen yang mengubah hasil
+    let fingerprint = || -> Result<String> {
+        let mut files = filenames.clone();
+        files.push(tokenizer_filename.clone());
+        let description = format!(
+            "model: {:?}, quantized: {}, dtype: {:?}, lora: {:?}, seed: {}, temperature: {:?}, top_p: {:?}, repeat_penalty: {}, repeat_last_n: {}",
+            args.model,
+            args.quantized,
+            args.dtype,
+            args.lora,
+            args.seed,
+            args.temperature,
+            args.top_p,
+            args.repeat_penalty,
+            args.repeat_last_n
+        );
+        journal::model_fingerprint(&description, &files)
+    };
+    if let Some(Command::Eval(eval_args)) = command {
+        let mut pipeline = new_pipeline(model, tokenizer);
+        return eval::run(&mut pipeline, &eval_args, &fingerprint()?);
+    }
+    if let Some(Command::EvalGen(gen_args)) = command {
+        let mut pipeline = new_pipeline(model, tokenizer);
+        return gen_eval::run(&mut pipeline, &gen_args, &fingerprint()?);
+    }
+
+    // Pada mode worker model dimuat sekali lalu request dilayani sampai stdin ditutup
+    if args.worker {
+        if prompts.is_some() || args.mmlu_dir.is_some() {
+            anyhow::bail!(
+                "--worker cannot be used with --prompt, --messages, --batch-file or --mmlu-dir"
+            )
+        }
+        let mut pipeline = new_pipeline(model, tokenizer);
+        let limits = worker::Limits {
+            max_tokens: args.sample_len,
+            max_time: args.max_time,
+            max_prompt_time: args.max_prompt_time,
+        };
+        log!("worker ready, reading requests from stdin");
+        let (stdin, stdout) = (std::io::stdin(), std::io::stdout());
+        return worker::serve(&mut pipeline, limits, stdin.lock(), stdout.lock());
+    }
 
     // Jika prompt atau mmlu_dir diberikan oleh pengguna, maka akan menjalankan generasi teks berdasarkan prompt atau mmlu_dir yang diberikan oleh pengguna (untuk menjalankan generasi teks)
-    match (args.prompt, args.mmlu_dir) {
+    match (prompts, args.mmlu_dir) {
         (None, None) | (Some(_), Some(_)) => {
-            anyhow::bail!("exactly one of --prompt and --mmlu-dir must be specified")
+            anyhow::bail!(
+                "exactly one of --prompt (or --messages), --batch-file, --mmlu-dir and --worker must be specified"
+            )
         }
-        (Some(prompt), None) => {
-            let mut pipeline = TextGeneration::new(
+        (Some(prompts), None) => {
+            let mut pipeline = new_pipeline(model, tokenizer);
+            budget::install_ctrlc_handler()?;
+            let mut results = vec![];
+            // Batas waktu berlaku untuk setiap prompt (atau setiap batch jika --batch-size lebih dari 1)
+            for chunk in prompts.chunks(args.batch_size.max(1)) {
+                let budget = budget::Budget::new(args.max_time, args.max_prompt_time);
+                if chunk.len() > 1 {
+                    results.extend(pipeline.run_batch(chunk, args.sample_len, &budget)?);
+                } else {
+                    results.push(pipeline.run(&chunk[0], args.sample_len, &budget)?);
+                }
+                if let Some((_, budget::FinishReason::Cancelled)) = results.last() {
+                    break;
+                }
+            }
+            if args.batch_file.is_some() {
+                println!("{}", pipeline.prefix.summary());
+            }
+            if let Some(path) = args.batch_output.as_ref() {
+                let mut lines = String::new();
+                for (prompt, (output, finish_reason)) in prompts.iter().zip(results.iter()) {
+                    let line = serde_json::json!({
+                        "prompt": prompt,
+                        "output": output,
+                        "finish_reason": finish_reason.to_string(),
+                    });
+                    lines.push_str(&format!("{line}\n"));
+                }
+                std::fs::write(path, lines)?;
+                println!("wrote {} results to {path}", results.len());
+            }
+            // Menulis manifest run jika diminta, output batch digabung per baris
+            if let Some(path) = args.manifest {
+                let mut files = filenames.clone();
+                files.push(tokenizer_filename);
+                if phi {
+                    files.push(repo.get("config.json")?);
+                }
+                if let Some(batch_file) = args.batch_file.as_ref() {
+                    files.push(std::path::PathBuf::from(batch_file));
+                }
+                let output = results
+                    .iter()
+                    .map(|(output, _)| output.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let finish_reason = match results.last() {
+                    Some((_, finish_reason)) => *finish_reason,
+                    None => budget::FinishReason::Length,
+                };
+                manifest::Manifest::new(&build, &files, &output, finish_reason)?.save(path)?;
+            }
+        }
+        (None, Some(mmlu_dir)) => {
+            // Hash journal MMLU mencakup model, kalimat pembuka prompt dan jumlah contoh few-shot
+            let lead = args.mmlu_template.lead();
+            let template = journal::hash(&[lead, &args.mmlu_shots.to_string()]);
+            let journal = journal::Journal::open(
+                args.mmlu_journal.as_deref().map(std::path::Path::new),
+                args.resume,
+                format!("{}-{template}", fingerprint()?),
+            )?;
+            mmlu(
                 model,
                 tokenizer,
-                args.seed,
-                args.temperature,
-                args.top_p,
-                args.repeat_penalty,
-                args.repeat_last_n,
-                args.verbose_prompt,
                 &device,
-            );
-            pipeline.run(&prompt, args.sample_len)?;
+                mmlu_dir,
+                args.mmlu_shots,
+                args.mmlu_template,
+                args.batch_size,
+                args.mmlu_output.as_deref(),
+                journal,
+                &calibration::Options {
+                    bins: args.mmlu_bins,
+                    thresholds: args.mmlu_thresholds,
+                    reliability: args.mmlu_reliability,
+                    temperature_scaling: args.mmlu_temperature_scaling,
+                },
+            )?
         }
-        (None, Some(mmlu_dir)) => mmlu(model, tokenizer, &device, mmlu_dir)?,
     }
     Ok(())
 }
 
-// Fungsi untuk menjalankan Multiple Choice Question (MCQ) berdasarkan mmlu_dir yang diberikan oleh pengguna (untuk menjalankan MCQ)
+// Fungsi untuk menjalankan satu forward dengan satu token untuk mengecek apakah tipe data model didukung
+fn probe(model: &mut Model, device: &Device) -> Result<()> {
+    let input = Tensor::new(&[[0u32]], device)?;
+    let logits = model.forward(&input)?.to_dtype(DType::F32)?;
+    logits.sum_all()?.to_scalar::<f32>()?;
+    model.clear_kv_cache();
+    Ok(())
+}
+
+// Struct untuk satu baris file batch
+#[derive(Debug, serde::Deserialize)]
+struct BatchPrompt {
+    prompt: String,
+}
+
+// Fungsi untuk membaca prompt dari file batch JSONL
+fn load_batch(path: &str) -> Result<Vec<String>> {
+    let mut prompts = vec![];
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item: BatchPrompt = serde_json::from_str(line)
+            .map_err(|err| anyhow::anyhow!("{path} line {}: {err}", index + 1))?;
+        prompts.push(item.prompt);
+    }
+    if prompts.is_empty() {
+        anyhow::bail!("no prompts in {path}")
+    }
+    Ok(prompts)
+}
+
+// Fungsi untuk membaca soal MMLU dari file CSV, setiap soal berisi pertanyaan dengan empat pilihan dan jawabannya
+fn mmlu_questions<P: AsRef<std::path::Path>>(
+    path: P,
+    template: indommlu::PromptTemplate,
+) -> Result<Vec<(String, String)>> {
+    let file = std::fs::File::open(path)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(file);
+    let mut questions = vec![];
+    for row in reader.records() {
+        let row = match row {
+            Err(_) => continue,
+            Ok(row) => row,
+        };
+        if row.len() < 6 {
+            continue;
+        }
+        let question = format!(
+            "{}\nA. {}\nB. {}\nC. {}\nD. {}\n{}\n",
+            &row[0],
+            &row[1],
+            &row[2],
+            &row[3],
+            &row[4],
+            template.answer_cue()
+        );
+        questions.push((question, row[5].to_string()));
+    }
+    Ok(questions)
+}
+
+// Fungsi untuk membuat header prompt MMLU satu subjek dengan contoh few-shot yang sudah dijawab, kalimat pembukanya
+// diambil dari template
+fn mmlu_header(
+    template: indommlu::PromptTemplate,
+    theme: &str,
+    examples: &[&(String, String)],
+) -> String {
+    let mut header = format!("{} {theme}.\n", template.lead());
+    for (question, answer) in examples.iter() {
+        header.push_str(&format!("{question}{answer}\n\n"));
+    }
+    header
+}
+
+// Fungsi untuk menghitung logits token jawaban (A, B, C, D) setelah setiap prompt, prompt diproses per batch dan
+// prefix yang sama dengan prompt sebelumnya tidak diproses ulang
+fn mmlu_logits(
+    model: &mut Model,
+    prefix: &mut prefix_cache::PrefixCache,
+    tokenizer: &Tokenizer,
+    device: &Device,
+    prompts: &[String],
+    choices: &[u32],
+    batch_size: usize,
+) -> Result<Vec<Vec<f32>>> {
+    let mut all = vec![];
+    for chunk in prompts.chunks(batch_size.max(1)) {
+        let mut batch = vec![];
+        for prompt in chunk {
+            let tokens = tokenizer.encode(prompt.as_str(), true).map_err(E::msg)?;
+            batch.push(tokens.get_ids().to_vec());
+        }
+        for logits in prefix.prefill_batch(model, &batch, device)? {
+            let logits: Vec<f32> = logits.squeeze(0)?.to_dtype(DType::F32)?.to_vec1()?;
+            all.push(choices.iter().map(|&id| logits[id as usize]).collect());
+        }
+    }
+    Ok(all)
+}
+
+// Fungsi untuk memilih jawaban dari logits A-D, jika logits sama pilihan yang lebih akhir yang dipilih
+fn mmlu_answer(logits: &[f32]) -> &'static str {
+    let (pr_a, pr_b, pr_c, pr_d) = (logits[0], logits[1], logits[2], logits[3]);
+    if pr_a > pr_b && pr_a > pr_c && pr_a > pr_d {
+        "A"
+    } else if pr_b > pr_c && pr_b > pr_d {
+        "B"
+    } else if pr_c > pr_d {
+        "C"
+    } else {
+        "D"
+    }
+}
+
+// Fungsi untuk menjalankan Multiple Choice Question (MCQ) berdasarkan mmlu_dir yang diberikan oleh pengguna (untuk menjalankan MCQ),
+// contoh few-shot diambil dari direktori dev di sebelah mmlu_dir. Soal yang sudah ada di journal tidak dinilai ulang.
+// Logits A-D setiap soal dipakai untuk laporan kalibrasi, temperature scaling di-fit pada soal dev
+#[allow(clippy::too_many_arguments)]
 fn mmlu<P: AsRef<std::path::Path>>(
     mut model: Model,
     tokenizer: Tokenizer,
     device: &Device,
     mmlu_dir: P,
+    shots: usize,
+    template: indommlu::PromptTemplate,
+    batch_size: usize,
+    output: Option<&str>,
+    mut journal: journal::Journal,
+    options: &calibration::Options,
 ) -> anyhow::Result<()> {
+    let choices = ["A", "B", "C", "D"]
+        .iter()
+        .map(|c| match tokenizer.token_to_id(c) {
+            Some(id) => Ok(id),
+            None => anyhow::bail!("no token for the answer {c}"),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let dev_dir = match mmlu_dir.as_ref().parent() {
+        Some(parent) => parent.join("dev"),
+        None => std::path::PathBuf::from("dev"),
+    };
+    // Prompt dengan subjek dan contoh few-shot yang sama berbagi prefix, sehingga prefix tersebut hanya diproses sekali
+    let mut prefix = prefix_cache::PrefixCache::new();
+    let mut lines = String::new();
+    let (mut total, mut correct) = (0usize, 0usize);
+    let (mut scored, mut dev_scored) = (vec![], vec![]);
+    if let Some(summary) = journal.summary() {
+        println!("{summary}");
+    }
     for dir_entry in mmlu_dir.as_ref().read_dir()?.flatten() {
         let dir_entry = dir_entry.path();
-        let theme = match dir_entry.file_stem().and_then(|v| v.to_str()) {
+        let subject = match dir_entry.file_stem().and_then(|v| v.to_str()) {
             None => "".to_string(),
-            Some(v) => match v.strip_suffix("_test") {
-                None => v.replace('_', " "),
-                Some(v) => v.replace('_', " "),
-            },
+            Some(v) => v.strip_suffix("_test").unwrap_or(v).to_string(),
         };
+        let theme = subject.replace('_', " ");
         if dir_entry.extension().as_ref().and_then(|v| v.to_str()) != Some("csv") {
             continue;
         }
         println!("reading {dir_entry:?}");
-        let dir_entry = std::fs::File::open(dir_entry)?;
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_reader(dir_entry);
-        let token_a = tokenizer.token_to_id("A").unwrap();
-        let token_b = tokenizer.token_to_id("B").unwrap();
-        let token_c = tokenizer.token_to_id("C").unwrap();
-        let token_d = tokenizer.token_to_id("D").unwrap();
-        for row in reader.records() {
-            let row = match row {
-                Err(_) => continue,
-                Ok(row) => row,
-            };
-            if row.len() < 5 {
-                continue;
-            }
-            let question = row.get(0).unwrap();
-            let answer_a = row.get(1).unwrap();
-            let answer_b = row.get(2).unwrap();
-            let answer_c = row.get(3).unwrap();
-            let answer_d = row.get(4).unwrap();
-            let answer = row.get(5).unwrap();
-            let prompt = format!(
-                    "{} {theme}.\n{question}\nA. {answer_a}\nB. {answer_b}\nC. {answer_c}\nD. {answer_d}\nAnswer:\n",
-                    "The following are multiple choice questions (with answers) about"
-                );
-            let tokens = tokenizer.encode(prompt.as_str(), true).map_err(E::msg)?;
-            let tokens = tokens.get_ids().to_vec();
-            let input = Tensor::new(tokens, device)?.unsqueeze(0)?;
-            let logits = match &mut model {
-                Model::MixFormer(m) => {
-                    m.clear_kv_cache();
-                    m.forward(&input)?
-                }
-                Model::Phi(m) => {
-                    m.clear_kv_cache();
-                    m.forward(&input)?
-                }
-                Model::Quantized(m) => {
-                    m.clear_kv_cache();
-                    m.forward(&input)?
-                }
-            };
-            let logits = logits.squeeze(0)?.to_dtype(DType::F32)?;
-            let logits_v: Vec<f32> = logits.to_vec1()?;
-            let pr_a = logits_v[token_a as usize];
-            let pr_b = logits_v[token_b as usize];
-            let pr_c = logits_v[token_c as usize];
-            let pr_d = logits_v[token_d as usize];
-            let model_answer = if pr_a > pr_b && pr_a > pr_c && pr_a > pr_d {
-                "A"
-            } else if pr_b > pr_c && pr_b > pr_d {
-                "B"
-            } else if pr_c > pr_d {
-                "C"
-            } else {
-                "D"
-            };
+        let examples = if shots > 0 || options.temperature_scaling {
+            let dev_file = dev_dir.join(format!("{subject}_dev.csv"));
+            mmlu_questions(&dev_file, template).map_err(|err| {
+                anyhow::anyhow!("cannot read the few-shot file {dev_file:?}: {err}")
+            })?
+        } else {
+            vec![]
+        };
+        let header = mmlu_header(
+            template,
+            &theme,
+            &examples.iter().take(shots).collect::<Vec<_>>(),
+        );
 
+        // Soal yang belum ada di journal diproses per batch, header yang sama hanya diproses sekali untuk setiap batch
+        let questions = mmlu_questions(&dir_entry, template)?;
+        let file = dir_entry.display().to_string();
+        let mut results = (0..questions.len())
+            .map(|row| journal.get(&file, row).cloned())
+            .collect::<Vec<_>>();
+        let pending = (0..questions.len())
+            .filter(|row| results[*row].is_none())
+            .collect::<Vec<_>>();
+        let prompts = pending
+            .iter()
+            .map(|&row| format!("{header}{}", questions[row].0))
+            .collect::<Vec<_>>();
+        let logits = mmlu_logits(
+            &mut model,
+            &mut prefix,
+            &tokenizer,
+            device,
+            &prompts,
+            &choices,
+            batch_size,
+        )?;
+        for ((&row, prompt), logits) in pending.iter().zip(prompts).zip(logits) {
+            let answer = &questions[row].1;
+            let model_answer = mmlu_answer(&logits);
             println!("{prompt}\n -> {model_answer} vs {answer}");
+            let line = serde_json::json!({
+                "id": row,
+                "subject": subject,
+                "gold": answer,
+                "pred": model_answer,
+                "correct": *answer == model_answer,
+                "logits": logits,
+            });
+            journal.record(&file, row, &line)?;
+            results[row] = Some(line);
+        }
+        // Hasil dari journal dan hasil baru dihitung bersama, urut sesuai baris CSV
+        for line in results.into_iter().flatten() {
+            total += 1;
+            correct += (line["correct"].as_bool() == Some(true)) as usize;
+            let logits = line["logits"].as_array().map(|logits| {
+                logits
+                    .iter()
+                    .filter_map(|l| l.as_f64().map(|l| l as f32))
+                    .collect::<Vec<_>>()
+            });
+            if let (Some(logits), Some(answer)) = (logits, line["gold"].as_str()) {
+                scored.extend(calibration::Scored::new(logits, answer));
+            }
+            lines.push_str(&format!("{line}\n"));
+        }
+
+        // Setiap soal dev dijawab dengan contoh few-shot dari soal dev lainnya, hasilnya untuk temperature scaling
+        if options.temperature_scaling {
+            let prompts = (0..examples.len())
+                .map(|row| {
+                    let others = examples
+                        .iter()
+                        .enumerate()
+                        .filter(|(other, _)| *other != row)
+                        .map(|(_, example)| example)
+                        .take(shots)
+                        .collect::<Vec<_>>();
+                    format!(
+                        "{}{}",
+                        mmlu_header(template, &theme, &others),
+                        examples[row].0
+                    )
+                })
+                .collect::<Vec<_>>();
+            let logits = mmlu_logits(
+                &mut model,
+                &mut prefix,
+                &tokenizer,
+                device,
+                &prompts,
+                &choices,
+                batch_size,
+            )?;
+            for (logits, (_, answer)) in logits.into_iter().zip(examples.iter()) {
+                dev_scored.extend(calibration::Scored::new(logits, answer));
+            }
         }
     }
+    println!("{}", prefix.summary());
+    println!(
+        "accuracy {:.4} ({correct}/{total})",
+        correct as f64 / total.max(1) as f64
+    );
+    if let Some(path) = output {
+        std::fs::write(path, lines)?;
+        println!("wrote {total} results to {path}");
+    }
+
+    // Laporan kalibrasi dari probabilitas softmax logits A-D, sebelum dan sesudah temperature scaling
+    if scored.is_empty() {
+        return Ok(());
+    }
+    let mut reports = vec![calibration::report(
+        &scored,
+        1.,
+        options.bins,
+        &options.thresholds,
+    )];
+    if options.temperature_scaling {
+        if dev_scored.is_empty() {
+            anyhow::bail!("no dev questions to fit the temperature on")
+        }
+        let temperature = calibration::fit_temperature(&dev_scored);
+        println!(
+            "fitted temperature {temperature:.3} on {} dev questions",
+            dev_scored.len()
+        );
+        reports.push(calibration::report(
+            &scored,
+            temperature,
+            options.bins,
+            &options.thresholds,
+        ));
+    }
+    for report in reports.iter() {
+        report.print();
+    }
+    if let Some(path) = options.reliability.as_ref() {
+        calibration::write_reliability(path, &reports)?;
+        println!("wrote the reliability diagram to {path}");
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,123 @@
+// Modul untuk memakai ulang kv cache dari prefix token yang sama antar prompt (MMLU, few-shot, dan file batch)
+use anyhow::Result;
+
+use candle_core::{Device, Tensor}; // Untuk operasi tensor
+
+use crate::Model;
+
+// Struct untuk mencatat token yang tersimpan di kv cache model beserta statistik pemakaian ulangnya
+#[derive(Debug, Default)]
+pub struct PrefixCache {
+    tokens: Vec<u32>,
+    reused_tokens: usize,
+    prompt_tokens: usize,
+}
+
+// Fungsi untuk menghitung panjang prefix yang sama dari dua urutan token
+fn common_prefix_len(a: &[u32], b: &[u32]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count()
+}
+
+impl PrefixCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Memproses prompt dengan hanya menjalankan token setelah prefix yang sudah ada di kv cache,
+    // mengembalikan logits token terakhir
+    pub fn prefill(
+        &mut self,
+        model: &mut Model,
+        tokens: &[u32],
+        device: &Device,
+    ) -> Result<Tensor> {
+        if tokens.is_empty() {
+            anyhow::bail!("cannot prefill an empty prompt")
+        }
+        // Minimal satu token tetap diproses agar logits token terakhir tersedia
+        let shared = common_prefix_len(&self.tokens, tokens).min(tokens.len() - 1);
+        let shared = model.truncate_kv_cache(shared)?;
+        let input = Tensor::new(&tokens[shared..], device)?.unsqueeze(0)?;
+        let logits = model.forward(&input)?;
+        self.tokens = tokens.to_vec();
+        self.reused_tokens += shared;
+        self.prompt_tokens += tokens.len();
+        Ok(logits)
+    }
+
+    // Memproses beberapa prompt sekaligus, mengembalikan logits token terakhir setiap prompt. Pada model Phi
+    // dengan kv cache yang bisa dipotong, prefix yang sama di semua prompt diproses sekali lalu cache-nya
+    // diulang untuk setiap baris dan sisa prompt diproses dalam satu batch dengan padding di kanan,
+    // model lain memproses prompt satu per satu
+    pub fn prefill_batch(
+        &mut self,
+        model: &mut Model,
+        batch: &[Vec<u32>],
+        device: &Device,
+    ) -> Result<Vec<Tensor>> {
+        if batch.len() < 2 || !matches!(model, Model::Cached(..)) {
+            return batch
+                .iter()
+                .map(|tokens| self.prefill(model, tokens, device))
+                .collect();
+        }
+        let Model::Cached(phi, cache) = model else {
+            unreachable!()
+        };
+        let min_len = batch.iter().map(|tokens| tokens.len()).min().unwrap_or(0);
+        if min_len == 0 {
+            anyhow::bail!("cannot prefill an empty prompt")
+        }
+        // Prefix yang sama di semua prompt, minimal satu token setiap prompt tetap diproses
+        let shared = batch[1..]
+            .iter()
+            .map(|tokens| common_prefix_len(&batch[0], tokens))
+            .min()
+            .unwrap_or(0)
+            .min(min_len - 1);
+        let prefix = &batch[0][..shared];
+        let reused = common_prefix_len(&self.tokens, prefix);
+        cache.truncate(reused)?;
+        if shared > reused {
+            let input = Tensor::new(&prefix[reused..], device)?.unsqueeze(0)?;
+            phi.forward_cached(&input, cache)?;
+        }
+        self.tokens = prefix.to_vec();
+
+        let suffix_len = batch
+            .iter()
+            .map(|tokens| tokens.len() - shared)
+            .max()
+            .unwrap_or(0);
+        let mut input = vec![];
+        let mut last = vec![];
+        for tokens in batch {
+            let suffix = &tokens[shared..];
+            input.extend_from_slice(suffix);
+            input.extend(std::iter::repeat_n(0, suffix_len - suffix.len()));
+            last.push(suffix.len() - 1);
+        }
+        let input = Tensor::from_vec(input, (batch.len(), suffix_len), device)?;
+        let logits = phi.forward_at(&input, &mut cache.repeat(batch.len())?, &last)?;
+
+        self.reused_tokens += reused + shared * (batch.len() - 1);
+        self.prompt_tokens += batch.iter().map(|tokens| tokens.len()).sum::<usize>();
+        (0..batch.len())
+            .map(|row| Ok(logits.get(row)?.unsqueeze(0)?))
+            .collect()
+    }
+
+    // Mencatat token yang ditambahkan ke kv cache saat generasi
+    pub fn push(&mut self, token: u32) {
+        self.tokens.push(token)
+    }
+
+    // Ringkasan jumlah token prompt yang tidak perlu diproses ulang
+    pub fn summary(&self) -> String {
+        let percent = 100. * self.reused_tokens as f64 / self.prompt_tokens.max(1) as f64;
+        format!(
+            "prefix cache: reused {} of {} prompt tokens ({percent:.1}%)",
+            self.reused_tokens, self.prompt_tokens
+        )
+    }
+}
@@ -0,0 +1,168 @@
+// Modul untuk subcommand quantize model Phi. Kuantisasi dan penulisan GGUF ada di common::quantize, di sini hanya
+// penggantian nama tensor Phi ke format MixFormer yang bisa dimuat dengan --quantized
+use anyhow::Result;
+use std::collections::HashMap;
+
+use candle_core::Tensor; // Untuk operasi tensor
+
+pub use common::quantize::QuantizeArgs;
+
+// Fungsi untuk mengganti nama tensor dari format Phi (transformers) ke format MixFormer yang dipakai QMixFormer
+fn to_mixformer(mut tensors: HashMap<String, Tensor>, v2: bool) -> Result<HashMap<String, Tensor>> {
+    if !tensors.contains_key("model.embed_tokens.weight") {
+        // Bobot sudah dalam format MixFormer
+        return Ok(tensors);
+    }
+    let n_layer = (0..)
+        .take_while(|i| tensors.contains_key(&format!("model.layers.{i}.mlp.fc1.weight")))
+        .count();
+    let (embd, head) = if v2 {
+        ("transformer.embd".to_string(), "lm_head".to_string())
+    } else {
+        ("layers.0".to_string(), format!("layers.{}", n_layer + 1))
+    };
+    let mut take = |name: &str| {
+        tensors
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("missing tensor {name}"))
+    };
+    let mut out = HashMap::new();
+    out.insert(
+        format!("{embd}.wte.weight"),
+        take("model.embed_tokens.weight")?,
+    );
+    for i in 0..n_layer {
+        let src = format!("model.layers.{i}");
+        let dst = if v2 {
+            format!("transformer.h.{i}")
+        } else {
+            format!("layers.{}", i + 1)
+        };
+        for suffix in ["weight", "bias"] {
+            out.insert(
+                format!("{dst}.ln.{suffix}"),
+                take(&format!("{src}.input_layernorm.{suffix}"))?,
+            );
+            let q = take(&format!("{src}.self_attn.q_proj.{suffix}"))?;
+            let k = take(&format!("{src}.self_attn.k_proj.{suffix}"))?;
+            let v = take(&format!("{src}.self_attn.v_proj.{suffix}"))?;
+            out.insert(
+                format!("{dst}.mixer.Wqkv.{suffix}"),
+                Tensor::cat(&[q, k, v], 0)?,
+            );
+            out.insert(
+                format!("{dst}.mixer.out_proj.{suffix}"),
+                take(&format!("{src}.self_attn.dense.{suffix}"))?,
+            );
+            for fc in ["fc1", "fc2"] {
+                out.insert(
+                    format!("{dst}.mlp.{fc}.{suffix}"),
+                    take(&format!("{src}.mlp.{fc}.{suffix}"))?,
+                );
+            }
+        }
+    }
+    for suffix in ["weight", "bias"] {
+        out.insert(
+            format!("{head}.ln.{suffix}"),
+            take(&format!("model.final_layernorm.{suffix}"))?,
+        );
+        out.insert(
+            format!("{head}.linear.{suffix}"),
+            take(&format!("lm_head.{suffix}"))?,
+        );
+    }
+    Ok(out)
+}
+
+// Fungsi utama subcommand quantize, v2 menentukan format nama tensor MixFormer (new_v2 atau new)
+pub fn run(args: QuantizeArgs, v2: bool) -> Result<()> {
+    common::quantize::run(args, "mixformer", |tensors| to_mixformer(tensors, v2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::{Device, IndexOp};
+
+    // Bobot Phi (format transformers) kecil dengan satu layer, setiap tensor berisi nilai yang berbeda
+    fn phi_tensors() -> Result<HashMap<String, Tensor>> {
+        let mut names = vec![
+            "model.embed_tokens.weight".to_string(),
+            "model.final_layernorm.weight".to_string(),
+            "model.final_layernorm.bias".to_string(),
+            "lm_head.weight".to_string(),
+            "lm_head.bias".to_string(),
+        ];
+        for module in [
+            "input_layernorm",
+            "self_attn.q_proj",
+            "self_attn.k_proj",
+            "self_attn.v_proj",
+            "self_attn.dense",
+            "mlp.fc1",
+            "mlp.fc2",
+        ] {
+            for suffix in ["weight", "bias"] {
+                names.push(format!("model.layers.0.{module}.{suffix}"));
+            }
+        }
+        names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Ok((name, Tensor::full(i as f32, (2, 4), &Device::Cpu)?)))
+            .collect()
+    }
+
+    #[test]
+    fn phi_tensors_are_renamed_to_mixformer() -> Result<()> {
+        let tensors = phi_tensors()?;
+        let value = |t: &Tensor| -> Result<Vec<f32>> { Ok(t.i((.., 0))?.to_vec1()?) };
+        let expected = |name: &str| -> Result<Vec<f32>> { value(&tensors[name]) };
+        let q = expected("model.layers.0.self_attn.q_proj.weight")?;
+        let k = expected("model.layers.0.self_attn.k_proj.weight")?;
+        let v = expected("model.layers.0.self_attn.v_proj.weight")?;
+
+        let v2 = to_mixformer(tensors.clone(), true)?;
+        let mut names = v2.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        let mut want = vec!["transformer.embd.wte.weight".to_string()];
+        for suffix in ["weight", "bias"] {
+            for name in ["ln", "mixer.Wqkv", "mixer.out_proj", "mlp.fc1", "mlp.fc2"] {
+                want.push(format!("transformer.h.0.{name}.{suffix}"));
+            }
+            want.push(format!("lm_head.ln.{suffix}"));
+            want.push(format!("lm_head.linear.{suffix}"));
+        }
+        want.sort();
+        assert_eq!(names, want);
+        // Wqkv adalah q, k dan v yang disambung pada dimensi 0
+        assert_eq!(
+            value(&v2["transformer.h.0.mixer.Wqkv.weight"])?,
+            [q, k, v].concat()
+        );
+        assert_eq!(
+            value(&v2["transformer.h.0.mixer.out_proj.weight"])?,
+            expected("model.layers.0.self_attn.dense.weight")?
+        );
+        assert_eq!(
+            value(&v2["lm_head.ln.bias"])?,
+            expected("model.final_layernorm.bias")?
+        );
+
+        // Format v1 memakai layers.0 untuk embedding dan layers.{n + 1} untuk head
+        let v1 = to_mixformer(tensors.clone(), false)?;
+        assert!(v1.contains_key("layers.0.wte.weight"));
+        assert!(v1.contains_key("layers.1.mixer.Wqkv.bias"));
+        assert!(v1.contains_key("layers.2.linear.weight"));
+        assert_eq!(v1.len(), v2.len());
+
+        // Bobot yang sudah berformat MixFormer tidak diubah, tensor yang hilang adalah error
+        assert_eq!(to_mixformer(v2.clone(), true)?.len(), v2.len());
+        let mut missing = tensors;
+        missing.remove("model.layers.0.mlp.fc2.bias");
+        let err = to_mixformer(missing, true).unwrap_err().to_string();
+        assert!(err.contains("model.layers.0.mlp.fc2.bias"), "{err}");
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+// Modul untuk detokenisasi bertahap dengan tokenizer Hugging Face, TokenStream sendiri ada di common::token_stream
+use anyhow::Result;
+use common::token_stream::{Decode, TokenStream};
+use tokenizers::Tokenizer;
+
+// Pembungkus tokenizer Hugging Face agar bisa mengimplementasikan Decode dari crate common
+#[derive(Clone, Copy)]
+pub struct HfDecoder<'a>(pub &'a Tokenizer);
+
+// Tokenizer Hugging Face (byte-level BPE) sudah mengganti byte yang belum lengkap dengan U+FFFD
+impl Decode for HfDecoder<'_> {
+    fn decode_lossy(&self, tokens: &[u32]) -> Result<String> {
+        self.0.decode(tokens, true).map_err(anyhow::Error::msg)
+    }
+}
+
+// Fungsi untuk membuat TokenStream dari tokenizer Hugging Face
+pub fn stream(tokenizer: &Tokenizer) -> TokenStream<HfDecoder<'_>> {
+    TokenStream::new(HfDecoder(tokenizer))
+}
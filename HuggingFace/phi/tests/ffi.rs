@@ -0,0 +1,272 @@
+// Test integrasi C ABI: model Phi acak kecil ditulis ke direktori sementara, fungsi phi_* dipanggil langsung dari Rust,
+// lalu tests/ffi_smoke.c dikompilasi dengan compiler C terhadap libphi dan dijalankan. Hanya smoke test C yang
+// dilewati jika compiler C tidak tersedia
+use anyhow::Result;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use candle_core::{DType, Device, Tensor};
+use candle_nn::{VarBuilder, VarMap};
+use phi::ffi::*;
+use phi::lora_phi::{Config, LoraPhi};
+
+const CONFIG: &str = r#"{
+    "vocab_size": 32,
+    "hidden_size": 16,
+    "intermediate_size": 32,
+    "num_hidden_layers": 2,
+    "num_attention_heads": 2,
+    "num_key_value_heads": null,
+    "layer_norm_eps": 1e-5,
+    "rope_theta": 10000.0,
+    "partial_rotary_factor": 0.5,
+    "max_position_embeddings": 64
+}"#;
+
+// Fungsi untuk membuat tokenizer WordLevel kecil: token khusus, "hello" (2), "world" (3), lalu kata pengisi
+fn tokenizer_json() -> String {
+    let mut vocab = serde_json::Map::new();
+    for (id, word) in ["<|endoftext|>", "<unk>", "hello", "world"]
+        .iter()
+        .enumerate()
+    {
+        vocab.insert(word.to_string(), id.into());
+    }
+    for id in vocab.len()..32 {
+        vocab.insert(format!("w{id}"), id.into());
+    }
+    serde_json::json!({
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": [{
+            "id": 0, "content": "<|endoftext|>", "single_word": false, "lstrip": false,
+            "rstrip": false, "normalized": false, "special": true
+        }],
+        "normalizer": null,
+        "pre_tokenizer": {"type": "Whitespace"},
+        "post_processor": null,
+        "decoder": {"type": "WordPiece", "prefix": "##", "cleanup": false},
+        "model": {"type": "WordLevel", "vocab": vocab, "unk_token": "<unk>"}
+    })
+    .to_string()
+}
+
+// Fungsi untuk menulis config.json, tokenizer.json dan bobot acak model.safetensors
+fn write_model(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join("config.json"), CONFIG)?;
+    std::fs::write(dir.join("tokenizer.json"), tokenizer_json())?;
+    let device = Device::Cpu;
+    let config: Config = serde_json::from_str(CONFIG)?;
+    let varmap = VarMap::new();
+    LoraPhi::load(
+        &config,
+        VarBuilder::from_varmap(&varmap, DType::F32, &device),
+    )?;
+    for var in varmap.all_vars() {
+        var.set(&Tensor::randn(0f32, 0.5, var.shape(), &device)?)?;
+    }
+    varmap.save(dir.join("model.safetensors"))?;
+    Ok(())
+}
+
+// Fungsi untuk mencari direktori libphi hasil build: di samping executable test (deps) atau satu tingkat di atasnya
+fn library_dir() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let name = format!(
+        "{}phi{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    for dir in exe.ancestors().skip(1).take(2) {
+        if dir.join(&name).exists() {
+            return Ok(dir.to_path_buf());
+        }
+    }
+    anyhow::bail!("cannot find {name} next to {exe:?}")
+}
+
+// Fungsi untuk membaca pesan error terakhir dari phi_last_error
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(phi_last_error()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Callback yang mengumpulkan potongan teks ke Vec<String> di user_data dan berhenti setelah stop_after potongan
+struct Pieces {
+    text: Vec<String>,
+    stop_after: usize,
+}
+
+unsafe extern "C" fn collect(text: *const c_char, user_data: *mut c_void) -> i32 {
+    let pieces = &mut *(user_data as *mut Pieces);
+    pieces
+        .text
+        .push(CStr::from_ptr(text).to_string_lossy().into_owned());
+    (pieces.text.len() >= pieces.stop_after) as i32
+}
+
+// Fungsi untuk menjalankan phi_generate dan mengembalikan status beserta potongan teksnya
+fn generate(
+    model: *const PhiModel,
+    prompt: &str,
+    params: Option<&PhiSamplingParams>,
+    stop_after: usize,
+) -> (PhiStatus, Vec<String>) {
+    let prompt = CString::new(prompt).unwrap();
+    let params = params.map_or(std::ptr::null(), |params| params as *const _);
+    let mut pieces = Pieces {
+        text: vec![],
+        stop_after,
+    };
+    let status = unsafe {
+        phi_generate(
+            model,
+            prompt.as_ptr(),
+            params,
+            Some(collect),
+            &mut pieces as *mut Pieces as *mut c_void,
+        )
+    };
+    (status, pieces.text)
+}
+
+#[test]
+fn rust_api_test() -> Result<()> {
+    let out = std::env::temp_dir().join(format!("phi-ffi-rust-{}", std::process::id()));
+    write_model(&out)?;
+
+    let mut model = std::ptr::null_mut();
+    let missing = CString::new("/nonexistent/phi-model")?;
+    let status = unsafe { phi_model_load(missing.as_ptr(), &mut model) };
+    assert_eq!(status, PhiStatus::Load);
+    assert!(model.is_null());
+    assert!(!last_error().is_empty());
+
+    let dir = CString::new(out.to_str().unwrap())?;
+    let status = unsafe { phi_model_load(dir.as_ptr(), &mut model) };
+    assert_eq!(status, PhiStatus::Ok, "{}", last_error());
+    assert!(!model.is_null());
+
+    // Buffer yang terlalu kecil tidak ditulis, tetapi panjang yang diperlukan tetap dikembalikan
+    let text = CString::new("hello world")?;
+    let mut len = 0;
+    let status = unsafe { phi_tokenize(model, text.as_ptr(), std::ptr::null_mut(), 0, &mut len) };
+    assert_eq!(status, PhiStatus::BufferTooSmall);
+    assert_eq!(len, 2);
+    assert_eq!(last_error(), "2 tokens do not fit in 0 entries");
+    let mut ids = [u32::MAX; 8];
+    let status = unsafe { phi_tokenize(model, text.as_ptr(), ids.as_mut_ptr(), 1, &mut len) };
+    assert_eq!(status, PhiStatus::BufferTooSmall);
+    assert_eq!((len, ids[0]), (2, u32::MAX));
+    let status = unsafe { phi_tokenize(model, text.as_ptr(), ids.as_mut_ptr(), 8, &mut len) };
+    assert_eq!(status, PhiStatus::Ok);
+    assert_eq!(&ids[..len], &[2, 3]);
+    let status = unsafe {
+        phi_tokenize(
+            model,
+            text.as_ptr(),
+            ids.as_mut_ptr(),
+            8,
+            std::ptr::null_mut(),
+        )
+    };
+    assert_eq!(status, PhiStatus::InvalidArgument);
+    assert_eq!(last_error(), "len is null");
+
+    // Generasi greedy deterministik dan dibatasi max_tokens
+    let params = PhiSamplingParams {
+        max_tokens: 8,
+        ..phi_sampling_params_default()
+    };
+    let (status, first) = generate(model, "hello world", Some(&params), usize::MAX);
+    assert_eq!(status, PhiStatus::Ok, "{}", last_error());
+    assert!(first.len() <= 8);
+    let (_, second) = generate(model, "hello world", Some(&params), usize::MAX);
+    assert_eq!(first, second);
+
+    // Callback yang mengembalikan nilai bukan nol menghentikan generasi
+    let (status, stopped) = generate(model, "hello", None, 1);
+    assert_eq!(status, PhiStatus::Ok);
+    assert!(stopped.len() <= 1);
+
+    let (status, _) = generate(model, "", Some(&params), usize::MAX);
+    assert_eq!(status, PhiStatus::Inference);
+    let status =
+        unsafe { phi_generate(model, std::ptr::null(), &params, None, std::ptr::null_mut()) };
+    assert_eq!(status, PhiStatus::InvalidArgument);
+    assert_eq!(last_error(), "prompt is null");
+
+    unsafe {
+        phi_model_free(model);
+        phi_model_free(std::ptr::null_mut());
+    }
+    std::fs::remove_dir_all(&out)?;
+    Ok(())
+}
+
+// include/phi.h di-commit, build.rs hanya membuat header di OUT_DIR. Header yang usang membuat test gagal,
+// UPDATE_GOLDEN=1 menyalin header baru ke include/phi.h
+#[test]
+fn header_is_up_to_date() -> Result<()> {
+    let generated = std::fs::read_to_string(concat!(env!("OUT_DIR"), "/phi.h"))?;
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/phi.h");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, generated)?;
+        eprintln!("recorded {path:?}");
+        return Ok(());
+    }
+    assert!(
+        std::fs::read_to_string(&path)? == generated,
+        "{path:?} is stale, rerun with UPDATE_GOLDEN=1 and commit it"
+    );
+    Ok(())
+}
+
+#[test]
+fn c_smoke_test() -> Result<()> {
+    if !cfg!(unix) {
+        eprintln!("skipping the C smoke test on this platform");
+        return Ok(());
+    }
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out = std::env::temp_dir().join(format!("phi-ffi-{}", std::process::id()));
+    write_model(&out.join("model"))?;
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let lib_dir = library_dir()?;
+    let exe = out.join("ffi_smoke");
+    let status = Command::new(&cc)
+        .arg(manifest_dir.join("tests/ffi_smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lphi")
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-o")
+        .arg(&exe)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{cc} failed to compile tests/ffi_smoke.c"),
+        Err(err) => {
+            eprintln!("skipping the C smoke test, cannot run {cc}: {err}");
+            std::fs::remove_dir_all(&out)?;
+            return Ok(());
+        }
+    }
+
+    let output = Command::new(&exe).arg(out.join("model")).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("smoke test passed"));
+    std::fs::remove_dir_all(&out)?;
+    Ok(())
+}
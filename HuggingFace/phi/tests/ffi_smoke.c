@@ -0,0 +1,80 @@
+// Smoke test C ABI libphi: memuat model acak kecil, tokenisasi, generasi dengan callback, lalu membebaskan handle.
+// Dikompilasi dan dijalankan oleh tests/ffi.rs dengan direktori model sebagai argumen
+#include <stdio.h>
+#include <string.h>
+
+#include "phi.h"
+
+// Callback yang menghitung potongan teks dan berhenti setelah stop_after potongan
+struct Counter {
+  int pieces;
+  int stop_after;
+};
+
+static int32_t on_text(const char *text, void *user_data) {
+  struct Counter *counter = (struct Counter *)user_data;
+  counter->pieces += 1;
+  printf("%s", text);
+  return counter->pieces >= counter->stop_after;
+}
+
+static int fail(const char *what) {
+  fprintf(stderr, "%s failed: %s\n", what, phi_last_error());
+  return 1;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <model-dir>\n", argv[0]);
+    return 2;
+  }
+
+  PhiModel *missing = NULL;
+  if (phi_model_load("/nonexistent/phi-model", &missing) != PHI_STATUS_LOAD || missing != NULL ||
+      strlen(phi_last_error()) == 0) {
+    return fail("loading a missing model");
+  }
+
+  PhiModel *model = NULL;
+  if (phi_model_load(argv[1], &model) != PHI_STATUS_OK) {
+    return fail("phi_model_load");
+  }
+
+  // Panjang token ditanyakan dulu dengan buffer kosong
+  size_t len = 0;
+  if (phi_tokenize(model, "hello world", NULL, 0, &len) != PHI_STATUS_BUFFER_TOO_SMALL || len != 2) {
+    return fail("phi_tokenize without a buffer");
+  }
+  uint32_t ids[8];
+  if (phi_tokenize(model, "hello world", ids, 8, &len) != PHI_STATUS_OK || len != 2 ||
+      ids[0] != 2 || ids[1] != 3) {
+    return fail("phi_tokenize");
+  }
+
+  PhiSamplingParams params = phi_sampling_params_default();
+  params.temperature = 0.8;
+  params.max_tokens = 16;
+  struct Counter counter = {0, 1000};
+  if (phi_generate(model, "hello world", &params, on_text, &counter) != PHI_STATUS_OK) {
+    return fail("phi_generate");
+  }
+  if (counter.pieces > 16) {
+    fprintf(stderr, "phi_generate produced %d pieces for 16 tokens\n", counter.pieces);
+    return 1;
+  }
+
+  // Callback yang mengembalikan nilai bukan nol menghentikan generasi
+  struct Counter stopped = {0, 1};
+  if (phi_generate(model, "hello", NULL, on_text, &stopped) != PHI_STATUS_OK || stopped.pieces > 1) {
+    return fail("stopping phi_generate from the callback");
+  }
+
+  if (phi_generate(model, NULL, &params, on_text, &counter) != PHI_STATUS_INVALID_ARGUMENT) {
+    return fail("phi_generate without a prompt");
+  }
+
+  phi_model_free(model);
+  phi_model_free(NULL);
+  printf("\nsmoke test passed\n");
+  return 0;
+}
@@ -0,0 +1,24 @@
+// Script build untuk mencatat versi candle dari Cargo.lock agar bisa ditulis ke manifest run
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let version = std::fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| {
+            let mut lines = lock.lines();
+            while let Some(line) = lines.next() {
+                if line == "name = \"candle-core\"" {
+                    let version = lines.next()?.strip_prefix("version = ")?.trim_matches('"');
+                    // Untuk dependensi git, commit yang dipakai juga dicatat
+                    return match lines.next().and_then(|l| l.strip_prefix("source = \"git+")) {
+                        Some(source) => {
+                            Some(format!("{version} ({})", source.trim_end_matches('"')))
+                        }
+                        None => Some(version.to_string()),
+                    };
+                }
+            }
+            None
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CANDLE_VERSION={version}");
+}
@@ -0,0 +1,59 @@
+// Modul fixture untuk test offline rwkv: vocab rwkv kecil dan bobot acak deterministik yang ditulis ke safetensors.
+// Bagian ini memakai candle dari git sehingga tidak bisa memakai fitur candle di common, direktori sementara dan file
+// golden diambil dari common::fixtures
+use anyhow::Result;
+use candle_core::Tensor;
+use candle_nn::VarMap;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::Tokenizer;
+use common::compare::SplitMix;
+
+pub use common::fixtures::{assert_golden, TempDir};
+
+// Fungsi untuk menulis vocab JSON rwkv (token ke id) lalu memuatnya: karakter ASCII yang bisa dicetak dan baris baru
+// memakai id kodenya, id lain memakai kata " t{id}" agar teks hasil generasi bisa dibaca sebagai urutan token. Id 0 dan
+// EOS_TOKEN_ID (261) tidak punya teks
+pub fn tokenizer(path: &Path, vocab_size: usize) -> Result<Tokenizer> {
+    let mut vocab = serde_json::Map::new();
+    for id in 1..vocab_size as u32 {
+        let token = match id {
+            0x0a | 0x20..=0x7e => char::from(id as u8).to_string(),
+            crate::EOS_TOKEN_ID => continue,
+            _ => format!(" t{id}"),
+        };
+        vocab.insert(token, id.into());
+    }
+    std::fs::write(path, Value::Object(vocab).to_string())?;
+    Ok(Tokenizer::new(path)?)
+}
+
+// Fungsi untuk mengisi semua variabel VarMap dengan nilai uniform di [-0.5, 0.5) dari seed. Variabel diisi urut nama
+// agar bobotnya sama di setiap run (Tensor::randn di CPU tidak bisa diberi seed)
+pub fn randomize(varmap: &VarMap, seed: u64) -> Result<()> {
+    let data = varmap.data().lock().unwrap();
+    let mut names = data.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut rng = SplitMix(seed);
+    for name in names {
+        let var = &data[name];
+        let values = (0..var.elem_count())
+            .map(|_| rng.uniform() - 0.5)
+            .collect::<Vec<f32>>();
+        var.set(&Tensor::from_vec(values, var.shape(), var.device())?)?;
+    }
+    Ok(())
+}
+
+// Fungsi untuk mengubah nilai satu variabel VarMap, contoh menaikkan bias lm_head pada token eos
+pub fn update(varmap: &VarMap, name: &str, f: impl FnOnce(&mut [f32])) -> Result<()> {
+    let data = varmap.data().lock().unwrap();
+    let var = data
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("no variable {name} in the fixture"))?;
+    let mut values = var.flatten_all()?.to_vec1::<f32>()?;
+    f(&mut values);
+    var.set(&Tensor::from_vec(values, var.shape(), var.device())?)?;
+    Ok(())
+}
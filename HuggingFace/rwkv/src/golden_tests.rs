@@ -0,0 +1,454 @@
+// Test golden offline dengan model rwkv v5 dan v6 acak kecil: bobot dibuat dari seed tetap, ditulis ke safetensors
+// (dan ke GGUF lewat subcommand quantize) lalu dimuat lewat jalur yang sama dengan CLI. Output dibandingkan dengan
+// tests/golden, lihat modul fixtures
+use super::*;
+use crate::fixtures::{self, TempDir};
+use crate::worker::Backend;
+use candle_nn::VarMap;
+use candle_transformers::models::{rwkv_v5, rwkv_v6};
+use serde_json::{json, Value};
+
+// Direktori file golden crate ini, fixtures ada di crate common sehingga tidak bisa memakai CARGO_MANIFEST_DIR sendiri
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+// Vocab harus lebih besar dari EOS_TOKEN_ID (261)
+const VOCAB_SIZE: usize = 320;
+const HIDDEN_SIZE: usize = 32;
+const PROMPT: &str = "Hello world";
+// Seed bobot acak fixture, dipilih agar generasi greedy v5 dan v6 dari PROMPT tidak langsung berulang
+const SEED: u64 = 45;
+
+// Config rwkv kecil yang dipakai v5 dan v6, dimensi kelipatan 32 agar bisa dikuantisasi q8_0
+const CONFIG: &str = r#"{
+    "vocab_size": 320,
+    "hidden_size": 32,
+    "num_hidden_layers": 2,
+    "attention_hidden_size": 32,
+    "num_attention_heads": 8,
+    "head_size": 8,
+    "intermediate_size": 64,
+    "layer_norm_epsilon": 1e-5,
+    "rescale_every": 6
+}"#;
+
+// Struct untuk fixture rwkv di direktori sementara: config.json, vocab JSON dan model.safetensors
+struct RwkvFixture {
+    dir: TempDir,
+    config: Config,
+    v6: bool,
+}
+
+impl RwkvFixture {
+    // Menulis fixture v5 atau v6 dengan bobot acak dari seed tetap, update dipanggil sebelum bobot ditulis
+    fn new(name: &str, v6: bool, update: impl FnOnce(&VarMap) -> Result<()>) -> Result<Self> {
+        let dir = TempDir::new(name)?;
+        std::fs::write(dir.join("config.json"), CONFIG)?;
+        fixtures::tokenizer(&dir.join("vocab.json"), VOCAB_SIZE)?;
+        let config: Config = serde_json::from_str(CONFIG)?;
+        let varmap = VarMap::new();
+        let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+        if v6 {
+            rwkv_v6::Model::new(&config, vb)?;
+        } else {
+            rwkv_v5::Model::new(&config, vb)?;
+        }
+        fixtures::randomize(&varmap, SEED)?;
+        update(&varmap)?;
+        varmap.save(dir.join("model.safetensors"))?;
+        Ok(Self { dir, config, v6 })
+    }
+
+    fn model(&self) -> Result<Model> {
+        let filenames = [self.dir.join("model.safetensors")];
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &Device::Cpu)? };
+        let weights = model::Weights::Plain(vb);
+        Ok(Model::new(&self.config, self.version(), weights)?)
+    }
+
+    // Menjalankan subcommand quantize lewat parser argumen CLI lalu memuat GGUF-nya
+    fn quantized(&self, quantization: &str) -> Result<Model> {
+        let weights = self.dir.join("model.safetensors");
+        let out = self.dir.join(format!("model-{quantization}.gguf"));
+        let (weights_str, out_str) = (weights.to_string_lossy(), out.to_string_lossy());
+        let args = Args::try_parse_from([
+            "rwkv",
+            "quantize",
+            "--weight-files",
+            &*weights_str,
+            "--out-file",
+            &*out_str,
+            "--quantization",
+            quantization,
+        ])?;
+        match args.command {
+            Some(Command::Quantize(quantize_args)) => quantize::run(quantize_args)?,
+            command => anyhow::bail!("unexpected command {command:?}"),
+        }
+        let vb =
+            candle_transformers::quantized_var_builder::VarBuilder::from_gguf(&out, &Device::Cpu)?;
+        let weights = model::Weights::Quantized(vb);
+        Ok(Model::new(&self.config, self.version(), weights)?)
+    }
+
+    // Membuat pipeline greedy (tanpa temperature) dengan repeat penalty yang diberikan. Prompt dimasukkan token demi
+    // token seperti saat file golden dibuat
+    fn pipeline(
+        &self,
+        model: Model,
+        repeat_penalty: f32,
+        repeat_last_n: usize,
+    ) -> Result<TextGeneration> {
+        Ok(TextGeneration::new(
+            model,
+            self.config.clone(),
+            Tokenizer::new(self.dir.join("vocab.json"))?,
+            0,
+            None,
+            None,
+            repeat_penalty,
+            repeat_last_n,
+            1,
+            &Device::Cpu,
+        ))
+    }
+
+    fn version(&self) -> version::Version {
+        if self.v6 {
+            version::Version::V6
+        } else {
+            version::Version::V5
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        if self.v6 {
+            "rwkv6"
+        } else {
+            "rwkv5"
+        }
+    }
+}
+
+// Fungsi untuk menjalankan generasi dari PROMPT lewat Backend yang sama dengan mode worker, hasilnya dalam bentuk
+// JSON. Token selain karakter ASCII ditulis sebagai " t{id}", sehingga teksnya adalah urutan token yang dihasilkan
+fn generate(pipeline: &mut TextGeneration, sample_len: usize) -> Result<Value> {
+    let budget = budget::Budget::new(None, None);
+    let generation = pipeline.generate(PROMPT, sample_len, &budget, &mut |_| Ok(()))?;
+    Ok(json!({
+        "prompt": PROMPT,
+        "text": generation.text,
+        "generated_tokens": generation.generated_tokens,
+        "finish_reason": generation.finish_reason.to_string(),
+    }))
+}
+
+// Fungsi untuk mengambil logits token terakhir setelah PROMPT dimasukkan ke state
+fn last_logits(pipeline: &TextGeneration) -> Result<Vec<f32>> {
+    let tokens = pipeline.tokenizer.encode(PROMPT)?;
+    let mut state = State::new(1, &pipeline.config, &Device::Cpu)?;
+    let budget = budget::Budget::new(None, None);
+    match pipeline.prefill(&tokens, &mut state, &budget, None)? {
+        Some(logits) => Ok(logits.squeeze(0)?.squeeze(0)?.to_vec1()?),
+        None => anyhow::bail!("empty prompt"),
+    }
+}
+
+#[test]
+fn greedy_generation_matches_golden() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("greedy", v6, |_| Ok(()))?;
+        let mut pipeline = fixture.pipeline(fixture.model()?, 1., 64)?;
+        let first = generate(&mut pipeline, 16)?;
+        // Setiap generasi dimulai dari state baru, sehingga generasi kedua harus sama
+        assert_eq!(first, generate(&mut pipeline, 16)?);
+        fixtures::assert_golden(GOLDEN_DIR, &format!("{}_greedy", fixture.name()), &first)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn repeat_penalty_matches_golden() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("repeat-penalty", v6, |_| Ok(()))?;
+        let plain = generate(&mut fixture.pipeline(fixture.model()?, 1., 64)?, 16)?;
+        // Tanpa token di jendela repeat_last_n, penalty tidak mengubah logits
+        let empty_window = generate(&mut fixture.pipeline(fixture.model()?, 4., 0)?, 16)?;
+        assert_eq!(plain, empty_window);
+        let penalized = generate(&mut fixture.pipeline(fixture.model()?, 4., 64)?, 16)?;
+        fixtures::assert_golden(
+            GOLDEN_DIR,
+            &format!("{}_repeat_penalty", fixture.name()),
+            &penalized,
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn eos_stops_generation() -> Result<()> {
+    // Head rwkv tidak punya bias: ln_out diisi weight 0 dan bias 1 sehingga hidden state terakhir semua 1 dan logits
+    // adalah jumlah baris head. Baris EOS_TOKEN_ID diisi 1
+    let eos = EOS_TOKEN_ID as usize;
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("eos", v6, |varmap| {
+            fixtures::update(varmap, "rwkv.ln_out.weight", |w| w.fill(0.))?;
+            fixtures::update(varmap, "rwkv.ln_out.bias", |b| b.fill(1.))?;
+            fixtures::update(varmap, "head.weight", |w| {
+                w[eos * HIDDEN_SIZE..(eos + 1) * HIDDEN_SIZE].fill(1.)
+            })
+        })?;
+        for model in [fixture.model()?, fixture.quantized("q8_0")?] {
+            let mut pipeline = fixture.pipeline(model, 1., 64)?;
+            let budget = budget::Budget::new(None, None);
+            let generation = pipeline.generate(PROMPT, 16, &budget, &mut |_| Ok(()))?;
+            assert_eq!(generation.finish_reason, budget::FinishReason::Eos);
+            assert_eq!(generation.generated_tokens, 1);
+            assert_eq!(generation.text, "");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn quantized_gguf_matches_golden() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("quantized", v6, |_| Ok(()))?;
+        // GGUF f32 berisi bobot yang sama, sehingga logits model terkuantisasi harus sama dengan model safetensors
+        let expected = last_logits(&fixture.pipeline(fixture.model()?, 1., 64)?)?;
+        let actual = last_logits(&fixture.pipeline(fixture.quantized("f32")?, 1., 64)?)?;
+        let max_diff = expected
+            .iter()
+            .zip(actual.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0f32, f32::max);
+        assert!(
+            max_diff < 1e-3,
+            "{}: max logit difference {max_diff}",
+            fixture.name()
+        );
+        let q8_0 = generate(
+            &mut fixture.pipeline(fixture.quantized("q8_0")?, 1., 64)?,
+            16,
+        )?;
+        fixtures::assert_golden(GOLDEN_DIR, &format!("{}_q8_0", fixture.name()), &q8_0)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn version_is_detected_from_the_weights() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("version", v6, |_| Ok(()))?;
+        let expected = if v6 {
+            version::Version::V6
+        } else {
+            version::Version::V5
+        };
+        // config.json fixture tidak menyatakan versi, sehingga versi diambil dari nama tensor safetensors dan GGUF
+        let config = fixture.dir.join("config.json");
+        let weights = [fixture.dir.join("model.safetensors")];
+        assert_eq!(version::detect(&config, &weights)?, expected);
+        fixture.quantized("q8_0")?;
+        let weights = [fixture.dir.join("model-q8_0.gguf")];
+        assert_eq!(version::detect(&config, &weights)?, expected);
+    }
+    Ok(())
+}
+
+// Fungsi untuk mengambil semua tensor state sebagai satu vektor f32
+fn state_values(state: &State) -> Result<Vec<f32>> {
+    let mut values = vec![];
+    for layer in state.per_layer.iter() {
+        for tensor in [
+            &layer.extract_key_value,
+            &layer.linear_attention,
+            &layer.feed_forward,
+        ] {
+            values.extend(
+                tensor
+                    .flatten_all()?
+                    .to_dtype(DType::F32)?
+                    .to_vec1::<f32>()?,
+            );
+        }
+    }
+    Ok(values)
+}
+
+// Fungsi untuk selisih terbesar antara dua vektor
+fn max_diff(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0f32, f32::max)
+}
+
+// Forward model candle v5 atau v6 yang dipakai sebagai referensi
+type ReferenceForward = Box<dyn Fn(&Tensor, &mut State) -> candle_core::Result<Tensor>>;
+
+#[test]
+fn chunked_prefill_matches_token_by_token() -> Result<()> {
+    // Prompt lebih panjang dari dua potongan WKV agar state dibawa antar potongan
+    let prompt =
+        "Pasal 5: setiap orang berhak atas pekerjaan dan penghidupan yang layak bagi kemanusiaan.";
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("prefill", v6, |_| Ok(()))?;
+        let device = Device::Cpu;
+        let tokenizer = Tokenizer::new(fixture.dir.join("vocab.json"))?;
+        let tokens = tokenizer.encode(prompt)?;
+        assert!(tokens.len() > 64);
+
+        // Referensi: model candle, token demi token
+        let filenames = [fixture.dir.join("model.safetensors")];
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &device)? };
+        let reference: ReferenceForward = if v6 {
+            let model = rwkv_v6::Model::new(&fixture.config, vb)?;
+            Box::new(move |xs, state| model.forward(xs, state))
+        } else {
+            let model = rwkv_v5::Model::new(&fixture.config, vb)?;
+            Box::new(move |xs, state| model.forward(xs, state))
+        };
+        let mut expected = State::new(1, &fixture.config, &device)?;
+        let mut logits = None;
+        for &t in tokens.iter() {
+            logits = Some(reference(&Tensor::new(&[[t]], &device)?, &mut expected)?);
+        }
+        let expected_logits = match logits {
+            Some(logits) => logits.flatten_all()?.to_vec1::<f32>()?,
+            None => anyhow::bail!("empty prompt"),
+        };
+        let expected_values = state_values(&expected)?;
+
+        let budget = budget::Budget::new(None, None);
+        for (model, prefill_chunk, tolerance) in [
+            (fixture.model()?, 1, 1e-6),
+            (fixture.model()?, 7, 1e-4),
+            (fixture.model()?, 64, 1e-4),
+            (fixture.quantized("f32")?, 64, 1e-3),
+        ] {
+            let mut pipeline = fixture.pipeline(model, 1., 64)?;
+            pipeline.prefill_chunk = prefill_chunk;
+            let mut state = State::new(1, &fixture.config, &device)?;
+            let mut echoed = String::new();
+            let mut echo = |text: &str| -> Result<()> {
+                echoed.push_str(text);
+                Ok(())
+            };
+            let logits = match pipeline.prefill(&tokens, &mut state, &budget, Some(&mut echo))? {
+                Some(logits) => logits.flatten_all()?.to_vec1::<f32>()?,
+                None => anyhow::bail!("empty prompt"),
+            };
+            assert_eq!(echoed, prompt);
+            assert_eq!(state.pos, tokens.len());
+            let logits_diff = max_diff(&logits, &expected_logits);
+            let state_diff = max_diff(&state_values(&state)?, &expected_values);
+            assert!(
+                logits_diff <= tolerance && state_diff <= tolerance,
+                "{} chunk {prefill_chunk}: logits differ by {logits_diff}, state by {state_diff}",
+                fixture.name()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn ingest_resumes_from_a_checkpoint() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("ingest", v6, |_| Ok(()))?;
+        let pipeline = fixture.pipeline(fixture.model()?, 1., 64)?;
+        let document = "Pasal 1 ayat 2 berlaku sejak tanggal diundangkan.\n".repeat(3);
+        let budget = budget::Budget::new(None, None);
+        let tokens = pipeline.tokenizer.encode(&document)?;
+        let mut expected = State::new(1, &pipeline.config, &Device::Cpu)?;
+        pipeline.prefill(&tokens, &mut expected, &budget, None)?;
+
+        // Separuh dokumen dibaca per 5 byte, state disimpan lalu dimuat ulang dan sisa dokumen dilanjutkan
+        let bytes = document.as_bytes();
+        let half = bytes.len() / 2;
+        let mut state = State::new(1, &pipeline.config, &Device::Cpu)?;
+        let mut progress = ingest::Progress::default();
+        let mut calls = 0;
+        ingest::feed(
+            &pipeline,
+            &mut state,
+            &mut progress,
+            &mut &bytes[..half],
+            5,
+            &budget,
+            &mut |_, _| {
+                calls += 1;
+                Ok(())
+            },
+        )?;
+        assert_eq!(progress.bytes, half as u64);
+        assert_eq!(calls, progress.tokens);
+        let path = fixture.dir.join("checkpoint.safetensors");
+        state_file::save(&path, &state, &json!({"bytes": progress.bytes}))?;
+        let (mut state, meta) = state_file::load(&path, &pipeline.config, &Device::Cpu)?;
+        assert_eq!(meta["bytes"], half);
+        assert_eq!(state.pos, progress.tokens);
+        ingest::feed(
+            &pipeline,
+            &mut state,
+            &mut progress,
+            &mut &bytes[half..],
+            5,
+            &budget,
+            &mut |_, _| Ok(()),
+        )?;
+        assert!(progress.complete);
+        assert_eq!(progress.bytes, bytes.len() as u64);
+        assert_eq!(progress.tokens, tokens.len());
+        assert_eq!(state_values(&state)?, state_values(&expected)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn chat_session_continues_after_save_and_load() -> Result<()> {
+    for v6 in [false, true] {
+        let fixture = RwkvFixture::new("chat", v6, |_| Ok(()))?;
+        let path = fixture.dir.join("session.safetensors");
+        let turns = ["Halo", "Apa kabar?"];
+
+        // Sesi tanpa jeda sebagai pembanding
+        let mut pipeline = fixture.pipeline(fixture.model()?, 1., 64)?;
+        let mut expected = session::Session::new(&pipeline, Some("Jawab singkat."))?;
+        for turn in turns {
+            expected.turn(&mut pipeline, turn, 8, &mut |_| Ok(()))?;
+        }
+
+        // Giliran pertama, simpan, muat dengan pipeline baru lalu giliran kedua
+        let mut pipeline = fixture.pipeline(fixture.model()?, 1., 64)?;
+        let mut session = session::Session::new(&pipeline, Some("Jawab singkat."))?;
+        let mut streamed = String::new();
+        let generation = session.turn(&mut pipeline, turns[0], 8, &mut |text| {
+            streamed.push_str(text);
+            Ok(())
+        })?;
+        assert_eq!(streamed, generation.text);
+        session.save(&path, "model")?;
+        assert!(session::Session::load(&path, &pipeline, "other").is_err());
+        let mut pipeline = fixture.pipeline(fixture.model()?, 1., 64)?;
+        let mut session = session::Session::load(&path, &pipeline, "model")?;
+        session.turn(&mut pipeline, turns[1], 8, &mut |_| Ok(()))?;
+
+        let roles = session.transcript.iter().map(|m| m.role.as_str());
+        assert_eq!(
+            roles.collect::<Vec<_>>(),
+            ["system", "user", "assistant", "user", "assistant"]
+        );
+        assert_eq!(
+            serde_json::to_value(&session.transcript)?,
+            serde_json::to_value(&expected.transcript)?
+        );
+        assert_eq!(session.state.pos, expected.state.pos);
+        assert_eq!(
+            state_values(&session.state)?,
+            state_values(&expected.state)?
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,402 @@
+// Modul untuk subcommand ingest: dokumen panjang dari file atau stdin dialirkan ke state RWKV token demi token dengan
+// memori tetap, disertai progress bar dan checkpoint state berkala. Setelah dokumen selesai, setiap pertanyaan dijawab
+// dari salinan state dokumen sehingga jawaban satu pertanyaan tidak mempengaruhi pertanyaan berikutnya
+use anyhow::Result;
+use clap::Args as ClapArgs;
+use serde_json::json;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::budget::{Budget, FinishReason};
+use crate::gen_eval::{truncate_at_stop, unescape};
+use crate::worker::{self, Backend};
+use crate::{state_file, State, TextGeneration};
+
+// Argumen untuk subcommand ingest
+#[derive(ClapArgs, Debug)]
+pub struct IngestArgs {
+    /// The document to ingest, read from stdin when not given.
+    #[arg(long)]
+    document: Option<PathBuf>,
+
+    /// A question asked after the document, can be repeated. Every question starts from the
+    /// state right after the document, so the answers do not see each other.
+    #[arg(long = "question")]
+    questions: Vec<String>,
+
+    /// How a question is appended to the document, `{question}` is replaced by the question.
+    #[arg(long, default_value = r"\n\nUser: {question}\n\nAssistant:", value_parser = unescape)]
+    template: String,
+
+    /// Stop an answer as soon as it contains one of these sequences, which are not printed.
+    #[arg(long = "stop", default_values = [r"\n\nUser:"], value_parser = unescape)]
+    stop: Vec<String>,
+
+    /// The maximum number of tokens of every answer.
+    #[arg(long, default_value_t = 512)]
+    max_tokens: usize,
+
+    /// The number of bytes read and tokenized at once.
+    #[arg(long, default_value_t = 65536)]
+    read_size: usize,
+
+    /// Safetensors file where the state is saved every --checkpoint-every tokens, when
+    /// interrupted with ctrl-c and after the document, with the number of bytes ingested.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// The number of document tokens between two checkpoints.
+    #[arg(long, default_value_t = 16384)]
+    checkpoint_every: usize,
+
+    /// Continue from --checkpoint, the bytes already ingested are skipped. With the checkpoint of
+    /// a finished document the questions are answered without reading the document again.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
+
+// Struct untuk posisi ingest: byte dan token dokumen yang sudah masuk ke state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub bytes: u64,
+    pub tokens: usize,
+    pub complete: bool,
+}
+
+// Fungsi untuk menentukan berapa byte awal buffer yang bisa ditokenisasi sekarang: sampai sebelum deretan spasi atau
+// baris baru terakhir (token RWKV World biasanya diawali spasi), atau sampai karakter UTF-8 utuh terakhir jika buffer
+// tidak berisi spasi. Di akhir input seluruh buffer ditokenisasi
+fn split_point(buffer: &[u8], end_of_input: bool) -> usize {
+    if end_of_input {
+        return buffer.len();
+    }
+    let is_space = |b: &u8| *b == b' ' || *b == b'\n';
+    let mut end = buffer.iter().rposition(is_space).unwrap_or(0);
+    while end > 0 && is_space(&buffer[end - 1]) {
+        end -= 1;
+    }
+    if end > 0 {
+        return end;
+    }
+    match std::str::from_utf8(buffer) {
+        Ok(_) => buffer.len(),
+        Err(err) => err.valid_up_to(),
+    }
+}
+
+// Fungsi untuk mengalirkan input ke state per potongan prefill_chunk token mulai dari progress, on_chunk dipanggil
+// setelah setiap potongan (progress bar dan checkpoint). Ctrl-C diperiksa sebelum setiap potongan, progress selalu
+// sesuai dengan state karena byte dihitung dari teks token yang sudah masuk
+pub fn feed(
+    pipeline: &TextGeneration,
+    state: &mut State,
+    progress: &mut Progress,
+    input: &mut dyn Read,
+    read_size: usize,
+    budget: &Budget,
+    on_chunk: &mut dyn FnMut(&State, &Progress) -> Result<()>,
+) -> Result<Option<FinishReason>> {
+    let mut buffer = vec![];
+    let mut chunk = vec![0u8; read_size.max(1)];
+    loop {
+        let n = input.read(&mut chunk)?;
+        buffer.extend_from_slice(&chunk[..n]);
+        let end = split_point(&buffer, n == 0);
+        let text = match std::str::from_utf8(&buffer[..end]) {
+            Ok(text) => text,
+            Err(err) => anyhow::bail!(
+                "the document is not UTF-8 at byte {}",
+                progress.bytes + err.valid_up_to() as u64
+            ),
+        };
+        let tokens = pipeline.tokenizer.encode(text)?;
+        for chunk in tokens.chunks(pipeline.prefill_chunk) {
+            if let Some(reason) = budget.check() {
+                return Ok(Some(reason));
+            }
+            pipeline.forward_tokens(chunk, state)?;
+            progress.bytes += pipeline.tokenizer.decode_bytes(chunk).len() as u64;
+            progress.tokens += chunk.len();
+            on_chunk(state, progress)?;
+        }
+        buffer.drain(..end);
+        if n == 0 {
+            progress.complete = true;
+            return Ok(None);
+        }
+    }
+}
+
+// Fungsi untuk menulis baris progress: bar dan persentase jika ukuran dokumen diketahui (file), selalu dengan jumlah
+// byte, token dan kecepatan
+fn progress_line(progress: &Progress, total: Option<u64>, rate: f64) -> String {
+    let mb = progress.bytes as f64 / 1e6;
+    let counts = format!("{} tokens, {rate:.1} token/s", progress.tokens);
+    match total {
+        Some(total) if total > 0 => {
+            let fraction = (progress.bytes as f64 / total as f64).min(1.);
+            let filled = (fraction * 30.).round() as usize;
+            format!(
+                "[{}{}] {:5.1}% {mb:.2}/{:.2} MB, {counts}",
+                "#".repeat(filled),
+                "-".repeat(30 - filled),
+                fraction * 100.,
+                total as f64 / 1e6
+            )
+        }
+        _ => format!("{mb:.2} MB, {counts}"),
+    }
+}
+
+// Fungsi untuk menentukan akhir teks yang aman dikirim: sebelum stop sequence pertama (true jika ditemukan), atau
+// sebelum akhiran teks yang mungkin awal sebuah stop sequence sehingga ditahan dulu sampai token berikutnya
+fn safe_end(text: &str, stops: &[String]) -> (usize, bool) {
+    let found = stops
+        .iter()
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| text.find(s.as_str()))
+        .min();
+    if let Some(end) = found {
+        return (end, true);
+    }
+    let mut end = text.len();
+    for stop in stops.iter() {
+        let held = (1..stop.len())
+            .rev()
+            .find(|&len| stop.is_char_boundary(len) && text.ends_with(&stop[..len]));
+        if let Some(len) = held {
+            end = end.min(text.len() - len);
+        }
+    }
+    (end, false)
+}
+
+// Fungsi untuk memasukkan prompt ke state lalu menghasilkan jawaban yang berhenti pada stop sequence pertama. Teks
+// yang mungkin awal stop sequence ditahan, sehingga on_text tidak pernah menerima stop sequence. Selain jawaban,
+// dikembalikan teks setelah jawaban yang sudah masuk ke state (stop sequence dan sisa token terakhirnya)
+pub fn answer(
+    pipeline: &mut TextGeneration,
+    state: &mut State,
+    prompt: &str,
+    max_tokens: usize,
+    stops: &[String],
+    on_text: &mut dyn FnMut(&str) -> Result<()>,
+) -> Result<(worker::Generation, String)> {
+    let tokens = pipeline.tokenizer.encode(prompt)?;
+    let budget = Budget::new(None, None);
+    let next_logits = pipeline.prefill(&tokens, state, &budget, None)?;
+    let mut text = String::new();
+    let mut sent = 0;
+    let mut generation = pipeline.generate_tokens(
+        tokens,
+        state,
+        next_logits,
+        max_tokens,
+        &budget,
+        &mut |chunk| {
+            text.push_str(chunk);
+            let (end, stopped) = safe_end(&text, stops);
+            if stopped {
+                budget.stop();
+            }
+            if end > sent {
+                on_text(&text[sent..end])?;
+                sent = end;
+            }
+            Ok(())
+        },
+    )?;
+    // Teks yang ditahan dikirim jika generasi berhenti tanpa stop sequence
+    let end = truncate_at_stop(&generation.text, stops).len();
+    if end > sent {
+        on_text(&generation.text[sent..end])?;
+    }
+    let rest = generation.text.split_off(end);
+    Ok((generation, rest))
+}
+
+// Fungsi untuk menyimpan checkpoint ingest: state beserta posisi dokumen dan sidik jari model
+fn save_checkpoint(
+    path: &Path,
+    state: &State,
+    progress: &Progress,
+    model: &str,
+    document: &str,
+) -> Result<()> {
+    let meta = json!({
+        "model": model,
+        "document": document,
+        "bytes": progress.bytes,
+        "tokens": progress.tokens,
+        "complete": progress.complete,
+    });
+    state_file::save(path, state, &meta)
+}
+
+// Fungsi untuk menjalankan subcommand ingest, model adalah sidik jari model yang dicatat di checkpoint
+pub fn run(pipeline: &mut TextGeneration, args: &IngestArgs, model: &str) -> Result<()> {
+    let document = match args.document.as_ref() {
+        Some(path) => path.display().to_string(),
+        None => "stdin".to_string(),
+    };
+    let (mut state, mut progress) = match args.checkpoint.as_ref() {
+        Some(path) if args.resume => {
+            let (state, meta) = state_file::load(path, &pipeline.config, &pipeline.device)?;
+            if meta["model"] != model {
+                anyhow::bail!("the checkpoint {path:?} was made with another model")
+            }
+            if meta["document"] != document.as_str() {
+                anyhow::bail!(
+                    "the checkpoint {path:?} is of {}, not of {document}",
+                    meta["document"]
+                )
+            }
+            let progress = Progress {
+                bytes: meta["bytes"].as_u64().unwrap_or(0),
+                tokens: meta["tokens"].as_u64().unwrap_or(0) as usize,
+                complete: meta["complete"].as_bool() == Some(true),
+            };
+            log!(
+                "resuming {document} after {} bytes ({} tokens)",
+                progress.bytes,
+                progress.tokens
+            );
+            (state, progress)
+        }
+        Some(path) if path.exists() => {
+            anyhow::bail!("the checkpoint {path:?} already exists, pass --resume to continue it")
+        }
+        _ => (
+            State::new(1, &pipeline.config, &pipeline.device)?,
+            Progress::default(),
+        ),
+    };
+
+    crate::budget::install_ctrlc_handler()?;
+    let budget = Budget::new(None, None);
+    if !progress.complete {
+        // Byte yang sudah masuk ke state dilewati: file di-seek, stdin dibaca lalu dibuang
+        let (mut input, total): (Box<dyn Read>, Option<u64>) = match args.document.as_ref() {
+            Some(path) => {
+                let mut file = std::fs::File::open(path)?;
+                let total = file.metadata()?.len();
+                file.seek(std::io::SeekFrom::Start(progress.bytes))?;
+                (Box::new(file), Some(total))
+            }
+            None => {
+                let mut stdin = std::io::stdin().lock();
+                std::io::copy(
+                    &mut stdin.by_ref().take(progress.bytes),
+                    &mut std::io::sink(),
+                )?;
+                (Box::new(stdin), None)
+            }
+        };
+        let start = Instant::now();
+        let start_tokens = progress.tokens;
+        let mut shown = Instant::now();
+        let mut saved_at = progress.tokens;
+        let mut on_chunk = |state: &State, progress: &Progress| -> Result<()> {
+            if shown.elapsed() > Duration::from_millis(200) {
+                let rate = (progress.tokens - start_tokens) as f64 / start.elapsed().as_secs_f64();
+                eprint!("\r{}", progress_line(progress, total, rate));
+                shown = Instant::now();
+            }
+            if let Some(path) = args.checkpoint.as_ref() {
+                if progress.tokens - saved_at >= args.checkpoint_every.max(1) {
+                    save_checkpoint(path, state, progress, model, &document)?;
+                    saved_at = progress.tokens;
+                }
+            }
+            Ok(())
+        };
+        let reason = feed(
+            pipeline,
+            &mut state,
+            &mut progress,
+            &mut input,
+            args.read_size,
+            &budget,
+            &mut on_chunk,
+        )?;
+        let rate = (progress.tokens - start_tokens) as f64 / start.elapsed().as_secs_f64();
+        eprintln!("\r{}", progress_line(&progress, total, rate));
+        if let Some(path) = args.checkpoint.as_ref() {
+            save_checkpoint(path, &state, &progress, model, &document)?;
+            log!(
+                "saved the state after {} tokens to {path:?}",
+                progress.tokens
+            );
+        }
+        if let Some(reason) = reason {
+            log!("ingestion stopped ({reason}), continue it with --resume");
+            return Ok(());
+        }
+        log!(
+            "ingested {} tokens of {document} in {:.2}s",
+            progress.tokens,
+            start.elapsed().as_secs_f64()
+        );
+    }
+
+    for question in args.questions.iter() {
+        // Setiap pertanyaan dimulai dari state dokumen dan sampler dengan seed awal
+        let mut state = state_file::clone_state(&state);
+        pipeline.reset()?;
+        println!("\n> {question}");
+        let prompt = args.template.replace("{question}", question);
+        let (generation, _) = answer(
+            pipeline,
+            &mut state,
+            &prompt,
+            args.max_tokens,
+            &args.stop,
+            &mut |text| {
+                print!("{text}");
+                std::io::stdout().flush()?;
+                Ok(())
+            },
+        )?;
+        println!(
+            "\n{} tokens generated, finish reason: {}",
+            generation.generated_tokens, generation.finish_reason
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_end_before_spaces_and_stops_are_held_back() {
+        assert_eq!(split_point(b"one two  three", false), 7);
+        assert_eq!(split_point(b"one\n\ntwo", false), 3);
+        assert_eq!(split_point(b"one two", true), 7);
+        // Tanpa spasi buffer dipotong di akhir karakter UTF-8 utuh
+        assert_eq!(split_point("abé".as_bytes(), false), 4);
+        assert_eq!(split_point(&"abé".as_bytes()[..3], false), 2);
+
+        let stops = vec!["\n\nUser:".to_string()];
+        assert_eq!(safe_end("Jawabannya 42", &stops), (13, false));
+        assert_eq!(safe_end("Jawabannya 42\n\nUs", &stops), (13, false));
+        assert_eq!(safe_end("Jawabannya 42\n\nUser: lagi", &stops), (13, true));
+        assert_eq!(safe_end("a\nb", &stops), (3, false));
+
+        let progress = Progress {
+            bytes: 500_000,
+            tokens: 120_000,
+            complete: false,
+        };
+        assert_eq!(
+            progress_line(&progress, Some(1_000_000), 250.),
+            "[###############---------------]  50.0% 0.50/1.00 MB, 120000 tokens, 250.0 token/s"
+        );
+        assert_eq!(
+            progress_line(&progress, None, 250.),
+            "0.50 MB, 120000 tokens, 250.0 token/s"
+        );
+    }
+}
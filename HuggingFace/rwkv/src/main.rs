@@ -1,13 +1,37 @@
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
-use candle_transformers::models::quantized_rwkv_v5::Model as Q5; // Import model quantized rwkv v5
-use candle_transformers::models::quantized_rwkv_v6::Model as Q6; // Import model quantized rwkv v6
-use candle_transformers::models::rwkv_v5::{Config, Model as M5, State, Tokenizer}; // Import model rwkv v5
-use candle_transformers::models::rwkv_v6::Model as M6; // Import model rwkv v6
+// Macro log untuk mencetak ke stdout, atau ke stderr pada mode --worker karena stdout dipakai untuk response JSON
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if crate::worker::is_active() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+#[cfg(test)]
+mod fixtures; // Modul untuk fixture test offline (model acak kecil, vocab, file golden)
+#[cfg(test)]
+mod golden_tests; // Modul untuk test golden generasi dan kuantisasi dengan model acak kecil
+mod ingest; // Modul untuk ingest dokumen panjang ke state dengan checkpoint, lalu menjawab pertanyaan
+mod memory; // Modul untuk perkiraan memori model sebelum bobot dimuat
+mod model; // Modul untuk model RWKV-5 dan RWKV-6 dengan prefill per potongan (chunked WKV)
+mod quantize; // Modul untuk kuantisasi bobot safetensors ke GGUF
+mod session; // Modul untuk subcommand chat dengan state yang disimpan di antara giliran
+mod state_file; // Modul untuk menyimpan dan memuat State ke safetensors
+mod token_stream; // Modul untuk detokenisasi bertahap tokenizer RWKV World dengan common::token_stream
+mod version; // Modul untuk deteksi versi RWKV dari config.json atau header file bobot
+
+use common::{budget, chat, compare, eval, gen_eval, journal, manifest, worker}; // Modul untuk eval, journal, chat, mode worker dan modul lain yang dibagi dengan binary lain
+
+use candle_transformers::models::rwkv_v5::{Config, State, Tokenizer}; // Import config, state dan tokenizer rwkv
+use model::Model; // Import model rwkv v5 dan v6 (safetensors atau GGUF)
 
 use candle_core::utils::{cuda_is_available, metal_is_available}; // Import fungsi untuk mengecek ketersediaan CUDA atau Metal
-use candle_core::{DType, Device, Tensor}; // Import struct Device dan Tensor dari candle_core
+use candle_core::{DType, Device, Tensor, D}; // Import struct Device dan Tensor dari candle_core
 use candle_nn::VarBuilder; // Import VarBuilder dari candle_nn
 use candle_transformers::generation::LogitsProcessor; // Import LogitsProcessor untuk memproses logit
 use hf_hub::{api::sync::Api, Repo, RepoType}; // Import untuk mengambil model dari Hugging Face
@@ -15,25 +39,8 @@ use hf_hub::{api::sync::Api, Repo, RepoType}; // Import untuk mengambil model da
 // Nilai token EOS untuk mengakhiri kalimat yang dihasilkan oleh model rwkv
 const EOS_TOKEN_ID: u32 = 261;
 
-// Enum untuk model rwkv yang tersedia di Hugging Face dan model rwkv yang sudah diquantized (q4k)
-enum Model {
-    M5(M5),
-    Q5(Q5),
-    M6(M6),
-    Q6(Q6),
-}
-
-// Implementasi model rwkv untuk menghasilkan output dari model rwkv yang dipilih oleh pengguna
-impl Model {
-    fn forward(&self, xs: &Tensor, state: &mut State) -> candle_core::Result<Tensor> {
-        match self {
-            Self::M5(m) => m.forward(xs, state),
-            Self::Q5(m) => m.forward(xs, state),
-            Self::M6(m) => m.forward(xs, state),
-            Self::Q6(m) => m.forward(xs, state),
-        }
-    }
-}
+// Callback opsional untuk teks prompt yang sudah dimasukkan ke state
+type OnPrompt<'a> = Option<&'a mut dyn FnMut(&str) -> Result<()>>;
 
 // Struct TextGeneration untuk menghasilkan output dari model rwkv yang dipilih oleh pengguna
 struct TextGeneration {
@@ -42,8 +49,14 @@ struct TextGeneration {
     device: Device,
     tokenizer: Tokenizer,
     logits_processor: LogitsProcessor,
+    // Parameter sampling untuk membuat ulang LogitsProcessor saat reset pada mode worker
+    seed: u64,
+    temp: Option<f64>,
+    top_p: Option<f64>,
     repeat_penalty: f32,
     repeat_last_n: usize,
+    // Jumlah token prompt yang dimasukkan dalam satu forward
+    prefill_chunk: usize,
 }
 
 // Implementasi TextGeneration untuk menghasilkan output dari model rwkv yang dipilih oleh pengguna
@@ -58,6 +71,7 @@ impl TextGeneration {
         top_p: Option<f64>,
         repeat_penalty: f32,
         repeat_last_n: usize,
+        prefill_chunk: usize,
         device: &Device,
     ) -> Self {
         let logits_processor = LogitsProcessor::new(seed, temp, top_p);
@@ -66,29 +80,133 @@ impl TextGeneration {
             config,
             tokenizer,
             logits_processor,
+            seed,
+            temp,
+            top_p,
             repeat_penalty,
             repeat_last_n,
+            prefill_chunk: prefill_chunk.max(1),
             device: device.clone(),
         }
     }
 
-    // Fungsi run untuk menghasilkan output dari model rwkv yang dipilih oleh pengguna
-    fn run(&mut self, prompt: &str, sample_len: usize) -> Result<()> {
+    // Fungsi run untuk menghasilkan output dari model rwkv yang dipilih oleh pengguna, mengembalikan teks yang dihasilkan.
+    // Prompt ikut dicetak jika echo
+    fn run(
+        &mut self,
+        prompt: &str,
+        sample_len: usize,
+        echo: bool,
+        budget: &budget::Budget,
+    ) -> Result<(String, budget::FinishReason)> {
         use std::io::Write;
-        let mut tokens = self.tokenizer.encode(prompt)?;
-        let mut generated_tokens = 0usize;
+        let tokens = self.tokenizer.encode(prompt)?;
         let mut state = State::new(1, &self.config, &self.device)?;
-        let mut next_logits = None;
-        for &t in tokens.iter() {
-            let input = Tensor::new(&[[t]], &self.device)?;
-            let logits = self.model.forward(&input, &mut state)?;
-            next_logits = Some(logits);
-            print!("{}", self.tokenizer.decode(&[t])?)
-        }
+        let mut echo_text = |text: &str| -> Result<()> {
+            print!("{text}");
+            Ok(())
+        };
+        let on_prompt: OnPrompt = if echo { Some(&mut echo_text) } else { None };
+        let start_prompt = std::time::Instant::now();
+        let next_logits = self.prefill(&tokens, &mut state, budget, on_prompt)?;
         std::io::stdout().flush()?;
+        if !echo {
+            log!(
+                "processed {} prompt tokens in {:.2}s",
+                tokens.len(),
+                start_prompt.elapsed().as_secs_f64()
+            );
+        }
 
         let start_gen = std::time::Instant::now();
+        let generation = self.generate_tokens(
+            tokens,
+            &mut state,
+            next_logits,
+            sample_len,
+            budget,
+            &mut |text| {
+                print!("{text}");
+                std::io::stdout().flush()?;
+                Ok(())
+            },
+        )?;
+        let dt = start_gen.elapsed();
+        println!(
+            "\n{} tokens generated ({:.2} token/s), finish reason: {}",
+            generation.generated_tokens,
+            generation.generated_tokens as f64 / dt.as_secs_f64(),
+            generation.finish_reason,
+        );
+        Ok((generation.text, generation.finish_reason))
+    }
+
+    // Fungsi untuk memasukkan token ke state dalam satu forward, mengembalikan logits token terakhir
+    fn forward_tokens(&self, tokens: &[u32], state: &mut State) -> Result<Tensor> {
+        let input = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        Ok(self.model.forward(&input, state)?)
+    }
+
+    // Fungsi prefill untuk memasukkan token prompt ke state per potongan prefill_chunk token, teks prompt dikirim ke
+    // on_prompt jika ada. Mengembalikan logits token terakhir (None jika prompt kosong), berhenti lebih awal jika
+    // budget prompt habis
+    fn prefill(
+        &self,
+        tokens: &[u32],
+        state: &mut State,
+        budget: &budget::Budget,
+        mut on_prompt: OnPrompt,
+    ) -> Result<Option<Tensor>> {
+        let mut next_logits = None;
+        let mut stream = token_stream::stream(&self.tokenizer);
+        for chunk in tokens.chunks(self.prefill_chunk) {
+            if budget.check_prompt().is_some() {
+                break;
+            }
+            next_logits = Some(self.forward_tokens(chunk, state)?);
+            if let Some(on_prompt) = on_prompt.as_mut() {
+                for &t in chunk.iter() {
+                    if let Some(text) = stream.next_token(t)? {
+                        on_prompt(&text)?;
+                    }
+                }
+            }
+        }
+        if let Some(on_prompt) = on_prompt.as_mut() {
+            if let Some(text) = stream.flush()? {
+                on_prompt(&text)?;
+            }
+        }
+        Ok(next_logits)
+    }
+
+    // Fungsi generate_tokens untuk menghasilkan teks setelah prompt diproses oleh prefill, setiap potongan teks baru
+    // dikirim ke on_text (stdout pada CLI, event token pada mode --worker)
+    fn generate_tokens(
+        &mut self,
+        mut tokens: Vec<u32>,
+        state: &mut State,
+        mut next_logits: Option<Tensor>,
+        sample_len: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        let prompt_tokens = tokens.len();
+        let mut generated_tokens = 0usize;
+        let mut output = String::new();
+        let mut stream = token_stream::stream(&self.tokenizer);
+        // Generasi dilewati jika pemrosesan prompt sudah dihentikan
+        let mut finish_reason = budget.check_prompt();
+        let sample_len = if finish_reason.is_some() {
+            0
+        } else {
+            sample_len
+        };
         for _ in 0..sample_len {
+            finish_reason = budget.check();
+            if finish_reason.is_some() {
+                break;
+            }
             let logits = match next_logits.as_ref() {
                 Some(logits) => logits,
                 None => anyhow::bail!("cannot work on an empty prompt"),
@@ -108,24 +226,99 @@ impl TextGeneration {
             tokens.push(next_token);
             generated_tokens += 1;
             if next_token == EOS_TOKEN_ID || next_token == 0 {
+                finish_reason = Some(budget::FinishReason::Eos);
                 break;
             }
-            print!("{}", self.tokenizer.decode(&[next_token])?);
-            std::io::stdout().flush()?;
+            if let Some(text) = stream.next_token(next_token)? {
+                on_text(&text)?;
+                output.push_str(&text);
+            }
 
             let input = Tensor::new(&[[next_token]], &self.device)?;
-            next_logits = Some(self.model.forward(&input, &mut state)?)
+            next_logits = Some(self.model.forward(&input, state)?)
         }
-        let dt = start_gen.elapsed();
-        println!(
-            "\n{generated_tokens} tokens generated ({:.2} token/s)",
-            generated_tokens as f64 / dt.as_secs_f64(),
-        );
+        if let Some(text) = stream.flush()? {
+            on_text(&text)?;
+            output.push_str(&text);
+        }
+        Ok(worker::Generation {
+            text: output,
+            finish_reason: finish_reason.unwrap_or(budget::FinishReason::Length),
+            prompt_tokens,
+            generated_tokens,
+        })
+    }
+}
+
+// Implementasi Backend agar TextGeneration bisa dilayani oleh mode --worker, setiap request dimulai dari state baru
+impl worker::Backend for TextGeneration {
+    fn generate(
+        &mut self,
+        prompt: &str,
+        max_tokens: usize,
+        budget: &budget::Budget,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        let tokens = self.tokenizer.encode(prompt)?;
+        let mut state = State::new(1, &self.config, &self.device)?;
+        let next_logits = self.prefill(&tokens, &mut state, budget, None)?;
+        self.generate_tokens(tokens, &mut state, next_logits, max_tokens, budget, on_text)
+    }
+
+    // Prompt dimasukkan ke state, lalu token continuation dimasukkan satu per satu
+    fn score(&mut self, prompt: &str, continuation: &str) -> Result<(f64, usize)> {
+        let prompt_tokens = self.tokenizer.encode(prompt)?;
+        let tokens = self.tokenizer.encode(&format!("{prompt}{continuation}"))?;
+        // Continuation dimulai dari token pertama yang berbeda dari token prompt
+        let start = prompt_tokens
+            .iter()
+            .zip(tokens.iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .max(1);
+        if start >= tokens.len() {
+            anyhow::bail!("the continuation {continuation:?} adds no tokens to the prompt")
+        }
+        let mut state = State::new(1, &self.config, &self.device)?;
+        let budget = budget::Budget::new(None, None);
+        let logits = self.prefill(&tokens[..start], &mut state, &budget, None)?;
+        let mut logits = match logits {
+            Some(logits) => logits,
+            None => anyhow::bail!("cannot work on an empty prompt"),
+        };
+        let mut logprob = 0f64;
+        for end in start..tokens.len() {
+            let log_probs = logits.squeeze(0)?.squeeze(0)?.to_dtype(DType::F32)?;
+            let log_probs = candle_nn::ops::log_softmax(&log_probs, D::Minus1)?;
+            logprob += log_probs.get(tokens[end] as usize)?.to_scalar::<f32>()? as f64;
+            if end + 1 < tokens.len() {
+                let input = Tensor::new(&[[tokens[end]]], &self.device)?;
+                logits = self.model.forward(&input, &mut state)?;
+            }
+        }
+        Ok((logprob, tokens.len() - start))
+    }
+
+    fn tokenize(&mut self, text: &str) -> Result<Vec<(u32, String)>> {
+        let tokens = self.tokenizer.encode(text)?;
+        Ok(tokens
+            .iter()
+            .map(|&id| {
+                let bytes = self.tokenizer.decode_bytes(&[id]);
+                (id, String::from_utf8_lossy(&bytes).to_string())
+            })
+            .collect())
+    }
+
+    // State dibuat baru untuk setiap request, sehingga hanya sampler yang perlu dikembalikan ke seed awal
+    fn reset(&mut self) -> Result<()> {
+        self.logits_processor = LogitsProcessor::new(self.seed, self.temp, self.top_p);
         Ok(())
     }
 }
 
-// Enum untuk memilih model rwkv yang tersedia di Hugging Face
+// Enum untuk memilih model rwkv yang tersedia di Hugging Face, hanya menentukan repo dan file bawaan. Versi arsitektur
+// dideteksi dari config.json dan file bobot, lihat modul version
 #[derive(Parser, ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
 enum Which {
     Eagle7b,
@@ -162,6 +355,210 @@ impl Which {
     }
 }
 
+// Enum untuk memilih tipe data komputasi model non-kuantisasi
+#[derive(Clone, Copy, Debug, ValueEnum, PartialEq, Eq)]
+enum WhichDType {
+    F32,
+    F16,
+    Bf16,
+}
+
+impl WhichDType {
+    fn dtype(&self) -> DType {
+        match self {
+            Self::F32 => DType::F32,
+            Self::F16 => DType::F16,
+            Self::Bf16 => DType::BF16,
+        }
+    }
+}
+
+// Enum untuk subcommand selain generasi teks
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Quantize local safetensors weights into a GGUF file usable with --quantized.
+    Quantize(quantize::QuantizeArgs),
+    /// Re-run the generation recorded in a manifest and diff the output.
+    Replay(manifest::ReplayArgs),
+    /// Evaluate a multiple-choice JSONL task by the length-normalised log-likelihood of each
+    /// answer.
+    Eval(eval::EvalArgs),
+    /// Evaluate a free-form QA JSONL task (GSM8K style) by generating an answer and extracting the
+    /// final answer with regexes.
+    EvalGen(gen_eval::GenEvalArgs),
+    /// Stream a long document from a file or stdin into the recurrent state with progress and
+    /// checkpoints, then answer questions about it.
+    Ingest(ingest::IngestArgs),
+    /// Chat in the RWKV World format (`User: ...\n\nAssistant:`) keeping the recurrent state
+    /// between turns, with /save and /load of sessions.
+    Chat(session::ChatArgs),
+    /// Compare two per-question eval results (from eval --output) and fail when the accuracy
+    /// drops more than --max-drop.
+    Compare(compare::CompareArgs),
+}
+
+// Struct untuk argumen baris perintah
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Run on CPU rather than on GPU.
+    #[arg(long)]
+    cpu: bool,
+
+    /// Enable tracing (generates a trace-timestamp.json file).
+    #[arg(long)]
+    tracing: bool,
+
+    #[arg(long)]
+    prompt: Option<String>,
+
+    /// JSON file with a list of `{"role": ..., "content": ...}` messages, rendered with the chat
+    /// template and used as the prompt.
+    #[arg(long)]
+    messages: Option<String>,
+
+    /// The chat template used with --messages: a builtin (chatml, mistral-inst, rwkv, alpaca,
+    /// vicuna) or a jinja file. Defaults to the rwkv builtin, the RWKV repos have no
+    /// tokenizer_config.json with a `chat_template`.
+    #[arg(long)]
+    chat_template: Option<String>,
+
+    /// The temperature used to generate samples.
+    #[arg(long)]
+    temperature: Option<f64>,
+
+    /// Nucleus sampling probability cutoff.
+    #[arg(long)]
+    top_p: Option<f64>,
+
+    /// The seed to use when generating random samples.
+    #[arg(long, default_value_t = 299792458)]
+    seed: u64,
+
+    /// The length of the sample to generate (in tokens).
+    #[arg(long, short = 'n', default_value_t = 5000)]
+    sample_len: usize,
+
+    /// The number of prompt tokens processed in one forward pass, 1 feeds the prompt token by
+    /// token.
+    #[arg(long, default_value_t = 64)]
+    prefill_chunk: usize,
+
+    /// Do not print the prompt before the generated text.
+    #[arg(long)]
+    no_echo: bool,
+
+    /// Stop generating after this many seconds of wall-clock time, prompt processing included.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_time: Option<f64>,
+
+    /// Stop if processing the prompt takes longer than this many seconds.
+    #[arg(long, value_parser = budget::parse_seconds)]
+    max_prompt_time: Option<f64>,
+
+    /// The default repo and weight files when --model-id or --weight-files are not given. The
+    /// RWKV version (5 or 6) is detected from config.json and the weights.
+    #[arg(long, default_value = "world1b5")]
+    which: Which,
+
+    #[arg(long)]
+    model_id: Option<String>,
+
+    #[arg(long)]
+    revision: Option<String>,
+
+    #[arg(long)]
+    tokenizer: Option<String>,
+
+    #[arg(long)]
+    weight_files: Option<String>,
+
+    #[arg(long)]
+    config_file: Option<String>,
+
+    #[arg(long)]
+    quantized: bool,
+
+    /// The dtype used for the weights of the non-quantized model, logits are always upcast to
+    /// f32 before sampling. Falls back to f32 when the model ops do not support the dtype.
+    #[arg(long, value_enum, default_value = "f32")]
+    dtype: WhichDType,
+
+    /// Load the weights even when the estimated memory (weights plus state) is larger than the
+    /// available memory.
+    #[arg(long)]
+    no_memory_check: bool,
+
+    /// Write a run manifest (file hashes, features, args and output hash) to this file.
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// Penalty to be applied for repeating tokens, 1. means no penalty.
+    #[arg(long, default_value_t = 1.1)]
+    repeat_penalty: f32,
+
+    /// The context size to consider for the repeat penalty.
+    #[arg(long, default_value_t = 64)]
+    repeat_last_n: usize,
+
+    /// Serve newline-delimited JSON requests (generate, score, tokenize, reset) from stdin
+    /// instead of running a prompt. Responses and token events are written to stdout, logs to
+    /// stderr.
+    #[arg(long)]
+    worker: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// Fungsi untuk memilih device (CPU, CUDA, atau Metal)
+fn device(cpu: bool) -> Result<Device> {
+    if cpu {
+        Ok(Device::Cpu)
+    } else if cuda_is_available() {
+        Ok(Device::new_cuda(0)?)
+    } else if metal_is_available() {
+        Ok(Device::new_metal(0)?)
+    } else {
+        log!("Running on CPU, to run on GPU, build this example with `--features cuda`");
+        Ok(Device::Cpu)
+    }
+}
+
+// Fungsi untuk menjalankan forward dua token (jalur prefill) lalu satu token (jalur generasi) dengan state baru untuk
+// mengecek apakah tipe data model didukung
+fn probe(model: &Model, config: &Config, device: &Device) -> Result<()> {
+    let mut state = State::new(1, config, device)?;
+    for input in [&[0u32, 0][..], &[0]] {
+        let input = Tensor::new(input, device)?.unsqueeze(0)?;
+        let logits = model.forward(&input, &mut state)?.to_dtype(DType::F32)?;
+        logits.sum_all()?.to_scalar::<f32>()?;
+    }
+    Ok(())
+}
+
+// Fungsi untuk informasi build yang dicatat di manifest run: versi candle dari build.rs, fitur CPU dan fitur cargo yang aktif
+fn build_info() -> manifest::BuildInfo {
+    manifest::BuildInfo {
+        crate_name: env!("CARGO_PKG_NAME"),
+        crate_version: env!("CARGO_PKG_VERSION"),
+        candle_version: env!("CANDLE_VERSION"),
+        cpu_features: format!(
+            "avx: {}, neon: {}, simd128: {}, f16c: {}",
+            candle_core::utils::with_avx(),
+            candle_core::utils::with_neon(),
+            candle_core::utils::with_simd128(),
+            candle_core::utils::with_f16c()
+        ),
+        cargo_features: manifest::enabled_features(&[
+            ("accelerate", cfg!(feature = "accelerate")),
+            ("flash-attn", cfg!(feature = "flash-attn")),
+            ("mkl", cfg!(feature = "mkl")),
+            ("metal", cfg!(feature = "metal")),
+        ]),
+    }
+}
+
 // Implementasi fungsi main
 fn main() -> Result<()> {
     use tracing_chrome::ChromeLayerBuilder;
@@ -176,14 +573,34 @@ fn main() -> Result<()> {
     } else {
         None
     };
-    println!(
-        "avx: {}, neon: {}, simd128: {}, f16c: {}",
-        candle_core::utils::with_avx(),
-        candle_core::utils::with_neon(),
-        candle_core::utils::with_simd128(),
-        candle_core::utils::with_f16c()
-    );
-    println!(
+    // Jika subcommand diberikan, maka hanya menjalankan subcommand tersebut
+    match args.command {
+        Some(Command::Quantize(quantize_args)) => return quantize::run(quantize_args),
+        Some(Command::Replay(replay_args)) => return manifest::replay(replay_args),
+        Some(Command::Compare(compare_args)) => return compare::run(&compare_args),
+        Some(Command::Eval(_) | Command::EvalGen(_) | Command::Ingest(_) | Command::Chat(_))
+        | None => {}
+    }
+    let eval = args.command.is_some();
+    if args.worker && eval {
+        anyhow::bail!("eval, eval-gen, ingest and chat cannot be used with --worker")
+    }
+    if args.worker || eval {
+        if args.prompt.is_some() || args.messages.is_some() {
+            anyhow::bail!(
+                "--worker, eval, eval-gen, ingest and chat cannot be used with --prompt or --messages"
+            )
+        }
+        // Pada mode worker stdout hanya berisi response JSON, log dipindah ke stderr
+        if args.worker {
+            worker::activate();
+        }
+    } else if args.prompt.is_some() == args.messages.is_some() {
+        anyhow::bail!("exactly one of --prompt and --messages must be specified")
+    }
+    let build = build_info();
+    log!("{}", build.cpu_features);
+    log!(
         "temp: {:.2} repeat-penalty: {:.2} repeat-last-n: {}",
         args.temperature.unwrap_or(0.),
         args.repeat_penalty,
@@ -202,7 +619,7 @@ fn main() -> Result<()> {
     ));
 
     // Mendapatkan tokenizer dan konfigurasi model
-    let tokenizer = match args.tokenizer {
+    let tokenizer_filename = match args.tokenizer {
         Some(file) => std::path::PathBuf::from(file),
         None => api
             .model("lmz/candle-rwkv".to_string())
@@ -243,29 +660,88 @@ fn main() -> Result<()> {
             }
         }
     };
-    println!("retrieved the files in {:?}", start.elapsed());
+    log!("retrieved the files in {:?}", start.elapsed());
 
     // Inisialisasi tokenizer dan model
-    let tokenizer = Tokenizer::new(tokenizer)?;
+    let tokenizer = Tokenizer::new(&tokenizer_filename)?;
+
+    // Jika messages diberikan, pesan dirender dengan template chat dan dipakai sebagai prompt
+    let prompt = match (args.prompt, args.messages) {
+        (Some(prompt), _) => Some(prompt),
+        (None, Some(messages)) => {
+            let messages = chat::load_messages(messages)?;
+            // Repo RWKV hanya berisi vocab JSON tanpa tokenizer_config.json, sehingga tidak ada chat_template
+            // yang bisa dibaca dan template bawaan rwkv dipakai jika --chat-template tidak diberikan
+            let template = chat::resolve(
+                args.chat_template.as_deref(),
+                None,
+                chat::BuiltinTemplate::Rwkv,
+                "",
+            )?;
+            Some(template.render(&messages, true)?)
+        }
+        (None, None) => None,
+    };
     let start = std::time::Instant::now();
-    let config: Config = serde_json::from_slice(&std::fs::read(config_filename)?)?;
+    let config: Config = serde_json::from_slice(&std::fs::read(&config_filename)?)?;
+    let version = version::detect(&config_filename, &filenames)?;
+    log!("detected {version}");
     let device = device(args.cpu)?;
+    // Memori yang dibutuhkan diperkirakan dari header file bobot sebelum dimuat. Hanya di CPU, karena bobot di GPU
+    // tidak memakai RAM
+    if device.is_cpu() && !args.no_memory_check {
+        let state = memory::state_bytes(&config);
+        let (weights, suggestions) = if args.quantized {
+            (memory::gguf_bytes(&filenames[0])?, vec![])
+        } else {
+            let dtype = args.dtype.dtype();
+            let weights = memory::safetensors_elements(&filenames)? * dtype.size_in_bytes() as u64;
+            let mut suggestions = vec!["--quantized".to_string()];
+            if dtype == DType::F32 {
+                let half = memory::Estimate {
+                    weights: weights / 2,
+                    cache: state,
+                    cache_name: "state",
+                };
+                let bf16 = memory::gib(half.total());
+                suggestions.push(format!("--dtype bf16 (about {bf16})"));
+            }
+            (weights, suggestions)
+        };
+        let estimate = memory::Estimate {
+            weights,
+            cache: state,
+            cache_name: "state",
+        };
+        memory::check(&estimate, memory::available_bytes(), &suggestions)?;
+    }
     let model = if args.quantized {
         let filename = &filenames[0];
         let vb =
             candle_transformers::quantized_var_builder::VarBuilder::from_gguf(filename, &device)?;
-        match args.which {
-            Which::World1b5 | Which::World3b | Which::Eagle7b => Model::Q5(Q5::new(&config, vb)?),
-            Which::World6_1b6 => Model::Q6(Q6::new(&config, vb)?),
-        }
+        Model::new(&config, version, model::Weights::Quantized(vb))?
     } else {
-        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, DType::F32, &device)? };
-        match args.which {
-            Which::World1b5 | Which::World3b | Which::Eagle7b => Model::M5(M5::new(&config, vb)?),
-            Which::World6_1b6 => Model::M6(M6::new(&config, vb)?),
+        // Fungsi untuk memuat model non-kuantisasi dengan tipe data tertentu
+        let load = |dtype: DType| -> Result<Model> {
+            let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, &device)? };
+            Ok(Model::new(&config, version, model::Weights::Plain(vb))?)
+        };
+        let dtype = args.dtype.dtype();
+        let model = load(dtype)?;
+        // Jika tipe data bukan f32, dicoba satu forward untuk memastikan operasi didukung
+        match probe(&model, &config, &device) {
+            Ok(()) => model,
+            Err(err) if dtype != DType::F32 => {
+                log!(
+                    "warning: {dtype:?} is not supported on {device:?} ({err}), falling back to F32"
+                );
+                drop(model);
+                load(DType::F32)?
+            }
+            Err(err) => return Err(err),
         }
     };
-    println!("loaded the model in {:?}", start.elapsed());
+    log!("loaded the model in {:?}", start.elapsed());
 
     // Inisialisasi pipeline untuk generasi teks
     let mut pipeline = TextGeneration::new(
@@ -277,10 +753,71 @@ fn main() -> Result<()> {
         args.top_p,
         args.repeat_penalty,
         args.repeat_last_n,
+        args.prefill_chunk,
         &device,
     );
 
+    // Subcommand eval menilai setiap pilihan jawaban dan eval-gen menghasilkan jawaban bebas, keduanya lewat Backend
+    // yang sama dengan mode worker, ingest mengalirkan dokumen ke state lalu menjawab pertanyaan dan chat menyimpan state
+    // di antara giliran. Sidik jari model untuk journal, checkpoint dan sesi dihitung dari path dan ukuran file serta
+    // argumen yang mengubah hasil
+    let fingerprint = || -> Result<String> {
+        let mut files = filenames.clone();
+        files.push(tokenizer_filename.clone());
+        files.push(config_filename.clone());
+        let description = format!(
+            "which: {:?}, quantized: {}, dtype: {:?}, seed: {}, temperature: {:?}, top_p: {:?}, repeat_penalty: {}, repeat_last_n: {}",
+            args.which,
+            args.quantized,
+            args.dtype,
+            args.seed,
+            args.temperature,
+            args.top_p,
+            args.repeat_penalty,
+            args.repeat_last_n
+        );
+        journal::model_fingerprint(&description, &files)
+    };
+    match args.command {
+        Some(Command::Eval(eval_args)) => {
+            return eval::run(&mut pipeline, &eval_args, &fingerprint()?)
+        }
+        Some(Command::EvalGen(gen_args)) => {
+            return gen_eval::run(&mut pipeline, &gen_args, &fingerprint()?)
+        }
+        Some(Command::Ingest(ingest_args)) => {
+            return ingest::run(&mut pipeline, &ingest_args, &fingerprint()?)
+        }
+        Some(Command::Chat(chat_args)) => {
+            return session::run(&mut pipeline, &chat_args, &fingerprint()?)
+        }
+        _ => {}
+    }
+    // Pada mode worker model dimuat sekali lalu request dilayani sampai stdin ditutup
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => {
+            let limits = worker::Limits {
+                max_tokens: args.sample_len,
+                max_time: args.max_time,
+                max_prompt_time: args.max_prompt_time,
+            };
+            log!("worker ready, reading requests from stdin");
+            let (stdin, stdout) = (std::io::stdin(), std::io::stdout());
+            return worker::serve(&mut pipeline, limits, stdin.lock(), stdout.lock());
+        }
+    };
+
     // Jalankan generasi teks
-    pipeline.run(&args.prompt, args.sample_len)?;
+    budget::install_ctrlc_handler()?;
+    let budget = budget::Budget::new(args.max_time, args.max_prompt_time);
+    let (output, finish_reason) = pipeline.run(&prompt, args.sample_len, !args.no_echo, &budget)?;
+    // Tulis manifest run jika diminta
+    if let Some(path) = args.manifest {
+        let mut files = filenames.clone();
+        files.push(tokenizer_filename);
+        files.push(config_filename);
+        manifest::Manifest::new(&build, &files, &output, finish_reason)?.save(path)?;
+    }
     Ok(())
 }
\ No newline at end of file
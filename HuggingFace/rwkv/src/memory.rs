@@ -0,0 +1,66 @@
+// Modul untuk perkiraan memori model RWKV sebelum bobot dimuat. Pembacaan header safetensors dan pengecekan memori
+// diambil dari common::memory, di sini hanya ukuran State dan ukuran GGUF yang memakai candle dari git
+use anyhow::Result;
+use candle_core::quantized::gguf_file;
+use candle_core::DType;
+use std::path::Path;
+
+use crate::Config;
+
+pub use common::memory::{available_bytes, check, gib, safetensors_elements, Estimate};
+
+// Fungsi untuk menghitung ukuran tensor file GGUF, tensor terkuantisasi dimuat ke memori dalam blok aslinya
+pub fn gguf_bytes(path: &Path) -> Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let content = gguf_file::Content::read(&mut file)
+        .map_err(|err| anyhow::anyhow!("cannot read the GGUF header of {path:?}: {err}"))?;
+    Ok(content
+        .tensor_infos
+        .values()
+        .map(|info| {
+            let blocks = info.shape.elem_count() / info.ggml_dtype.block_size();
+            (blocks * info.ggml_dtype.type_size()) as u64
+        })
+        .sum())
+}
+
+// Fungsi untuk menghitung ukuran State satu sequence, selalu f32: dua vektor token shift dan state WKV setiap layer.
+// Ukurannya tetap, tidak bergantung pada panjang konteks
+pub fn state_bytes(config: &Config) -> u64 {
+    let per_layer = config.hidden_size * (2 + config.num_attention_heads);
+    (config.num_hidden_layers * per_layer * DType::F32.size_in_bytes()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    #[test]
+    fn state_size_matches_the_state_tensors() -> Result<()> {
+        // Config Eagle 7B, ukuran state harus sama dengan tensor yang dibuat State::new
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "vocab_size": 65536,
+            "hidden_size": 4096,
+            "num_hidden_layers": 32,
+            "attention_hidden_size": 4096,
+            "head_size": 64,
+            "intermediate_size": null,
+            "layer_norm_epsilon": 1e-5,
+            "rescale_every": 6
+        }))?;
+        let state = crate::State::new(1, &config, &Device::Cpu)?;
+        let elements: usize = state
+            .per_layer
+            .iter()
+            .map(|layer| {
+                layer.extract_key_value.elem_count()
+                    + layer.linear_attention.elem_count()
+                    + layer.feed_forward.elem_count()
+            })
+            .sum();
+        assert_eq!(state_bytes(&config), (elements * 4) as u64);
+        assert_eq!(state_bytes(&config), 32 * 4096 * 66 * 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,482 @@
+// Modul untuk model RWKV-5 dan RWKV-6 dari bobot safetensors atau GGUF. Forward satu token memakai operasi yang sama
+// dengan model candle (rwkv_v5, rwkv_v6 dan versi quantized-nya). Forward beberapa token sekaligus dipakai untuk
+// prefill: token shift memakai token sebelumnya di dalam urutan, dan WKV dihitung per potongan (chunked WKV) dengan
+// matmul, bukan satu langkah rekurensi per token. State akhirnya sama dengan memasukkan token satu per satu
+use candle_core::{IndexOp, Module, Result, Shape, Tensor, D};
+use candle_nn::{Embedding, GroupNorm, LayerNorm, VarBuilder};
+use candle_transformers::models::rwkv_v5::{Config, State};
+use candle_transformers::quantized_nn;
+use candle_transformers::quantized_var_builder::VarBuilder as QVarBuilder;
+
+use crate::version::Version;
+
+// Panjang potongan WKV, di dalam potongan peluruhan antar posisi dihitung sebagai tensor WKV_CHUNK x WKV_CHUNK per
+// head dan channel
+const WKV_CHUNK: usize = 32;
+
+// Enum untuk sumber bobot: safetensors lewat VarBuilder candle_nn atau GGUF lewat VarBuilder quantized
+#[derive(Clone)]
+pub enum Weights<'a> {
+    Plain(VarBuilder<'a>),
+    Quantized(QVarBuilder),
+}
+
+impl Weights<'_> {
+    fn pp(&self, name: impl ToString) -> Self {
+        match self {
+            Self::Plain(vb) => Self::Plain(vb.pp(name)),
+            Self::Quantized(vb) => Self::Quantized(vb.pp(name)),
+        }
+    }
+
+    // Tensor biasa, bobot GGUF didekuantisasi seperti pada model quantized candle
+    fn get(&self, shape: impl Into<Shape>, name: &str) -> Result<Tensor> {
+        match self {
+            Self::Plain(vb) => vb.get(shape, name),
+            Self::Quantized(vb) => vb.get(shape, name)?.dequantize(vb.device()),
+        }
+    }
+
+    fn linear(&self, in_dim: usize, out_dim: usize) -> Result<Linear> {
+        match self {
+            Self::Plain(vb) => Ok(Linear::Plain(candle_nn::linear_no_bias(
+                in_dim,
+                out_dim,
+                vb.clone(),
+            )?)),
+            Self::Quantized(vb) => Ok(Linear::Quantized(quantized_nn::linear_no_bias(
+                in_dim,
+                out_dim,
+                vb.clone(),
+            )?)),
+        }
+    }
+
+    fn layer_norm(&self, size: usize, eps: f64) -> Result<LayerNorm> {
+        match self {
+            Self::Plain(vb) => candle_nn::layer_norm(size, eps, vb.clone()),
+            Self::Quantized(vb) => quantized_nn::layer_norm(size, eps, vb.clone()),
+        }
+    }
+
+    fn group_norm(&self, num_groups: usize, num_channels: usize) -> Result<GroupNorm> {
+        match self {
+            Self::Plain(vb) => candle_nn::group_norm(num_groups, num_channels, 1e-5, vb.clone()),
+            Self::Quantized(_) => GroupNorm::new(
+                self.get(num_channels, "weight")?,
+                self.get(num_channels, "bias")?,
+                num_channels,
+                num_groups,
+                1e-5,
+            ),
+        }
+    }
+
+    fn embedding(&self, vocab_size: usize, hidden_size: usize) -> Result<Embedding> {
+        let embeddings = self.get((vocab_size, hidden_size), "weight")?;
+        Ok(Embedding::new(embeddings, hidden_size))
+    }
+}
+
+// Enum untuk layer linear tanpa bias, bobot biasa atau terkuantisasi
+#[derive(Debug, Clone)]
+enum Linear {
+    Plain(candle_nn::Linear),
+    Quantized(quantized_nn::Linear),
+}
+
+impl Module for Linear {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        match self {
+            Self::Plain(linear) => linear.forward(xs),
+            Self::Quantized(linear) => linear.forward(xs),
+        }
+    }
+}
+
+// Fungsi untuk token shift: setiap posisi dicampur dengan token sebelumnya, posisi pertama dengan token terakhir yang
+// disimpan di state. Untuk satu token hasilnya adalah state itu sendiri seperti pada model candle
+fn token_shift(last: &Tensor, xs: &Tensor) -> Result<Tensor> {
+    let last = if last.rank() == 2 {
+        last.unsqueeze(1)?
+    } else {
+        last.clone()
+    };
+    let t = xs.dim(1)?;
+    if t == 1 {
+        Ok(last)
+    } else {
+        Tensor::cat(&[&last, &xs.i((.., ..t - 1))?], 1)
+    }
+}
+
+// Fungsi untuk satu potongan WKV. r, k, v dan log_decay berukuran (b, h, t, s), bonus (time_faaaa) berukuran
+// (1, h, 1, s) dan state (b, h, s, s) dengan baris untuk channel key. Rekurensi per token adalah
+// out_t = r_t (state + bonus k_t^T v_t) lalu state = k_t^T v_t + decay_t state, sehingga dengan peluruhan kumulatif
+// A_t (jumlah log decay sebelum t) out_t = (r_t exp(A_t)) state_awal + jumlah_{j<t} (r_t k_j exp(A_t - A_{j+1})) v_j
+// + (r_t bonus k_t) v_t. Selisih A_t - A_{j+1} tidak pernah positif, sehingga exp-nya tidak overflow
+fn wkv_chunk(
+    r: &Tensor,
+    k: &Tensor,
+    v: &Tensor,
+    log_decay: &Tensor,
+    bonus: &Tensor,
+    state: &Tensor,
+) -> Result<(Tensor, Tensor)> {
+    let t = r.dim(2)?;
+    // inclusive_j = A_{j+1}, exclusive_t = A_t
+    let inclusive = log_decay.cumsum(2)?;
+    let exclusive = (&inclusive - log_decay)?;
+
+    let from_state = (r * exclusive.exp()?)?.matmul(state)?;
+
+    let idx = Tensor::arange(0u32, t as u32, r.device())?;
+    let causal = idx
+        .reshape((1, t))?
+        .broadcast_lt(&idx.reshape((t, 1))?)?
+        .to_dtype(r.dtype())?
+        .reshape((1, 1, t, t))?;
+    let decay = exclusive
+        .unsqueeze(3)?
+        .broadcast_sub(&inclusive.unsqueeze(2)?)?
+        .minimum(0f64)?
+        .exp()?;
+    let scores = r
+        .unsqueeze(3)?
+        .broadcast_mul(&k.unsqueeze(2)?)?
+        .mul(&decay)?
+        .sum(D::Minus1)?
+        .broadcast_mul(&causal)?;
+    let within = scores.matmul(v)?;
+
+    let current = (r * k)?
+        .broadcast_mul(bonus)?
+        .sum_keepdim(D::Minus1)?
+        .broadcast_mul(v)?;
+    let out = ((from_state + within)? + current)?;
+
+    // State setelah potongan: state awal meluruh sepanjang potongan, setiap k_j^T v_j meluruh sejak posisi j
+    let total = inclusive.narrow(2, t - 1, 1)?;
+    let carried = total.broadcast_sub(&inclusive)?.exp()?;
+    let state = (total.transpose(2, 3)?.exp()?.broadcast_mul(state)?
+        + (k * carried)?.transpose(2, 3)?.contiguous()?.matmul(v)?)?;
+    Ok((out, state))
+}
+
+// Struct untuk bobot RWKV-6 yang membuat token shift dan peluruhan bergantung pada data (LoRA time_mix dan time_decay)
+#[derive(Debug, Clone)]
+struct DataDependent {
+    time_mix_x: Tensor,
+    time_mix_w: Tensor,
+    time_mix_w1: Tensor,
+    time_mix_w2: Tensor,
+    time_decay_w1: Tensor,
+    time_decay_w2: Tensor,
+}
+
+// Struct untuk time mixing (attention linear) satu layer
+#[derive(Debug, Clone)]
+struct SelfAttention {
+    key: Linear,
+    receptance: Linear,
+    value: Linear,
+    gate: Linear,
+    output: Linear,
+    ln_x: GroupNorm,
+    time_mix_key: Tensor,
+    time_mix_value: Tensor,
+    time_mix_receptance: Tensor,
+    time_mix_gate: Tensor,
+    time_decay: Tensor,
+    time_faaaa: Tensor,
+    data_dependent: Option<DataDependent>,
+    layer_id: usize,
+    n_attn_heads: usize,
+}
+
+impl SelfAttention {
+    fn new(layer_id: usize, cfg: &Config, version: Version, vb: Weights) -> Result<Self> {
+        let hidden_size = cfg.hidden_size;
+        let attn_hidden_size = cfg.attention_hidden_size;
+        let n_attn_heads = hidden_size / cfg.head_size;
+        let mix = |name: &str| vb.get((1, 1, hidden_size), name);
+        let (time_decay, data_dependent) = match version {
+            Version::V5 => (vb.get((n_attn_heads, cfg.head_size), "time_decay")?, None),
+            Version::V6 => {
+                let data_dependent = DataDependent {
+                    time_mix_x: mix("time_mix_x")?,
+                    time_mix_w: mix("time_mix_w")?,
+                    time_mix_w1: vb.get((hidden_size, n_attn_heads * 5), "time_mix_w1")?,
+                    time_mix_w2: vb.get((5, n_attn_heads, hidden_size), "time_mix_w2")?,
+                    time_decay_w1: vb.get((hidden_size, n_attn_heads * 2), "time_decay_w1")?,
+                    time_decay_w2: vb.get((n_attn_heads * 2, hidden_size), "time_decay_w2")?,
+                };
+                (mix("time_decay")?, Some(data_dependent))
+            }
+        };
+        Ok(Self {
+            key: vb.pp("key").linear(hidden_size, attn_hidden_size)?,
+            receptance: vb.pp("receptance").linear(hidden_size, attn_hidden_size)?,
+            value: vb.pp("value").linear(hidden_size, attn_hidden_size)?,
+            gate: vb.pp("gate").linear(hidden_size, attn_hidden_size)?,
+            output: vb.pp("output").linear(attn_hidden_size, hidden_size)?,
+            ln_x: vb.pp("ln_x").group_norm(n_attn_heads, hidden_size)?,
+            time_mix_key: mix("time_mix_key")?,
+            time_mix_value: mix("time_mix_value")?,
+            time_mix_receptance: mix("time_mix_receptance")?,
+            time_mix_gate: mix("time_mix_gate")?,
+            time_decay,
+            time_faaaa: vb.get((n_attn_heads, cfg.head_size), "time_faaaa")?,
+            data_dependent,
+            layer_id,
+            n_attn_heads,
+        })
+    }
+
+    // Input receptance, key, value dan gate dari token shift, beserta log decay per posisi (b, t, hidden) untuk
+    // RWKV-6. RWKV-5 memakai interpolasi tetap, RWKV-6 menambah interpolasi dan peluruhan dari LoRA
+    fn mix(&self, xs: &Tensor, shifted: &Tensor) -> Result<([Tensor; 4], Option<Tensor>)> {
+        let lerp = |mix: &Tensor| xs.broadcast_mul(mix)? + shifted.broadcast_mul(&(1.0 - mix)?)?;
+        let dd = match self.data_dependent.as_ref() {
+            None => {
+                let inputs = [
+                    lerp(&self.time_mix_receptance)?,
+                    lerp(&self.time_mix_key)?,
+                    lerp(&self.time_mix_value)?,
+                    lerp(&self.time_mix_gate)?,
+                ];
+                return Ok((inputs, None));
+            }
+            Some(dd) => dd,
+        };
+        let (b, t, _) = xs.dims3()?;
+        let sx = (shifted - xs)?;
+        let xxx = (xs + sx.broadcast_mul(&dd.time_mix_x)?)?;
+        let xxx = xxx
+            .broadcast_matmul(&dd.time_mix_w1)?
+            .tanh()?
+            .reshape((b * t, 5, ()))?
+            .transpose(0, 1)?;
+        let xxx = xxx.matmul(&dd.time_mix_w2)?.reshape((5, b, t, ()))?;
+        let lerp =
+            |mix: &Tensor, i: usize| xs + sx.broadcast_mul(&mix.broadcast_add(&xxx.i(i)?)?)?;
+        let xw = lerp(&dd.time_mix_w, 0)?;
+        let inputs = [
+            lerp(&self.time_mix_receptance, 3)?,
+            lerp(&self.time_mix_key, 1)?,
+            lerp(&self.time_mix_value, 2)?,
+            lerp(&self.time_mix_gate, 4)?,
+        ];
+        let w = self.time_decay.broadcast_add(
+            &xw.broadcast_matmul(&dd.time_decay_w1)?
+                .tanh()?
+                .broadcast_matmul(&dd.time_decay_w2)?,
+        )?;
+        Ok((inputs, Some(w)))
+    }
+
+    fn forward(&self, xs: &Tensor, state: &mut State) -> Result<Tensor> {
+        let h = self.n_attn_heads;
+        let (b, t, s) = xs.dims3()?;
+        let s = s / h;
+        let layer = &mut state.per_layer[self.layer_id];
+        let shifted = token_shift(&layer.extract_key_value, xs)?;
+        let ([receptance, key, value, gate], w) = self.mix(xs, &shifted)?;
+        let key = self.key.forward(&key)?;
+        let value = self.value.forward(&value)?;
+        let receptance = self.receptance.forward(&receptance)?;
+        let gate = candle_nn::ops::silu(&self.gate.forward(&gate)?)?;
+        layer.extract_key_value = xs.i((.., t - 1))?;
+
+        let state_ = &layer.linear_attention;
+        let (out, state_) = if t == 1 {
+            // Satu langkah rekurensi, sama dengan model candle
+            let key = key.reshape((b, t, h, s))?.permute((0, 2, 3, 1))?;
+            let value = value.reshape((b, t, h, s))?.transpose(1, 2)?;
+            let receptance = receptance.reshape((b, t, h, s))?.transpose(1, 2)?;
+            let decay = match w {
+                Some(w) => w.reshape(((), 1, 1))?.reshape((h, (), 1))?,
+                None => self.time_decay.reshape(((), 1, 1))?.reshape((h, (), 1))?,
+            };
+            let decay = decay.exp()?.neg()?.exp()?;
+            let time_faaaa = self.time_faaaa.reshape(((), 1, 1))?.reshape((h, (), 1))?;
+            let at = key.contiguous()?.matmul(&value.contiguous()?)?;
+            let rhs = (time_faaaa.broadcast_mul(&at)? + state_)?;
+            let out = receptance.contiguous()?.matmul(&rhs)?.squeeze(2)?;
+            let state_ = (&at + decay.broadcast_mul(state_))?;
+            (out, state_)
+        } else {
+            let heads = |x: Tensor| x.reshape((b, t, h, s))?.transpose(1, 2)?.contiguous();
+            let receptance = heads(receptance)?;
+            let key = heads(key)?;
+            let value = heads(value)?;
+            let log_decay = match w {
+                Some(w) => heads(w.exp()?.neg()?)?,
+                None => self
+                    .time_decay
+                    .exp()?
+                    .neg()?
+                    .reshape((1, h, 1, s))?
+                    .broadcast_as((b, h, t, s))?
+                    .contiguous()?,
+            };
+            let bonus = self.time_faaaa.reshape((1, h, 1, s))?;
+            let mut state_ = state_.clone();
+            let mut outs = Vec::with_capacity(t.div_ceil(WKV_CHUNK));
+            for start in (0..t).step_by(WKV_CHUNK) {
+                let len = WKV_CHUNK.min(t - start);
+                let chunk = |x: &Tensor| x.narrow(2, start, len)?.contiguous();
+                let (out, next) = wkv_chunk(
+                    &chunk(&receptance)?,
+                    &chunk(&key)?,
+                    &chunk(&value)?,
+                    &chunk(&log_decay)?,
+                    &bonus,
+                    &state_,
+                )?;
+                outs.push(out);
+                state_ = next;
+            }
+            let out = Tensor::cat(&outs, 2)?.transpose(1, 2)?.contiguous()?;
+            (out, state_)
+        };
+        let out = out.reshape((b * t, h * s, 1))?;
+        let out = out.apply(&self.ln_x)?.reshape((b, t, h * s))?;
+        let out = (out * gate)?.apply(&self.output)?;
+        layer.linear_attention = state_;
+        Ok(out)
+    }
+}
+
+// Struct untuk channel mixing (feed forward) satu layer
+#[derive(Debug, Clone)]
+struct FeedForward {
+    time_mix_key: Tensor,
+    time_mix_receptance: Tensor,
+    key: Linear,
+    receptance: Linear,
+    value: Linear,
+    version: Version,
+    layer_id: usize,
+}
+
+impl FeedForward {
+    fn new(layer_id: usize, cfg: &Config, version: Version, vb: Weights) -> Result<Self> {
+        let int_size = cfg
+            .intermediate_size
+            .unwrap_or(((cfg.hidden_size as f64 * 3.5) as usize) / 32 * 32);
+        Ok(Self {
+            key: vb.pp("key").linear(cfg.hidden_size, int_size)?,
+            receptance: vb
+                .pp("receptance")
+                .linear(cfg.hidden_size, cfg.hidden_size)?,
+            value: vb.pp("value").linear(int_size, cfg.hidden_size)?,
+            time_mix_key: vb.get((1, 1, cfg.hidden_size), "time_mix_key")?,
+            time_mix_receptance: vb.get((1, 1, cfg.hidden_size), "time_mix_receptance")?,
+            version,
+            layer_id,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor, state: &mut State) -> Result<Tensor> {
+        let layer = &mut state.per_layer[self.layer_id];
+        let shifted = token_shift(&layer.feed_forward, xs)?;
+        let (key, receptance) = match self.version {
+            Version::V5 => {
+                let lerp =
+                    |mix: &Tensor| xs.broadcast_mul(mix)? + shifted.broadcast_mul(&(1.0 - mix)?)?;
+                (lerp(&self.time_mix_key)?, lerp(&self.time_mix_receptance)?)
+            }
+            Version::V6 => {
+                let sx = (shifted - xs)?;
+                let lerp = |mix: &Tensor| xs + sx.broadcast_mul(mix)?;
+                (lerp(&self.time_mix_key)?, lerp(&self.time_mix_receptance)?)
+            }
+        };
+        let key = key.apply(&self.key)?.relu()?.sqr()?;
+        let value = key.apply(&self.value)?;
+        let receptance = candle_nn::ops::sigmoid(&receptance.apply(&self.receptance)?)?;
+        layer.feed_forward = xs.i((.., xs.dim(1)? - 1))?;
+        receptance * value
+    }
+}
+
+// Struct untuk satu blok RWKV, pre_ln hanya ada di layer pertama
+#[derive(Debug, Clone)]
+struct Block {
+    pre_ln: Option<LayerNorm>,
+    ln1: LayerNorm,
+    ln2: LayerNorm,
+    attention: SelfAttention,
+    feed_forward: FeedForward,
+}
+
+impl Block {
+    fn new(layer_id: usize, cfg: &Config, version: Version, vb: Weights) -> Result<Self> {
+        let eps = cfg.layer_norm_epsilon;
+        let pre_ln = if layer_id == 0 {
+            Some(vb.pp("pre_ln").layer_norm(cfg.hidden_size, eps)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            pre_ln,
+            ln1: vb.pp("ln1").layer_norm(cfg.hidden_size, eps)?,
+            ln2: vb.pp("ln2").layer_norm(cfg.hidden_size, eps)?,
+            attention: SelfAttention::new(layer_id, cfg, version, vb.pp("attention"))?,
+            feed_forward: FeedForward::new(layer_id, cfg, version, vb.pp("feed_forward"))?,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor, state: &mut State) -> Result<Tensor> {
+        let xs = match self.pre_ln.as_ref() {
+            None => xs.clone(),
+            Some(pre_ln) => xs.apply(pre_ln)?,
+        };
+        let attention = self.attention.forward(&xs.apply(&self.ln1)?, state)?;
+        let xs = (xs + attention)?;
+        let feed_forward = self.feed_forward.forward(&xs.apply(&self.ln2)?, state)?;
+        xs + feed_forward
+    }
+}
+
+// Struct untuk model RWKV-5 atau RWKV-6
+#[derive(Debug, Clone)]
+pub struct Model {
+    embeddings: Embedding,
+    blocks: Vec<Block>,
+    ln_out: LayerNorm,
+    head: Linear,
+}
+
+impl Model {
+    pub fn new(cfg: &Config, version: Version, vb: Weights) -> Result<Self> {
+        let vb_m = vb.pp("rwkv");
+        let vb_b = vb_m.pp("blocks");
+        let blocks = (0..cfg.num_hidden_layers)
+            .map(|i| Block::new(i, cfg, version, vb_b.pp(i)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            embeddings: vb_m
+                .pp("embeddings")
+                .embedding(cfg.vocab_size, cfg.hidden_size)?,
+            blocks,
+            ln_out: vb_m.pp("ln_out").layer_norm(cfg.hidden_size, 1e-5)?,
+            head: vb.pp("head").linear(cfg.hidden_size, cfg.vocab_size)?,
+        })
+    }
+
+    // Memasukkan token (b, t) ke state dan mengembalikan logits token terakhir (b, 1, vocab). state.pos bertambah
+    // sebanyak token yang dimasukkan
+    pub fn forward(&self, xs: &Tensor, state: &mut State) -> Result<Tensor> {
+        let (_b_size, seq_len) = xs.dims2()?;
+        let mut xs = xs.apply(&self.embeddings)?;
+        for block in self.blocks.iter() {
+            xs = block.forward(&xs, state)?;
+        }
+        let xs = xs.narrow(1, seq_len - 1, 1)?;
+        let xs = xs.apply(&self.ln_out)?.apply(&self.head)?;
+        state.pos += seq_len;
+        Ok(xs)
+    }
+}
@@ -0,0 +1,121 @@
+// Modul untuk subcommand quantize model RWKV. Argumen dan pilihan tipe per tensor diambil dari common::quantize, di
+// sini hanya penulisan GGUF yang memakai candle dari git. Nama tensor dipertahankan karena model terkuantisasi
+// memakai nama yang sama
+use anyhow::Result;
+use std::collections::HashMap;
+
+use candle_core::quantized::{gguf_file, GgmlDType, QTensor}; // Untuk kuantisasi dan penulisan GGUF
+use candle_core::{DType, Device}; // Untuk operasi tensor
+
+pub use common::quantize::{QuantType, QuantizeArgs};
+
+// Fungsi untuk mengubah QuantType menjadi GgmlDType milik candle dari git
+fn dtype(ty: QuantType) -> GgmlDType {
+    match ty {
+        QuantType::F32 => GgmlDType::F32,
+        QuantType::F16 => GgmlDType::F16,
+        QuantType::Q4_0 => GgmlDType::Q4_0,
+        QuantType::Q4k => GgmlDType::Q4K,
+        QuantType::Q5k => GgmlDType::Q5K,
+        QuantType::Q8_0 => GgmlDType::Q8_0,
+    }
+}
+
+// Fungsi utama subcommand quantize
+pub fn run(args: QuantizeArgs) -> Result<()> {
+    let start = std::time::Instant::now();
+    let mut tensors = HashMap::new();
+    for file in args.weight_files.split(',') {
+        println!("reading {file}");
+        tensors.extend(candle_core::safetensors::load(file, &Device::Cpu)?);
+    }
+    let mut names = tensors.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+
+    // Kuantisasi setiap tensor sesuai tipe yang dipilih
+    let mut qtensors = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let tensor = tensors[name].to_dtype(DType::F32)?;
+        let dtype = dtype(common::quantize::tensor_type(name, tensor.dims(), &args));
+        println!("  {name} {:?} -> {dtype:?}", tensor.shape());
+        qtensors.push((name.as_str(), QTensor::quantize(&tensor, dtype)?));
+    }
+    let qtensors = qtensors
+        .iter()
+        .map(|(name, t)| (*name, t))
+        .collect::<Vec<_>>();
+
+    // Menulis file GGUF beserta metadata arsitektur
+    let architecture = gguf_file::Value::String("rwkv".to_string());
+    let file_type = gguf_file::Value::U32(args.quantization.file_type());
+    let metadata = [
+        ("general.architecture", &architecture),
+        ("general.file_type", &file_type),
+    ];
+    let mut out = std::fs::File::create(&args.out_file)?;
+    gguf_file::write(&mut out, &metadata, &qtensors)?;
+    println!(
+        "wrote {} tensors to {:?} in {:?}",
+        qtensors.len(),
+        args.out_file,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::TempDir;
+    use candle_core::Tensor;
+    use clap::ValueEnum;
+
+    #[test]
+    fn block_sizes_match_candle() {
+        for ty in QuantType::value_variants() {
+            assert_eq!(ty.block_size(), dtype(*ty).block_size(), "{ty:?}");
+        }
+    }
+
+    #[test]
+    fn file_type_is_written_as_ggml_id() -> Result<()> {
+        let dir = TempDir::new("quantize")?;
+        let weights = dir.join("model.safetensors");
+        let tensors = HashMap::from([
+            (
+                "layers.0.fc1.weight".to_string(),
+                Tensor::ones((4, 32), DType::F32, &Device::Cpu)?,
+            ),
+            (
+                "layers.0.fc1.bias".to_string(),
+                Tensor::ones(4, DType::F32, &Device::Cpu)?,
+            ),
+        ]);
+        candle_core::safetensors::save(&tensors, &weights)?;
+        for (quantization, file_type) in [(QuantType::F16, 1), (QuantType::Q8_0, 7)] {
+            let out_file = dir.join(format!("model-{file_type}.gguf"));
+            run(QuantizeArgs {
+                weight_files: weights.to_string_lossy().to_string(),
+                out_file: out_file.clone(),
+                quantization,
+                overrides: Vec::new(),
+            })?;
+            let mut file = std::fs::File::open(&out_file)?;
+            let content = gguf_file::Content::read(&mut file)?;
+            assert_eq!(content.metadata["general.file_type"].to_u32()?, file_type);
+            assert_eq!(
+                content.metadata["general.architecture"].to_string()?,
+                "rwkv"
+            );
+            assert_eq!(
+                content.tensor_infos["layers.0.fc1.weight"].ggml_dtype,
+                dtype(quantization)
+            );
+            assert_eq!(
+                content.tensor_infos["layers.0.fc1.bias"].ggml_dtype,
+                GgmlDType::F32
+            );
+        }
+        Ok(())
+    }
+}
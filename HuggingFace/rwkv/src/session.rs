@@ -0,0 +1,258 @@
+// Modul untuk subcommand chat dengan format percakapan RWKV World ("User: ...\n\nAssistant:"). State rekuren disimpan
+// di antara giliran sehingga riwayat tidak perlu dimasukkan ulang, dan sesi (state beserta transkrip) bisa disimpan
+// dan dimuat dengan /save dan /load
+use anyhow::Result;
+use clap::Args as ClapArgs;
+use serde_json::json;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chat::Message;
+use crate::{budget, ingest, state_file, worker, State, TextGeneration};
+
+// Awal giliran user, jawaban berhenti saat model mulai menulis giliran user berikutnya
+const USER_TURN: &str = "\n\nUser:";
+
+// Argumen untuk subcommand chat
+#[derive(ClapArgs, Debug)]
+pub struct ChatArgs {
+    /// A system prompt fed as `System: ...` before the first turn of a new session.
+    #[arg(long)]
+    system: Option<String>,
+
+    /// Session file loaded at start when it exists, and the default file of /save and /load.
+    #[arg(long)]
+    session: Option<PathBuf>,
+
+    /// The maximum number of tokens of every answer.
+    #[arg(long, default_value_t = 1024)]
+    max_tokens: usize,
+}
+
+// Fungsi untuk merapikan isi pesan seperti template chat rwkv: baris kosong memisahkan giliran, sehingga "\n\n" di
+// dalam pesan diganti satu baris baru
+fn normalize(content: &str) -> String {
+    let mut content = content.trim().to_string();
+    while content.contains("\n\n") {
+        content = content.replace("\n\n", "\n");
+    }
+    content
+}
+
+// Fungsi untuk memasukkan teks ke state per potongan prefill tanpa output, tidak dihentikan oleh Ctrl-C agar state
+// selalu lengkap
+fn feed_text(pipeline: &TextGeneration, state: &mut State, text: &str) -> Result<()> {
+    let tokens = pipeline.tokenizer.encode(text)?;
+    for chunk in tokens.chunks(pipeline.prefill_chunk) {
+        pipeline.forward_tokens(chunk, state)?;
+    }
+    Ok(())
+}
+
+// Struct untuk sesi chat: state setelah giliran terakhir, transkrip percakapan dan teks setelah jawaban terakhir yang
+// sudah masuk ke state (awal giliran user jika jawaban berhenti pada "\n\nUser:")
+pub struct Session {
+    pub state: State,
+    pub transcript: Vec<Message>,
+    pending: String,
+}
+
+impl Session {
+    // Membuat sesi baru, system prompt langsung dimasukkan ke state
+    pub fn new(pipeline: &TextGeneration, system: Option<&str>) -> Result<Self> {
+        let mut session = Self {
+            state: State::new(1, &pipeline.config, &pipeline.device)?,
+            transcript: vec![],
+            pending: String::new(),
+        };
+        if let Some(system) = system {
+            let content = normalize(system);
+            feed_text(pipeline, &mut session.state, &format!("System: {content}"))?;
+            session.transcript.push(Message {
+                role: "system".to_string(),
+                content,
+            });
+        }
+        Ok(session)
+    }
+
+    // Menjalankan satu giliran: pesan user dimasukkan ke state lalu jawaban dihasilkan sampai "\n\nUser:", EOS atau
+    // max_tokens. Jika token terakhir sudah melewati awal giliran user (contoh "\n\nUser: Hai"), state dibangun ulang
+    // dari state sebelum giliran agar tidak berisi teks yang tidak pernah ditulis user
+    pub fn turn(
+        &mut self,
+        pipeline: &mut TextGeneration,
+        message: &str,
+        max_tokens: usize,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<worker::Generation> {
+        let content = normalize(message);
+        let separator = if self.state.pos == 0 { "" } else { "\n\n" };
+        let text = format!("{separator}User: {content}\n\nAssistant:");
+        let prompt = text
+            .strip_prefix(self.pending.as_str())
+            .unwrap_or(text.as_str());
+        let before = state_file::clone_state(&self.state);
+        let stops = [USER_TURN.to_string()];
+        let (generation, rest) = ingest::answer(
+            pipeline,
+            &mut self.state,
+            prompt,
+            max_tokens,
+            &stops,
+            on_text,
+        )?;
+        if format!("{USER_TURN} ").starts_with(rest.as_str()) {
+            self.pending = rest;
+        } else {
+            self.state = before;
+            feed_text(
+                pipeline,
+                &mut self.state,
+                &format!("{prompt}{}", generation.text),
+            )?;
+            self.pending = String::new();
+        }
+        self.transcript.push(Message {
+            role: "user".to_string(),
+            content,
+        });
+        self.transcript.push(Message {
+            role: "assistant".to_string(),
+            content: generation.text.trim().to_string(),
+        });
+        Ok(generation)
+    }
+
+    // Menyimpan state, transkrip dan sidik jari model ke satu file safetensors
+    pub fn save(&self, path: &Path, model: &str) -> Result<()> {
+        let meta = json!({
+            "model": model,
+            "transcript": self.transcript,
+            "pending": self.pending,
+        });
+        state_file::save(path, &self.state, &meta)
+    }
+
+    // Memuat sesi yang disimpan dengan model yang sama. Checkpoint ingest juga bisa dimuat, sehingga percakapan
+    // dimulai setelah dokumen
+    pub fn load(path: &Path, pipeline: &TextGeneration, model: &str) -> Result<Self> {
+        let (state, meta) = state_file::load(path, &pipeline.config, &pipeline.device)?;
+        if meta["model"] != model {
+            anyhow::bail!("the session {path:?} was made with another model")
+        }
+        let transcript = match meta.get("transcript") {
+            Some(transcript) => serde_json::from_value(transcript.clone())?,
+            None => vec![],
+        };
+        Ok(Self {
+            state,
+            transcript,
+            pending: meta["pending"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+// Fungsi untuk menentukan file sesi dari argumen perintah atau --session
+fn session_path(argument: &str, args: &ChatArgs) -> Result<PathBuf> {
+    match (argument, args.session.as_ref()) {
+        ("", Some(path)) => Ok(path.clone()),
+        ("", None) => anyhow::bail!("no session file, pass one or use --session"),
+        (argument, _) => Ok(PathBuf::from(argument)),
+    }
+}
+
+// Fungsi untuk menjalankan perintah yang diawali "/", mengembalikan false jika chat harus selesai
+fn command(
+    line: &str,
+    session: &mut Session,
+    pipeline: &TextGeneration,
+    args: &ChatArgs,
+    model: &str,
+) -> Result<bool> {
+    let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+    match name {
+        "/save" => {
+            let path = session_path(argument.trim(), args)?;
+            session.save(&path, model)?;
+            log!("saved the session to {path:?}");
+        }
+        "/load" => {
+            let path = session_path(argument.trim(), args)?;
+            *session = Session::load(&path, pipeline, model)?;
+            log!(
+                "loaded {path:?}: {} messages, {} tokens",
+                session.transcript.len(),
+                session.state.pos
+            );
+        }
+        "/reset" => {
+            *session = Session::new(pipeline, args.system.as_deref())?;
+            log!("started a new session");
+        }
+        "/exit" | "/quit" => return Ok(false),
+        _ => log!("unknown command {name}, use /save [file], /load [file], /reset or /exit"),
+    }
+    Ok(true)
+}
+
+// Fungsi untuk menjalankan subcommand chat, model adalah sidik jari model yang dicatat di file sesi
+pub fn run(pipeline: &mut TextGeneration, args: &ChatArgs, model: &str) -> Result<()> {
+    let mut session = match args.session.as_ref() {
+        Some(path) if path.exists() => {
+            let session = Session::load(path, pipeline, model)?;
+            log!(
+                "loaded {path:?}: {} messages, {} tokens",
+                session.transcript.len(),
+                session.state.pos
+            );
+            session
+        }
+        _ => Session::new(pipeline, args.system.as_deref())?,
+    };
+    budget::install_ctrlc_handler()?;
+    log!("chat ready, commands: /save [file], /load [file], /reset, /exit");
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("\nUser: ");
+        std::io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('/') {
+            match command(line, &mut session, pipeline, args, model) {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(err) => {
+                    log!("error: {err}");
+                    continue;
+                }
+            }
+        }
+        // Ctrl-C hanya menghentikan jawaban ini, chat tetap berjalan
+        budget::clear_cancelled();
+        print!("\nAssistant:");
+        let generation = session.turn(pipeline, line, args.max_tokens, &mut |text| {
+            print!("{text}");
+            std::io::stdout().flush()?;
+            Ok(())
+        })?;
+        println!();
+        if !matches!(
+            generation.finish_reason,
+            budget::FinishReason::Stop | budget::FinishReason::Eos
+        ) {
+            log!(
+                "({} tokens, finish reason: {})",
+                generation.generated_tokens,
+                generation.finish_reason
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+// Modul untuk menyimpan dan memuat State RWKV sebagai safetensors (checkpoint ingest dan sesi chat). Setiap layer
+// punya tiga tensor, metadata JSON (posisi state, sidik jari model, dan lainnya) disimpan sebagai tensor u8
+use anyhow::Result;
+use candle_core::{Device, Tensor};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{Config, State};
+
+// Fungsi untuk menyalin state, tensor candle tidak diubah di tempat oleh forward sehingga salinan cukup berbagi tensor
+pub fn clone_state(state: &State) -> State {
+    State {
+        per_layer: state
+            .per_layer
+            .iter()
+            .map(
+                |layer| candle_transformers::models::rwkv_v5::StatePerLayer {
+                    extract_key_value: layer.extract_key_value.clone(),
+                    linear_attention: layer.linear_attention.clone(),
+                    feed_forward: layer.feed_forward.clone(),
+                },
+            )
+            .collect(),
+        pos: state.pos,
+    }
+}
+
+// Fungsi untuk menyimpan state dan metadata. File ditulis ke file sementara lalu di-rename, sehingga checkpoint lama
+// tetap utuh jika proses mati saat menulis
+pub fn save(path: &Path, state: &State, meta: &Value) -> Result<()> {
+    let mut tensors = HashMap::new();
+    for (index, layer) in state.per_layer.iter().enumerate() {
+        for (name, tensor) in [
+            ("extract_key_value", &layer.extract_key_value),
+            ("linear_attention", &layer.linear_attention),
+            ("feed_forward", &layer.feed_forward),
+        ] {
+            tensors.insert(
+                format!("layer.{index}.{name}"),
+                tensor.to_device(&Device::Cpu)?,
+            );
+        }
+    }
+    let mut meta = meta.clone();
+    meta["pos"] = state.pos.into();
+    let meta = meta.to_string().into_bytes();
+    tensors.insert(
+        "meta".to_string(),
+        Tensor::new(meta.as_slice(), &Device::Cpu)?,
+    );
+    let tmp = path.with_extension("tmp");
+    candle_core::safetensors::save(&tensors, &tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Fungsi untuk memuat state dan metadata, ukuran setiap tensor harus sama dengan state baru dari config model
+pub fn load(path: &Path, config: &Config, device: &Device) -> Result<(State, Value)> {
+    let mut tensors = candle_core::safetensors::load(path, device)?;
+    let meta = match tensors.remove("meta") {
+        Some(meta) => meta.to_device(&Device::Cpu)?.to_vec1::<u8>()?,
+        None => anyhow::bail!("{path:?} is not a state file"),
+    };
+    let meta: Value = serde_json::from_slice(&meta)?;
+    let mut state = State::new(1, config, device)?;
+    for (index, layer) in state.per_layer.iter_mut().enumerate() {
+        for (name, tensor) in [
+            ("extract_key_value", &mut layer.extract_key_value),
+            ("linear_attention", &mut layer.linear_attention),
+            ("feed_forward", &mut layer.feed_forward),
+        ] {
+            let key = format!("layer.{index}.{name}");
+            match tensors.remove(&key) {
+                Some(saved) if saved.dims() == tensor.dims() => *tensor = saved,
+                _ => anyhow::bail!("the state in {path:?} does not match this model ({key})"),
+            }
+        }
+    }
+    if !tensors.is_empty() {
+        anyhow::bail!("the state in {path:?} has more layers than this model")
+    }
+    state.pos = meta["pos"].as_u64().unwrap_or(0) as usize;
+    Ok((state, meta))
+}
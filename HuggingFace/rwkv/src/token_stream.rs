@@ -0,0 +1,20 @@
+// Modul untuk detokenisasi bertahap dengan tokenizer RWKV World, TokenStream sendiri ada di common::token_stream
+use anyhow::Result;
+use candle_transformers::models::rwkv_v5::Tokenizer;
+use common::token_stream::{Decode, TokenStream};
+
+// Pembungkus tokenizer RWKV World agar bisa mengimplementasikan Decode dari crate common
+#[derive(Clone, Copy)]
+pub struct WorldDecoder<'a>(pub &'a Tokenizer);
+
+// Tokenizer RWKV World menyimpan token sebagai byte mentah, sehingga satu karakter bisa terpotong di beberapa token
+impl Decode for WorldDecoder<'_> {
+    fn decode_lossy(&self, tokens: &[u32]) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.0.decode_bytes(tokens)).into_owned())
+    }
+}
+
+// Fungsi untuk membuat TokenStream dari tokenizer RWKV World
+pub fn stream(tokenizer: &Tokenizer) -> TokenStream<WorldDecoder<'_>> {
+    TokenStream::new(WorldDecoder(tokenizer))
+}
@@ -0,0 +1,211 @@
+// Modul untuk mendeteksi versi arsitektur RWKV dari config.json dan dari file bobot (metadata GGUF atau nama tensor
+// di header safetensors/GGUF), sehingga checkpoint RWKV-5 atau RWKV-6 World apa pun (ukuran lain atau hasil
+// fine-tune) bisa dimuat tanpa menambah varian --which. Hanya header yang dibaca, bobotnya tidak dimuat
+use anyhow::Result;
+use candle_core::quantized::gguf_file;
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use common::memory::safetensors_header;
+
+// Enum untuk versi RWKV yang didukung
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V5,
+    V6,
+}
+
+impl Version {
+    // Membuat versi dari nomor versi, versi selain 5 dan 6 ditolak dengan pesan yang jelas
+    pub fn new(number: u32) -> Result<Self> {
+        match number {
+            5 => Ok(Self::V5),
+            6 => Ok(Self::V6),
+            n => anyhow::bail!(
+                "RWKV-{n} checkpoints are not supported, only RWKV-5 and RWKV-6 can be loaded"
+            ),
+        }
+    }
+
+    // Nomor versi untuk ditampilkan
+    pub fn number(&self) -> u32 {
+        match self {
+            Self::V5 => 5,
+            Self::V6 => 6,
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RWKV-{}", self.number())
+    }
+}
+
+// Fungsi untuk membaca nomor versi setelah kata "rwkv", contoh "rwkv6", "Rwkv5ForCausalLM", "rwkv-6-world" atau
+// "rwkv_v5". None jika tidak ada angka setelahnya
+fn number_after_rwkv(text: &str) -> Option<u32> {
+    let text = text.to_lowercase();
+    let rest = &text[text.find("rwkv")? + 4..];
+    let rest = rest.trim_start_matches(['-', '_', ' ']);
+    let rest = rest.strip_prefix('v').unwrap_or(rest);
+    let digits = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+// Fungsi untuk membaca versi yang dinyatakan config.json: field version ("5.2", 6), model_type ("rwkv5", "rwkv6",
+// "rwkv" untuk RWKV-4 di transformers) atau architectures ("Rwkv6ForCausalLM")
+pub fn from_config(config: &Value) -> Option<u32> {
+    let version = match &config["version"] {
+        Value::Number(n) => n.as_f64().map(|n| n as u32),
+        Value::String(s) => {
+            let s = s.trim().trim_start_matches(['v', 'V']);
+            s.split('.').next().and_then(|n| n.parse().ok())
+        }
+        _ => None,
+    };
+    let model_type = config["model_type"].as_str().and_then(|t| match t {
+        "rwkv" => Some(4),
+        t => number_after_rwkv(t),
+    });
+    let architectures = config["architectures"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str())
+        .find_map(|a| match a {
+            "RwkvForCausalLM" => Some(4),
+            a => number_after_rwkv(a),
+        });
+    version.or(model_type).or(architectures)
+}
+
+// Fungsi untuk mengenali versi dari nama tensor. RWKV-6 punya time_decay_w1 dan time_mix_x/time_mix_w1 (time_maa_*
+// pada checkpoint asli) selain time_faaaa, RWKV-5 hanya punya time_faaaa, RWKV-4 punya time_first dan RWKV-7 punya
+// x_r dan a0
+pub fn from_tensor_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<u32> {
+    let mut found = None;
+    for name in names {
+        let version = if name.ends_with(".x_r") || name.ends_with(".a0") {
+            7
+        } else if name.contains(".time_maa_")
+            || name.ends_with(".time_mix_x")
+            || name.ends_with(".time_mix_w1")
+            || name.ends_with(".time_decay_w1")
+        {
+            6
+        } else if name.ends_with(".time_faaaa") {
+            5
+        } else if name.ends_with(".time_first") {
+            4
+        } else {
+            continue;
+        };
+        found = found.max(Some(version));
+    }
+    found
+}
+
+// Fungsi untuk mengecek apakah file diawali magic GGUF
+fn is_gguf(path: &Path) -> Result<bool> {
+    let mut magic = [0u8; 4];
+    let mut file = std::fs::File::open(path)?;
+    Ok(file.read_exact(&mut magic).is_ok() && &magic == b"GGUF")
+}
+
+// Fungsi untuk membaca versi dari satu file bobot: general.architecture GGUF jika berisi versi (contoh "rwkv6"),
+// lalu nama tensornya
+pub fn from_weights(path: &Path) -> Result<Option<u32>> {
+    if is_gguf(path)? {
+        let mut file = std::fs::File::open(path)?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|err| anyhow::anyhow!("cannot read the GGUF header of {path:?}: {err}"))?;
+        let architecture = match content.metadata.get("general.architecture") {
+            Some(gguf_file::Value::String(architecture)) => number_after_rwkv(architecture),
+            _ => None,
+        };
+        let names = content.tensor_infos.keys().map(|name| name.as_str());
+        Ok(architecture.or_else(|| from_tensor_names(names)))
+    } else {
+        let header = safetensors_header(path)?;
+        Ok(from_tensor_names(
+            header
+                .keys()
+                .filter(|name| *name != "__metadata__")
+                .map(|name| name.as_str()),
+        ))
+    }
+}
+
+// Fungsi untuk mendeteksi versi model dari file bobot lalu dari config.json. File bobot menentukan kelas model yang
+// bisa memuatnya, sehingga jika config menyatakan versi lain hanya diberi peringatan
+pub fn detect(config_file: &Path, weight_files: &[PathBuf]) -> Result<Version> {
+    let config: Value = serde_json::from_slice(&std::fs::read(config_file)?)?;
+    let declared = from_config(&config);
+    let mut weights = None;
+    for file in weight_files.iter() {
+        if let Some(version) = from_weights(file)? {
+            weights = Some((version, file));
+            break;
+        }
+    }
+    let number = match (declared, weights) {
+        (Some(declared), Some((version, file))) if declared != version => {
+            log!(
+                "warning: {config_file:?} declares RWKV-{declared} but the tensors of {file:?} are RWKV-{version}, loading RWKV-{version}"
+            );
+            version
+        }
+        (_, Some((version, _))) | (Some(version), None) => version,
+        (None, None) => anyhow::bail!(
+            "cannot detect the RWKV version: {config_file:?} has no model_type, architectures or version and the weights have no RWKV-5 or RWKV-6 tensors"
+        ),
+    };
+    Version::new(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn versions_are_detected_from_config_and_tensor_names() {
+        assert_eq!(from_config(&json!({"model_type": "rwkv5"})), Some(5));
+        assert_eq!(from_config(&json!({"model_type": "rwkv6"})), Some(6));
+        assert_eq!(from_config(&json!({"model_type": "rwkv"})), Some(4));
+        assert_eq!(
+            from_config(&json!({"architectures": ["Rwkv6ForCausalLM"]})),
+            Some(6)
+        );
+        assert_eq!(from_config(&json!({"version": "5.2"})), Some(5));
+        assert_eq!(from_config(&json!({"version": 6})), Some(6));
+        assert_eq!(from_config(&json!({"hidden_size": 2048})), None);
+        assert_eq!(number_after_rwkv("rwkv-6-world-1b6"), Some(6));
+        assert_eq!(number_after_rwkv("rwkv_v5"), Some(5));
+        assert_eq!(number_after_rwkv("rwkv"), None);
+
+        let v5 = [
+            "blocks.0.attention.time_faaaa",
+            "blocks.0.attention.key.weight",
+        ];
+        assert_eq!(from_tensor_names(v5), Some(5));
+        let v6 = [
+            "blocks.0.attention.time_faaaa",
+            "blocks.0.attention.time_mix_x",
+        ];
+        assert_eq!(from_tensor_names(v6), Some(6));
+        assert_eq!(from_tensor_names(["blocks.0.att.time_maa_x"]), Some(6));
+        assert_eq!(from_tensor_names(["blocks.0.att.time_first"]), Some(4));
+        assert_eq!(from_tensor_names(["blocks.0.att.x_r"]), Some(7));
+        assert_eq!(from_tensor_names(["emb.weight"]), None);
+
+        assert_eq!(Version::new(6).unwrap(), Version::V6);
+        let err = Version::new(7).unwrap_err().to_string();
+        assert!(err.contains("RWKV-7"), "{err}");
+    }
+}